@@ -0,0 +1,64 @@
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+#import bevy_pbr::mesh_functions
+
+#import first_voxel_engine::packed_vertex
+
+struct ChunkDebugMaterial {
+    color: vec4<f32>,
+    block_size: vec3<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: ChunkDebugMaterial;
+
+#ifdef PACKED_VERTEX
+struct Vertex {
+    @location(0) packed: vec2<u32>,
+};
+#else
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+#endif
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) ao: f32,
+    @location(2) @interpolate(flat) layer: u32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+#ifdef PACKED_VERTEX
+    let unpacked = unpack_vertex(vertex.packed);
+    let position = (vec3<f32>(unpacked.corner) - 0.5) * material.block_size;
+    let normal = unpacked.normal;
+    let ao = unpacked.ao;
+    let layer = unpacked.layer;
+#else
+    let position = vertex.position;
+    let normal = vertex.normal;
+    let ao = 1.0;
+    let layer = 0u;
+#endif
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(mesh.model, vec4<f32>(position, 1.0));
+    out.world_normal = mesh_normal_local_to_world(normal);
+    out.ao = ao;
+    out.layer = layer;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef SHOW_NORMALS
+    return vec4<f32>(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+#else
+    // Shades wireframes by ambient occlusion, so that its levels can be checked.
+    return vec4<f32>(material.color.rgb * mix(0.25, 1.0, in.ao), material.color.a);
+#endif
+}
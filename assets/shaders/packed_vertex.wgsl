@@ -0,0 +1,37 @@
+#define_import_path first_voxel_engine::packed_vertex
+
+// A vertex of a `MeshFormat::Packed` mesh, decoded from its two words.
+struct PackedVertex {
+    // The corner position in blocks.
+    corner: vec3<u32>,
+    normal: vec3<f32>,
+    // From 0.0 for fully occluded to 1.0 for unoccluded.
+    ao: f32,
+    layer: u32,
+};
+
+// Face indices follow the bit order of `Face` in `block.rs`.
+fn face_normal(face: u32) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(0.0, 0.0, 1.0); }
+        case 1u: { return vec3<f32>(0.0, 0.0, -1.0); }
+        case 2u: { return vec3<f32>(1.0, 0.0, 0.0); }
+        case 3u: { return vec3<f32>(-1.0, 0.0, 0.0); }
+        case 4u: { return vec3<f32>(0.0, 1.0, 0.0); }
+        default: { return vec3<f32>(0.0, -1.0, 0.0); }
+    }
+}
+
+// Must match `PackedVertex::unpack` in `mesh_data.rs`.
+fn unpack_vertex(packed: vec2<u32>) -> PackedVertex {
+    var unpacked: PackedVertex;
+    unpacked.corner = vec3<u32>(
+        packed.x & 0xffu,
+        (packed.x >> 8u) & 0xffu,
+        (packed.x >> 16u) & 0xffu,
+    );
+    unpacked.normal = face_normal((packed.x >> 24u) & 0x7u);
+    unpacked.ao = f32((packed.x >> 27u) & 0x3u) / 3.0;
+    unpacked.layer = packed.y & 0xffffu;
+    return unpacked;
+}
@@ -0,0 +1,121 @@
+//! Times generating and meshing chunks in a headless app, and gathering the neighbors a chunk is
+//! meshed against by cloning whole chunks, as meshing used to, against extracting their borders.
+//! Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use bevy::diagnostic::{
+  DiagnosticId,
+  Diagnostics,
+};
+use bevy::math::{
+  ivec3,
+  uvec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  Block,
+  BlockKind,
+  Chunk,
+  ChunkGenerated,
+  ChunkLoadingPoint,
+  ChunkWorldConfig,
+  ChunksPlugin,
+  Face,
+  TerrainGenerator,
+};
+use first_voxel_engine::mesh_data::MeshFormat;
+
+const TERRAIN_HEIGHT: u32 = 16;
+/// Frames the loading point moves one chunk along x, loading and meshing a new slab each time.
+/// Diagnostics keep the last 20 measurements, so this averages all of them.
+const STEPS: usize = 20;
+const GATHER_ITERATIONS: u32 = 1000;
+
+fn main() {
+  let config = ChunkWorldConfig {
+    chunk_size: uvec3(16, 16, 16),
+    block_size: vec3(0.5, 0.5, 0.5),
+    mesh_format: MeshFormat::Packed,
+    history_len: 64,
+  };
+  bench_loading(&config);
+  bench_gathering_neighbors(&config);
+}
+
+/// Moves a loading point across the terrain in a headless app, with just enough assets to mesh
+/// without a renderer, and reports the time per chunk that `ChunksPlugin` measures.
+fn bench_loading(config: &ChunkWorldConfig) {
+  let mut app = App::new();
+  app
+    .add_plugins(MinimalPlugins)
+    .add_plugin(AssetPlugin::default())
+    .add_asset::<Shader>()
+    .add_asset::<Mesh>()
+    .add_asset::<Image>()
+    .add_asset::<StandardMaterial>()
+    .add_plugin(
+      ChunksPlugin::new(config.clone()).with_generator(TerrainGenerator::new(0, TERRAIN_HEIGHT)),
+    );
+  let radius = 4.0 * config.chunk_size.x as f32 * config.block_size.x;
+  let clp = app
+    .world
+    .spawn((Transform::default(), ChunkLoadingPoint { radius, lod_radii: [f32::INFINITY; 3] }))
+    .id();
+  app.update();
+
+  let step = config.chunk_size.x as f32 * config.block_size.x;
+  let mut generated = 0;
+  let start = Instant::now();
+  for _ in 0..STEPS {
+    app.world.get_mut::<Transform>(clp).unwrap().translation.x += step;
+    app.update();
+    let events = app.world.resource::<Events<ChunkGenerated>>();
+    generated += events.iter_current_update_events().count();
+  }
+  let elapsed = start.elapsed();
+
+  let diagnostics = app.world.resource::<Diagnostics>();
+  let average = |id: DiagnosticId| diagnostics.get(id).and_then(|diagnostic| diagnostic.average());
+  println!(
+    "Loading a slab of {} chunks of size {} per frame, {STEPS} times",
+    generated / STEPS,
+    config.chunk_size
+  );
+  for (name, id) in
+    [("generate", ChunksPlugin::GENERATION_TIME), ("mesh", ChunksPlugin::MESHING_TIME)]
+  {
+    println!("{name:>10}: {:.3} ms per chunk", average(id).unwrap_or(0.0));
+  }
+  println!("{:>10}: {:?} per frame", "total", elapsed / STEPS as u32);
+}
+
+fn bench_gathering_neighbors(config: &ChunkWorldConfig) {
+  // Enough chunks that they do not all fit in cache, like the dirty chunks of a busy frame.
+  let generator = TerrainGenerator::new(0, TERRAIN_HEIGHT);
+  let chunks =
+    (0..64).map(|x| config.generate_chunk(&generator, ivec3(x, 0, 0))).collect::<Vec<_>>();
+  let time = |name: &str, gather: &dyn Fn(&Chunk)| {
+    let start = Instant::now();
+    for i in 0..GATHER_ITERATIONS {
+      gather(&chunks[i as usize % chunks.len()]);
+    }
+    println!("{name:>10}: {:?} per chunk", start.elapsed() / GATHER_ITERATIONS);
+  };
+
+  println!("Gathering 6 neighbors of {} chunks, {GATHER_ITERATIONS} times", config.chunk_size);
+  time("clone", &|chunk| {
+    black_box(Face::all().iter().map(|_| chunk.clone()).collect::<Vec<_>>());
+  });
+  time("border", &|chunk| {
+    black_box(Face::all().iter().map(|face| chunk.border(face)).collect::<Vec<_>>());
+  });
+
+  let chunk = &chunks[0];
+  let clone_bytes = 6 * chunk.blocks.len() * std::mem::size_of::<Block>();
+  let border_bytes = Face::all().iter().map(|face| chunk.border(face).len()).sum::<usize>()
+    * std::mem::size_of::<Option<BlockKind>>();
+  println!("Copied per chunk: {clone_bytes} bytes cloning, {border_bytes} bytes of borders");
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{
+  Deserialize,
+  Serialize,
+};
+
+/// Something inputs are bound to through an `ActionMap`, and read as `Input<A>` once its
+/// `ActionsPlugin` is added.
+pub trait InputAction:
+  Copy + Eq + Ord + Hash + Send + Sync + Serialize + DeserializeOwned + 'static
+{
+  /// The bindings of the default `ActionMap`.
+  fn default_bindings() -> Vec<(Self, Vec<Binding>)>;
+}
+
+/// The actions the engine's own plugins read, bound by `ChunksPlugin::with_actions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+  ToggleWireframe,
+  ToggleNormals,
+  ToggleChunkBounds,
+}
+
+impl InputAction for Action {
+  fn default_bindings() -> Vec<(Action, Vec<Binding>)> {
+    vec![
+      (Action::ToggleWireframe, vec![Binding::Key(KeyCode::L)]),
+      (Action::ToggleNormals, vec![Binding::Key(KeyCode::K)]),
+      (Action::ToggleChunkBounds, vec![Binding::Key(KeyCode::J)]),
+    ]
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+  Key(KeyCode),
+  Mouse(MouseButton),
+  /// Pressed when the button is pressed on any connected gamepad.
+  Gamepad(GamepadButtonType),
+}
+
+/// The bindings triggering each action, stored as RON. Actions left out of a file are unbound.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ActionMap<A: InputAction = Action> {
+  pub bindings: BTreeMap<A, Vec<Binding>>,
+}
+
+impl<A: InputAction> Default for ActionMap<A> {
+  fn default() -> ActionMap<A> {
+    ActionMap { bindings: A::default_bindings().into_iter().collect() }
+  }
+}
+
+impl<A: InputAction> ActionMap<A> {
+  pub fn load(path: &Path) -> io::Result<ActionMap<A>> {
+    ron::from_str(&fs::read_to_string(path)?)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    fs::write(path, ron)
+  }
+}
+
+/// Updates `Input<A>` from the `ActionMap<A>` each frame, before any `Update` system reads it.
+/// Keeps an `ActionMap<A>` inserted before the plugin is added, and uses the default one otherwise.
+pub struct ActionsPlugin<A = Action>(PhantomData<fn() -> A>);
+
+impl<A: InputAction> ActionsPlugin<A> {
+  pub fn new() -> ActionsPlugin<A> { ActionsPlugin(PhantomData) }
+}
+
+impl<A: InputAction> Default for ActionsPlugin<A> {
+  fn default() -> ActionsPlugin<A> { ActionsPlugin::new() }
+}
+
+impl<A: InputAction> Plugin for ActionsPlugin<A> {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<ActionMap<A>>()
+      .init_resource::<Input<A>>()
+      .add_system(update_actions::<A>.in_base_set(CoreSet::PreUpdate).after(InputSystem));
+  }
+}
+
+fn update_actions<A: InputAction>(
+  action_map: Res<ActionMap<A>>,
+  mut actions: ResMut<Input<A>>,
+  keys: Res<Input<KeyCode>>,
+  mouse_buttons: Res<Input<MouseButton>>,
+  gamepad_buttons: Res<Input<GamepadButton>>,
+  gamepads: Res<Gamepads>,
+) {
+  actions.clear();
+  for (action, bindings) in &action_map.bindings {
+    let pressed = bindings.iter().any(|binding| match binding {
+      Binding::Key(key) => keys.pressed(*key),
+      Binding::Mouse(button) => mouse_buttons.pressed(*button),
+      Binding::Gamepad(button_type) => gamepads
+        .iter()
+        .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, *button_type))),
+    });
+    if pressed {
+      actions.press(*action);
+    } else {
+      actions.release(*action);
+    }
+  }
+}
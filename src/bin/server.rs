@@ -0,0 +1,79 @@
+//! `server [--addr <addr>] [--seed <seed>]`: runs the world without a window and streams it to
+//! clients started with `--connect <addr>`.
+
+use std::io;
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerSettings;
+use bevy::log::LogPlugin;
+use bevy::math::{
+  uvec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  ChunkWorldConfig,
+  ChunksPlugin,
+  TerrainGenerator,
+};
+use first_voxel_engine::mesh_data::MeshFormat;
+use first_voxel_engine::net::ServerPlugin;
+use rand::Rng;
+
+/// How far peaks rise above and valleys sink below y = 0, in blocks.
+const TERRAIN_HEIGHT: u32 = 16;
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:7878";
+
+fn main() {
+  let args = std::env::args().skip(1).collect::<Vec<String>>();
+  if let Err(error) = serve(&args) {
+    eprintln!("error: {error}");
+    std::process::exit(1);
+  }
+}
+
+fn serve(args: &[String]) -> io::Result<()> {
+  let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+
+  let mut addr = DEFAULT_SERVER_ADDR.to_string();
+  let mut seed = rand::thread_rng().gen();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--addr" => addr = args.next().ok_or_else(|| invalid("--addr expects an address"))?.clone(),
+      "--seed" => {
+        seed = args
+          .next()
+          .and_then(|seed| seed.parse().ok())
+          .ok_or_else(|| invalid("--seed expects an unsigned integer"))?
+      }
+      _ => return Err(invalid("usage: server [--addr <addr>] [--seed <seed>]")),
+    }
+  }
+
+  let server = ServerPlugin::bind(addr.as_str())?;
+  println!("Listening on {} with seed {seed}", server.local_addr()?);
+  App::new()
+    // Read when `MinimalPlugins` is built, so it goes first.
+    .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / 60.0)))
+    .add_plugins(MinimalPlugins)
+    .add_plugin(LogPlugin::default())
+    .add_plugin(
+      ChunksPlugin::new(chunk_world_config())
+        .with_generator(TerrainGenerator::new(seed, TERRAIN_HEIGHT))
+        .with_meshing(false),
+    )
+    .add_plugin(server)
+    .run();
+  Ok(())
+}
+
+/// Clients refuse a server whose chunk size differs from theirs, so this matches the demo's.
+fn chunk_world_config() -> ChunkWorldConfig {
+  ChunkWorldConfig {
+    chunk_size: uvec3(16, 16, 16),
+    block_size: vec3(0.5, 0.5, 0.5),
+    mesh_format: MeshFormat::Packed,
+    history_len: 64,
+  }
+}
@@ -0,0 +1,216 @@
+use std::cmp::Reverse;
+use std::f32::consts::TAU;
+
+use bevy::math::{
+  ivec3,
+  vec3,
+};
+use bevy::prelude::{
+  IVec3,
+  Mesh,
+  Query,
+  Resource,
+  Vec3,
+};
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::utils::HashMap;
+use bracket_noise::prelude::{
+  FastNoise,
+  NoiseType,
+};
+
+use crate::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use crate::mesh_data::{
+  MeshData,
+  MeshDataInsert,
+};
+
+const OUTLINE_SEGMENTS: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+  Sphere,
+  /// An axis-aligned cube.
+  Box,
+  /// A vertical cylinder as tall as it is wide.
+  Cylinder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrushMode {
+  Fill(BlockKind),
+  Erase,
+  /// Fills blocks surrounded mostly by solid blocks and clears the rest.
+  Smooth,
+  /// Shifts each column up or down by up to `amplitude` blocks following 2D noise.
+  Displace {
+    amplitude: f32,
+    frequency: f32,
+    seed: u64,
+  },
+}
+
+/// A world editing tool. Each stroke is queued as a single `ChunkWorld::set_blocks` batch, so every
+/// affected chunk is remeshed once and the stroke is undone as a whole.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+  pub shape: BrushShape,
+  pub mode: BrushMode,
+  /// Radius, or half extent for boxes, in world units.
+  pub radius: f32,
+}
+
+impl Brush {
+  /// The positions of the blocks whose centers the brush covers when centered at `center`, in world
+  /// space.
+  pub fn blocks(&self, chunk_world_config: &ChunkWorldConfig, center: Vec3) -> Vec<IVec3> {
+    let min = chunk_world_config.world_to_block(center - self.radius);
+    let max = chunk_world_config.world_to_block(center + self.radius);
+    let mut blocks = Vec::new();
+    for x in min.x..=max.x {
+      for y in min.y..=max.y {
+        for z in min.z..=max.z {
+          let pos = ivec3(x, y, z);
+          let offset = chunk_world_config.block_to_world(pos) - center;
+          let inside = match self.shape {
+            BrushShape::Sphere => offset.length() <= self.radius,
+            BrushShape::Box => offset.abs().max_element() <= self.radius,
+            BrushShape::Cylinder => {
+              offset.x.hypot(offset.z) <= self.radius && offset.y.abs() <= self.radius
+            }
+          };
+          if inside {
+            blocks.push(pos);
+          }
+        }
+      }
+    }
+    blocks
+  }
+
+  /// Queues the edits of one stroke centered at `center`, in world space. Smoothing and
+  /// displacement read the loaded blocks as they are before the stroke.
+  pub fn stroke(
+    &self,
+    chunk_world: &mut ChunkWorld,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    center: Vec3,
+  ) {
+    let voxel_at = |pos: IVec3| chunk_world.get_voxel(chunk_world_config, chunks, pos).flatten();
+
+    let blocks = self.blocks(chunk_world_config, center);
+    let edits = match self.mode {
+      BrushMode::Fill(kind) => blocks.into_iter().map(|pos| (pos, Some(kind))).collect(),
+      BrushMode::Erase => blocks.into_iter().map(|pos| (pos, None)).collect(),
+      BrushMode::Smooth => blocks
+        .into_iter()
+        .map(|pos| {
+          let mut kinds = HashMap::<BlockKind, u32>::new();
+          for x in -1..=1 {
+            for y in -1..=1 {
+              for z in -1..=1 {
+                if let Some(kind) = voxel_at(pos + ivec3(x, y, z)) {
+                  *kinds.entry(kind).or_default() += 1;
+                }
+              }
+            }
+          }
+          // Majority of the 27 blocks in the 3x3x3 neighborhood, the block itself included. Ties
+          // keep the block if it is among the most common kinds, or else take the lowest kind.
+          let current = voxel_at(pos);
+          let voxel = (kinds.values().sum::<u32>() > 13)
+            .then(|| {
+              kinds
+                .into_iter()
+                .max_by_key(|(kind, count)| (*count, Some(*kind) == current, Reverse(kind.0)))
+                .map(|(kind, _)| kind)
+            })
+            .flatten();
+          (pos, voxel)
+        })
+        .collect(),
+      BrushMode::Displace { amplitude, frequency, seed } => {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::Simplex);
+        noise.set_frequency(frequency);
+        blocks
+          .into_iter()
+          .map(|pos| {
+            let shift = (noise.get_noise(pos.x as f32, pos.z as f32) * amplitude).round() as i32;
+            (pos, voxel_at(pos - ivec3(0, shift, 0)))
+          })
+          .collect::<Vec<_>>()
+      }
+    };
+    chunk_world.set_blocks(edits);
+  }
+
+  /// A line mesh tracing the brush's shape around the origin, to preview it before a stroke.
+  pub fn outline(&self) -> Mesh {
+    let mut mesh_data =
+      MeshData { primitive_topology: PrimitiveTopology::LineList, ..Default::default() };
+    let mut add_lines = |points: Vec<Vec3>, indices: Vec<u32>| {
+      mesh_data.insert(
+        points.into_iter().map(|point| (point.into(), [0.0; 3], [0.0; 2])).collect(),
+        indices,
+        MeshDataInsert::INDICES_OFFSET,
+      );
+    };
+    let circle = |point: fn(f32, f32) -> Vec3| {
+      (0..OUTLINE_SEGMENTS)
+        .map(|i| {
+          let (sin, cos) = (i as f32 / OUTLINE_SEGMENTS as f32 * TAU).sin_cos();
+          point(sin, cos)
+        })
+        .collect::<Vec<Vec3>>()
+    };
+    let loop_indices =
+      (0..OUTLINE_SEGMENTS).flat_map(|i| [i, (i + 1) % OUTLINE_SEGMENTS]).collect::<Vec<u32>>();
+
+    let r = self.radius;
+    match self.shape {
+      BrushShape::Sphere => {
+        for point in [
+          (|s, c| vec3(s, c, 0.0)) as fn(f32, f32) -> Vec3,
+          |s, c| vec3(s, 0.0, c),
+          |s, c| vec3(0.0, s, c),
+        ] {
+          add_lines(circle(point).into_iter().map(|p| p * r).collect(), loop_indices.clone());
+        }
+      }
+      BrushShape::Box => {
+        let corners =
+          (0..8).map(|i| vec3([-r, r][i & 1], [-r, r][i >> 1 & 1], [-r, r][i >> 2 & 1])).collect();
+        #[rustfmt::skip]
+        let edges = vec![
+          0, 1, 2, 3, 4, 5, 6, 7,
+          0, 2, 1, 3, 4, 6, 5, 7,
+          0, 4, 1, 5, 2, 6, 3, 7,
+        ];
+        add_lines(corners, edges);
+      }
+      BrushShape::Cylinder => {
+        for y in [-r, r] {
+          add_lines(
+            circle(|s, c| vec3(s, 0.0, c)).into_iter().map(|p| p * r + vec3(0.0, y, 0.0)).collect(),
+            loop_indices.clone(),
+          );
+        }
+        add_lines(
+          [vec3(r, 0.0, 0.0), vec3(-r, 0.0, 0.0), vec3(0.0, 0.0, r), vec3(0.0, 0.0, -r)]
+            .into_iter()
+            .flat_map(|p| [p - vec3(0.0, r, 0.0), p + vec3(0.0, r, 0.0)])
+            .collect(),
+          (0..8).collect(),
+        );
+      }
+    }
+    mesh_data.into()
+  }
+}
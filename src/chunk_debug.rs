@@ -0,0 +1,281 @@
+use bevy::asset::load_internal_asset;
+use bevy::pbr::{
+  MaterialPipeline,
+  MaterialPipelineKey,
+  NotShadowCaster,
+};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{
+  AsBindGroup,
+  PolygonMode,
+  PrimitiveTopology,
+  RenderPipelineDescriptor,
+  ShaderRef,
+  SpecializedMeshPipelineError,
+};
+use bitflags::bitflags;
+
+use crate::actions::Action;
+use crate::chunks::{
+  Chunk,
+  ChunkMeshes,
+  ChunkWorldConfig,
+  RenderClass,
+};
+use crate::mesh_data::{
+  MeshData,
+  MeshDataInsert,
+  ATTRIBUTE_PACKED_VERTEX,
+  PACKED_VERTEX_SHADER_HANDLE,
+};
+
+/// `assets/shaders/chunk_debug.wgsl`, embedded so that games need not ship it.
+pub const CHUNK_DEBUG_SHADER_HANDLE: HandleUntyped =
+  HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x7d25_e8a4_19c3_f660);
+
+bitflags! {
+  /// Debug views drawn over the chunks, without touching their meshes.
+  #[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+  pub struct DebugRender: u8 {
+    /// Requires `WgpuFeatures::POLYGON_MODE_LINE`.
+    const WIREFRAME    = 0b001;
+    /// Colors each face by its world space normal.
+    const NORMALS      = 0b010;
+    const CHUNK_BOUNDS = 0b100;
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkDebugView {
+  Wireframe,
+  Normals,
+}
+
+/// Redraws a chunk's mesh, in either `MeshFormat`, as a `ChunkDebugView`. Drawn with a depth bias
+/// so that it wins against the solid mesh it overlays.
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "9e2b6a51-0c4f-4d8e-b3a7-41d5c8f07e62"]
+#[bind_group_data(ChunkDebugMaterialKey)]
+pub struct ChunkDebugMaterial {
+  #[uniform(0)]
+  pub color: Color,
+  #[uniform(0)]
+  pub block_size: Vec3,
+  pub view: ChunkDebugView,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkDebugMaterialKey {
+  view: ChunkDebugView,
+}
+
+impl From<&ChunkDebugMaterial> for ChunkDebugMaterialKey {
+  fn from(material: &ChunkDebugMaterial) -> ChunkDebugMaterialKey {
+    ChunkDebugMaterialKey { view: material.view }
+  }
+}
+
+impl Material for ChunkDebugMaterial {
+  fn vertex_shader() -> ShaderRef { CHUNK_DEBUG_SHADER_HANDLE.typed().into() }
+
+  fn fragment_shader() -> ShaderRef { CHUNK_DEBUG_SHADER_HANDLE.typed().into() }
+
+  fn specialize(
+    _pipeline: &MaterialPipeline<Self>,
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayout,
+    key: MaterialPipelineKey<Self>,
+  ) -> Result<(), SpecializedMeshPipelineError> {
+    descriptor.vertex.buffers = if layout.contains(ATTRIBUTE_PACKED_VERTEX) {
+      descriptor.vertex.shader_defs.push("PACKED_VERTEX".into());
+      vec![layout.get_layout(&[ATTRIBUTE_PACKED_VERTEX.at_shader_location(0)])?]
+    } else {
+      vec![layout.get_layout(&[
+        Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+        Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+      ])?]
+    };
+    match key.bind_group_data.view {
+      ChunkDebugView::Wireframe => descriptor.primitive.polygon_mode = PolygonMode::Line,
+      ChunkDebugView::Normals => {
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+          fragment.shader_defs.push("SHOW_NORMALS".into());
+        }
+      }
+    }
+    if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+      depth_stencil.bias.constant = 2;
+      depth_stencil.bias.slope_scale = 1.0;
+    }
+    Ok(())
+  }
+}
+
+/// Marks the children drawing `DebugRender` views of their parent chunk.
+#[derive(Component)]
+pub struct ChunkDebugOverlay;
+
+#[derive(Clone)]
+struct ChunkDebugAssets {
+  wireframe_material: Handle<ChunkDebugMaterial>,
+  normals_material: Handle<ChunkDebugMaterial>,
+  bounds_mesh: Handle<Mesh>,
+  bounds_material: Handle<StandardMaterial>,
+}
+
+/// Keeps every chunk's overlays in sync with `DebugRender`, toggling its views with their actions
+/// when `Input<Action>` is there to read.
+pub struct ChunkDebugPlugin;
+
+impl Plugin for ChunkDebugPlugin {
+  fn build(&self, app: &mut App) {
+    load_internal_asset!(
+      app,
+      PACKED_VERTEX_SHADER_HANDLE,
+      "../assets/shaders/packed_vertex.wgsl",
+      Shader::from_wgsl
+    );
+    load_internal_asset!(
+      app,
+      CHUNK_DEBUG_SHADER_HANDLE,
+      "../assets/shaders/chunk_debug.wgsl",
+      Shader::from_wgsl
+    );
+    app
+      .add_plugin(MaterialPlugin::<ChunkDebugMaterial> {
+        prepass_enabled: false,
+        ..Default::default()
+      })
+      .init_resource::<DebugRender>()
+      .add_system(toggle_debug_render.run_if(resource_exists::<Input<Action>>()))
+      .add_system(update_chunk_debug_overlays.after(toggle_debug_render));
+  }
+}
+
+fn toggle_debug_render(mut debug_render: ResMut<DebugRender>, actions: Res<Input<Action>>) {
+  for (action, view) in [
+    (Action::ToggleWireframe, DebugRender::WIREFRAME),
+    (Action::ToggleNormals, DebugRender::NORMALS),
+    (Action::ToggleChunkBounds, DebugRender::CHUNK_BOUNDS),
+  ] {
+    if actions.just_pressed(action) {
+      debug_render.toggle(view);
+    }
+  }
+}
+
+/// Rebuilds every chunk's overlays when `DebugRender` changes, and gives new chunks theirs. The
+/// overlays share the mesh handles of every `RenderClass` of their chunk, so they follow its
+/// remeshing for free.
+#[allow(clippy::too_many_arguments)]
+fn update_chunk_debug_overlays(
+  mut commands: Commands,
+  debug_render: Res<DebugRender>,
+  chunks: Query<(Entity, &Chunk, Ref<ChunkMeshes>)>,
+  overlays: Query<Entity, With<ChunkDebugOverlay>>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut mesh_assets: ResMut<Assets<Mesh>>,
+  mut standard_material_assets: ResMut<Assets<StandardMaterial>>,
+  mut debug_material_assets: ResMut<Assets<ChunkDebugMaterial>>,
+  mut debug_assets: Local<Option<ChunkDebugAssets>>,
+) {
+  let debug_assets = debug_assets
+    .get_or_insert_with(|| {
+      let block_size = chunk_world_config.block_size;
+      let max = chunk_world_config.chunk_size.as_vec3() * block_size;
+      ChunkDebugAssets {
+        wireframe_material: debug_material_assets.add(ChunkDebugMaterial {
+          color: Color::BLACK,
+          block_size,
+          view: ChunkDebugView::Wireframe,
+        }),
+        normals_material: debug_material_assets.add(ChunkDebugMaterial {
+          color: Color::WHITE,
+          block_size,
+          view: ChunkDebugView::Normals,
+        }),
+        // Chunk meshes put the center of their first block on the origin.
+        bounds_mesh: mesh_assets.add(box_outline(-block_size / 2.0, max - block_size / 2.0)),
+        bounds_material: standard_material_assets.add(StandardMaterial {
+          base_color: Color::YELLOW,
+          unlit: true,
+          ..Default::default()
+        }),
+      }
+    })
+    .clone();
+
+  if debug_render.is_changed() {
+    for overlay in &overlays {
+      commands.entity(overlay).despawn_recursive();
+    }
+  }
+
+  for (chunk_entity, chunk, chunk_meshes) in &chunks {
+    if !debug_render.is_changed() && !chunk_meshes.is_added() {
+      continue;
+    }
+    let mut overlays = Vec::new();
+    for (view, material) in [
+      (DebugRender::WIREFRAME, &debug_assets.wireframe_material),
+      (DebugRender::NORMALS, &debug_assets.normals_material),
+    ] {
+      if !debug_render.contains(view) {
+        continue;
+      }
+      for class in RenderClass::ALL {
+        overlays.push(
+          commands
+            .spawn((
+              MaterialMeshBundle {
+                mesh: chunk_meshes.0[&class].clone(),
+                material: material.clone(),
+                ..Default::default()
+              },
+              chunk.aabb(),
+              NotShadowCaster,
+              ChunkDebugOverlay,
+            ))
+            .id(),
+        );
+      }
+    }
+    if debug_render.contains(DebugRender::CHUNK_BOUNDS) {
+      overlays.push(
+        commands
+          .spawn((
+            PbrBundle {
+              mesh: debug_assets.bounds_mesh.clone(),
+              material: debug_assets.bounds_material.clone(),
+              ..Default::default()
+            },
+            NotShadowCaster,
+            ChunkDebugOverlay,
+          ))
+          .id(),
+      );
+    }
+    commands.entity(chunk_entity).push_children(&overlays);
+  }
+}
+
+fn box_outline(min: Vec3, max: Vec3) -> Mesh {
+  let mut mesh_data =
+    MeshData { primitive_topology: PrimitiveTopology::LineList, ..Default::default() };
+  let corners = (0..8)
+    .map(|i| {
+      let corner = Vec3::select(BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min);
+      (corner.into(), [0.0; 3], [0.0; 2])
+    })
+    .collect();
+  #[rustfmt::skip]
+  let edges = vec![
+    0, 1, 2, 3, 4, 5, 6, 7,
+    0, 2, 1, 3, 4, 6, 5, 7,
+    0, 4, 1, 5, 2, 6, 3, 7,
+  ];
+  mesh_data.insert(corners, edges, MeshDataInsert::INDICES_OFFSET);
+  mesh_data.into()
+}
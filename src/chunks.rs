@@ -1,205 +1,1202 @@
 mod block;
 mod chunk;
+mod culling;
+mod encoding;
+mod explosion;
+mod generator;
+mod material;
 
-use bevy::math::{
-  ivec3,
-  Vec3A,
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{
+  Duration,
+  Instant,
 };
-use bevy::prelude::*;
-use bevy::render::primitives::Aabb;
-use bevy::utils::HashMap;
 
-use bracket_noise::prelude::FastNoise;
+use bevy::asset::load_internal_asset;
+use bevy::diagnostic::{
+  Diagnostic,
+  DiagnosticId,
+  Diagnostics,
+};
+use bevy::math::ivec3;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::view::VisibilitySystems;
+use bevy::transform::TransformSystem;
+use bevy::utils::{
+  HashMap,
+  HashSet,
+};
+pub use block::{
+  Block,
+  BlockKind,
+  Face,
+};
+pub use chunk::Chunk;
 use chunk::{
-  Chunk,
   ChunkNeighbors,
   ChunkNeighborsInfo,
   ChunkUpdate,
 };
+use culling::cull_hidden_chunks;
+pub use culling::{
+  visible_chunks,
+  ChunkConnectivity,
+};
+pub use encoding::{
+  decode_chunk,
+  encode_chunk,
+  ChunkCompression,
+  CHUNK_FORMAT_VERSION,
+};
+use explosion::{
+  add_debris_meshes,
+  move_debris,
+};
+pub use explosion::{
+  BlockResistances,
+  Debris,
+  Explosion,
+  Falloff,
+};
+pub use generator::{
+  ChunkGenerator,
+  EmptyGenerator,
+  TerrainGenerator,
+};
+use material::add_chunk_materials;
+pub use material::{
+  ChunkMaterials,
+  RenderClass,
+  RenderClasses,
+};
+
+use crate::actions::{
+  Action,
+  ActionsPlugin,
+};
+use crate::chunk_debug::ChunkDebugPlugin;
+use crate::mesh_data::{
+  MeshData,
+  MeshFormat,
+  PackedVertex,
+  PACKED_VERTEX_SHADER_HANDLE,
+};
+use crate::voxel_material::{
+  VoxelMaterial,
+  VOXEL_SHADER_HANDLE,
+};
 
-#[derive(Resource)]
-pub struct Noise(pub FastNoise);
+/// The generator filling new chunks, set with `ChunksPlugin::with_generator`.
+#[derive(Resource, Clone)]
+pub struct Generator(pub Arc<dyn ChunkGenerator>);
 
-#[derive(Resource)]
+/// Each face of a chunk, the offset to the neighbor behind it, and the neighbor's face touching it.
+const NEIGHBOR_FACES: [(Face, IVec3, Face); 6] = [
+  (Face::Front, IVec3::Z, Face::Back),
+  (Face::Back, IVec3::NEG_Z, Face::Front),
+  (Face::Right, IVec3::X, Face::Left),
+  (Face::Left, IVec3::NEG_X, Face::Right),
+  (Face::Top, IVec3::Y, Face::Bottom),
+  (Face::Bottom, IVec3::NEG_Y, Face::Top),
+];
+
+#[derive(Resource, Debug, Clone)]
 pub struct ChunkWorldConfig {
-  pub y_chunks: i32,
   pub chunk_size: UVec3,
   pub block_size: Vec3,
+  pub mesh_format: MeshFormat,
+  /// Number of applied edit batches kept for `ChunkWorld::undo`.
+  pub history_len: usize,
+}
+
+/// Conversions between world positions, block positions and chunk keys. Every axis is scaled by its
+/// own `chunk_size` and `block_size`, and block `pos` is centered on `pos * block_size`.
+impl ChunkWorldConfig {
+  /// Rounds a world position to the block containing it.
+  pub fn world_to_block(&self, translation: Vec3) -> IVec3 {
+    (translation / self.block_size).round().as_ivec3()
+  }
+
+  /// The center of the block at `pos`.
+  pub fn block_to_world(&self, pos: IVec3) -> Vec3 {
+    pos.as_vec3() * self.block_size
+  }
+
+  /// Splits a block position into its chunk key and the block's position inside that chunk.
+  pub fn block_to_chunk(&self, pos: IVec3) -> (IVec3, UVec3) {
+    let chunk_size = self.chunk_size.as_ivec3();
+    (
+      ivec3(
+        pos.x.div_euclid(chunk_size.x),
+        pos.y.div_euclid(chunk_size.y),
+        pos.z.div_euclid(chunk_size.z),
+      ),
+      ivec3(
+        pos.x.rem_euclid(chunk_size.x),
+        pos.y.rem_euclid(chunk_size.y),
+        pos.z.rem_euclid(chunk_size.z),
+      )
+      .as_uvec3(),
+    )
+  }
+
+  /// The position of the first block of the chunk at `key`.
+  pub fn chunk_to_block(&self, key: IVec3) -> IVec3 {
+    key * self.chunk_size.as_ivec3()
+  }
+
+  /// The key of the chunk containing `translation`. This is also the inverse of
+  /// `chunk_translation`.
+  pub fn world_to_chunk(&self, translation: Vec3) -> IVec3 {
+    self.block_to_chunk(self.world_to_block(translation)).0
+  }
+
+  /// Where the chunk at `key` is placed, which is the center of its first block.
+  pub fn chunk_translation(&self, key: IVec3) -> Vec3 {
+    self.block_to_world(self.chunk_to_block(key))
+  }
+
+  /// The keys of the chunks overlapping a cube of half extent `radius` around `center`, as
+  /// `min..max`.
+  pub fn chunks_within(&self, center: Vec3, radius: f32) -> (IVec3, IVec3) {
+    (self.world_to_chunk(center - radius), self.world_to_chunk(center + radius) + 1)
+  }
+
+  pub fn generate_chunk(&self, generator: &dyn ChunkGenerator, key: IVec3) -> Chunk {
+    Chunk::new(generator, self.chunk_to_block(key), self.chunk_size, self.block_size)
+  }
 }
 
-#[derive(Resource)]
+/// A queued change to the world, applied in order by `apply_block_edits`.
+enum WorldEdit {
+  Blocks(Vec<(IVec3, Option<BlockKind>)>),
+  Undo,
+  Redo,
+  Synced(Vec<(IVec3, Option<BlockKind>)>),
+  Explode(Explosion),
+}
+
+/// The blocks an applied batch changed, with their voxels before and after.
+type Transaction = Vec<(IVec3, Option<BlockKind>, Option<BlockKind>)>;
+
+#[derive(Resource, Default)]
 pub struct ChunkWorld {
   pub chunks: HashMap<IVec3, Entity>,
+  edits: Vec<WorldEdit>,
+  undo_stack: VecDeque<Transaction>,
+  redo_stack: Vec<Transaction>,
+  /// Chunks given by `replace_chunk` whose entities are not spawned yet.
+  replacements: HashMap<IVec3, Chunk>,
+  /// Chunks whose blocks changed since they were generated, through edits or replacements.
+  edited: HashSet<IVec3>,
+  /// Edited chunks that were unloaded, encoded to be restored instead of generated.
+  unloaded: HashMap<IVec3, Vec<u8>>,
+}
+
+impl ChunkWorld {
+  /// Reads the block at `pos`, in block coordinates, or `None` if its chunk is not loaded.
+  pub fn get_block(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    pos: IVec3,
+  ) -> Option<Block> {
+    let (key, local) = chunk_world_config.block_to_chunk(pos);
+    let chunk = chunks.get(*self.chunks.get(&key)?).ok()?;
+    chunk.get_block(local.x as usize, local.y as usize, local.z as usize)
+  }
+
+  /// Reads the voxel at `pos`, in block coordinates, or `None` if its chunk is not loaded.
+  pub fn get_voxel(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    pos: IVec3,
+  ) -> Option<Option<BlockKind>> {
+    self.get_block(chunk_world_config, chunks, pos).map(|block| block.voxel())
+  }
+
+  /// Meshes the loaded chunks in `min..max` at full detail, edits included, merged into one mesh.
+  /// Faces against chunks outside the range or not loaded are kept.
+  pub fn region_mesh_data(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    min: IVec3,
+    max: IVec3,
+  ) -> MeshData {
+    let region = (min.x..max.x)
+      .flat_map(|x| (min.y..max.y).flat_map(move |y| (min.z..max.z).map(move |z| ivec3(x, y, z))))
+      .filter_map(|key| Some((key, chunks.get(*self.chunks.get(&key)?).ok()?)))
+      .collect::<Vec<_>>();
+    let by_key = region.iter().copied().collect::<HashMap<_, _>>();
+    let borders = |key: IVec3, face| by_key.get(&key).map(|chunk| chunk.border(face));
+
+    let mut mesh_data = MeshData::default();
+    for (key, chunk) in &region {
+      let chunk_neighbors = ChunkNeighbors {
+        front: borders(*key + ivec3(0, 0, 1), Face::Back),
+        back: borders(*key - ivec3(0, 0, 1), Face::Front),
+        right: borders(*key + ivec3(1, 0, 0), Face::Left),
+        left: borders(*key - ivec3(1, 0, 0), Face::Right),
+        top: borders(*key + ivec3(0, 1, 0), Face::Bottom),
+        bottom: borders(*key - ivec3(0, 1, 0), Face::Top),
+      };
+      // Chunks far from every loading point may be downsampled.
+      let mut chunk = (*chunk).clone();
+      chunk.lod = 0;
+      chunk.update(&chunk_neighbors, &RenderClasses::default());
+      mesh_data.append(chunk.mesh_data(), chunk_world_config.chunk_translation(*key));
+    }
+    mesh_data
+  }
+
+  /// Walks the blocks along a ray from `origin`, in world space, and returns the first activated
+  /// block within `max_distance` along with the block the ray entered it from.
+  pub fn raycast(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+  ) -> Option<(IVec3, IVec3)> {
+    // Work in block units with block `pos` spanning `pos..pos + 1`, so that `t` stays the distance
+    // travelled in world units.
+    let direction = direction.try_normalize()? / chunk_world_config.block_size;
+    let start = origin / chunk_world_config.block_size + 0.5;
+    let mut pos = start.floor().as_ivec3();
+    let step = direction.signum().as_ivec3();
+    let t_delta = direction.recip().abs();
+    let mut t_max = Vec3::select(
+      direction.cmpgt(Vec3::ZERO),
+      (pos.as_vec3() + 1.0 - start) / direction,
+      (pos.as_vec3() - start) / direction,
+    );
+    t_max = Vec3::select(direction.cmpeq(Vec3::ZERO), Vec3::splat(f32::INFINITY), t_max);
+
+    let mut previous = pos;
+    loop {
+      if self.get_block(chunk_world_config, chunks, pos).is_some_and(|block| block.activated) {
+        return Some((pos, previous));
+      }
+      let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+        0
+      } else if t_max.y < t_max.z {
+        1
+      } else {
+        2
+      };
+      if t_max[axis] > max_distance {
+        return None;
+      }
+      previous = pos;
+      pos[axis] += step[axis];
+      t_max[axis] += t_delta[axis];
+    }
+  }
+
+  /// Queues a batch of edits, in block coordinates, where `None` clears the block. Batches are
+  /// applied before the next remesh, and edits to chunks that are not loaded are dropped.
+  ///
+  /// Each batch is one transaction for `undo` and `redo`.
+  pub fn set_blocks(&mut self, edits: impl IntoIterator<Item = (IVec3, Option<BlockKind>)>) {
+    self.edits.push(WorldEdit::Blocks(edits.into_iter().collect()));
+  }
+
+  fn neighbors_info(&self, key: IVec3) -> ChunkNeighborsInfo {
+    let neighbor = |offset| self.chunks.get(&(key + offset)).copied();
+    ChunkNeighborsInfo {
+      front: neighbor(IVec3::Z),
+      back: neighbor(IVec3::NEG_Z),
+      right: neighbor(IVec3::X),
+      left: neighbor(IVec3::NEG_X),
+      top: neighbor(IVec3::Y),
+      bottom: neighbor(IVec3::NEG_Y),
+    }
+  }
+
+  /// Queues reverting the last applied transaction, if any.
+  pub fn undo(&mut self) {
+    self.edits.push(WorldEdit::Undo);
+  }
+
+  /// Queues reapplying the last undone transaction. Applying a new batch clears the redo history.
+  pub fn redo(&mut self) {
+    self.edits.push(WorldEdit::Redo);
+  }
+
+  /// Queues changes the player did not make, such as a server's or block ticks'. They are applied
+  /// like `set_blocks`, but left out of the undo history and sent as `BlockChanged::remote`.
+  pub fn sync_blocks(&mut self, edits: impl IntoIterator<Item = (IVec3, Option<BlockKind>)>) {
+    self.edits.push(WorldEdit::Synced(edits.into_iter().collect()));
+  }
+
+  /// Queues an explosion at `center`, in world space, removing blocks up to `power` blocks away
+  /// with `Falloff::Rays`. The removed blocks are undone as one edit.
+  pub fn explode(&mut self, center: Vec3, power: f32) {
+    self.explode_with(Explosion::new(center, power));
+  }
+
+  pub fn explode_with(&mut self, explosion: Explosion) {
+    self.edits.push(WorldEdit::Explode(explosion));
+  }
+
+  /// Replaces the blocks of the chunk at `key` with `chunk`'s, keeping its entity, level of detail
+  /// and activation. Waits until the chunk is spawned if it is not yet, unless no loading point
+  /// covers it anymore.
+  pub fn replace_chunk(&mut self, key: IVec3, chunk: Chunk) {
+    self.replacements.insert(key, chunk);
+  }
+}
+
+/// Reads voxels through changes the chunks do not have yet, such as those queued with
+/// `ChunkWorld::sync_blocks` earlier in the frame.
+pub struct PendingVoxels<'a> {
+  read: &'a dyn Fn(IVec3) -> Option<Option<BlockKind>>,
+  edits: HashMap<IVec3, Option<BlockKind>>,
+}
+
+impl<'a> PendingVoxels<'a> {
+  /// Reads through `edits` first, then `read`, which is usually `ChunkWorld::get_voxel`.
+  pub fn new(
+    read: &'a dyn Fn(IVec3) -> Option<Option<BlockKind>>,
+    edits: HashMap<IVec3, Option<BlockKind>>,
+  ) -> PendingVoxels<'a> {
+    PendingVoxels { read, edits }
+  }
+
+  /// The voxel at `pos`, or `None` if its chunk is not loaded.
+  pub fn get(&self, pos: IVec3) -> Option<Option<BlockKind>> {
+    self.edits.get(&pos).copied().or_else(|| (self.read)(pos))
+  }
+
+  pub fn set(&mut self, pos: IVec3, voxel: Option<BlockKind>) { self.edits.insert(pos, voxel); }
+
+  pub fn into_edits(self) -> HashMap<IVec3, Option<BlockKind>> { self.edits }
 }
 
 #[derive(Component)]
 pub struct ChunkLoadingPoint {
   pub radius: f32,
+  /// Distances past which chunks are meshed at LOD 1, 2 and 3 (2x, 4x and 8x downsampled).
+  pub lod_radii: [f32; 3],
 }
 
-pub struct ChunksPlugin;
+impl ChunkLoadingPoint {
+  pub fn lod_at(&self, clp_pos: Vec3, chunk_center: Vec3) -> u32 {
+    let distance = clp_pos.distance(chunk_center);
+    self.lod_radii.iter().filter(|lod_radius| distance > **lod_radius).count() as u32
+  }
+}
 
-impl Plugin for ChunksPlugin {
-  fn build(&self, app: &mut App) {
-    app
-      .insert_resource(ChunkWorld { chunks: HashMap::new() })
-      .add_system(toggle_wireframes_for_chunks)
-      .add_system(spawn_chunks_within_radius)
-      .add_system(load_chunks_within_radius)
-      .add_system(update_chunks.in_base_set(CoreSet::First));
+/// The finest level of detail any loading point asks for at `chunk_center`.
+fn finest_lod<'a>(
+  clps: impl IntoIterator<Item = (&'a Transform, &'a ChunkLoadingPoint)>,
+  chunk_center: Vec3,
+) -> u32 {
+  clps
+    .into_iter()
+    .map(|(clp_transform, clp)| clp.lod_at(clp_transform.translation, chunk_center))
+    .min()
+    .unwrap_or(0)
+}
+
+/// Sent when a chunk's blocks are generated, or restored after an unload, and its entity is
+/// spawned.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkGenerated {
+  pub key: IVec3,
+  pub entity: Entity,
+}
+
+/// Sent whenever a chunk's mesh is rebuilt.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkMeshed {
+  pub key: IVec3,
+  pub entity: Entity,
+}
+
+/// Sent for every block an edit, undo, redo or sync changes, in block coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockChanged {
+  /// Whether the change came from `ChunkWorld::sync_blocks` rather than a player's edit.
+  pub remote: bool,
+  pub pos: IVec3,
+  pub key: IVec3,
+  pub entity: Entity,
+  pub before: Option<BlockKind>,
+  pub after: Option<BlockKind>,
+}
+
+/// Sent when a chunk leaves every loading point's radius and its entity is despawned. Only the
+/// blocks of edited chunks are kept, compressed, to be restored if a loading point comes back.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkUnloaded {
+  pub key: IVec3,
+  pub entity: Entity,
+}
+
+/// Generates, loads, edits and meshes the chunks around every `ChunkLoadingPoint`.
+///
+/// Edits queued during `CoreSet::Update` are written in `CoreSet::PostUpdate` by
+/// `apply_block_edits`, which sends their `BlockChanged` events. Systems of other plugins that react
+/// to those events within the same frame run in `CoreSet::Last`.
+///
+/// ```ignore
+/// app.add_plugin(
+///   ChunksPlugin::new(config)
+///     .with_generator(TerrainGenerator::new(seed, 16))
+///     .with_material(StandardMaterial { base_color: Color::GREEN, ..Default::default() }),
+/// );
+/// ```
+pub struct ChunksPlugin {
+  config: ChunkWorldConfig,
+  generator: Arc<dyn ChunkGenerator>,
+  material: StandardMaterial,
+  class_materials: HashMap<RenderClass, StandardMaterial>,
+  render_classes: RenderClasses,
+  meshing: bool,
+  resistances: BlockResistances,
+  occlusion_culling: bool,
+  actions: bool,
+  debug_render: bool,
+}
+
+impl ChunksPlugin {
+  /// Generates `TerrainGenerator` terrain with seed 0, drawn orange red.
+  pub fn new(config: ChunkWorldConfig) -> ChunksPlugin {
+    ChunksPlugin {
+      config,
+      generator: Arc::new(TerrainGenerator::new(0, 16)),
+      material: StandardMaterial { base_color: Color::ORANGE_RED, ..Default::default() },
+      class_materials: HashMap::new(),
+      render_classes: RenderClasses::default(),
+      meshing: true,
+      resistances: BlockResistances::default(),
+      occlusion_culling: true,
+      actions: false,
+      debug_render: false,
+    }
+  }
+
+  pub fn with_generator(mut self, generator: impl ChunkGenerator) -> ChunksPlugin {
+    self.generator = Arc::new(generator);
+    self
+  }
+
+  /// The material of every `RenderClass` without its own, with the class's alpha mode.
+  pub fn with_material(mut self, material: StandardMaterial) -> ChunksPlugin {
+    self.material = material;
+    self
+  }
+
+  /// The material of `class`, whose alpha mode is replaced with the class's.
+  pub fn with_class_material(
+    mut self,
+    class: RenderClass,
+    material: StandardMaterial,
+  ) -> ChunksPlugin {
+    self.class_materials.insert(class, material);
+    self
+  }
+
+  /// Draws blocks of `kind` with the material of `class`. Kinds without a class are opaque.
+  pub fn with_render_class(mut self, kind: BlockKind, class: RenderClass) -> ChunksPlugin {
+    self.render_classes.kinds.insert(kind, class);
+    self
+  }
+
+  /// Whether chunks get meshes. Without meshing, chunks are still generated, loaded and edited,
+  /// and the plugin only needs `MinimalPlugins`, for servers and tests.
+  pub fn with_meshing(mut self, meshing: bool) -> ChunksPlugin {
+    self.meshing = meshing;
+    self
+  }
+
+  /// Whether chunks that cannot be seen from the camera's chunk through the empty blocks of the
+  /// chunks in between are hidden, such as caves under mountains. Only applies with meshing.
+  pub fn with_occlusion_culling(mut self, occlusion_culling: bool) -> ChunksPlugin {
+    self.occlusion_culling = occlusion_culling;
+    self
+  }
+
+  /// Whether to add `ActionsPlugin::<Action>`, binding inputs to the engine's own actions such as
+  /// toggling `DebugRender` views. Only applies with meshing.
+  pub fn with_actions(mut self, actions: bool) -> ChunksPlugin {
+    self.actions = actions;
+    self
+  }
+
+  /// Whether to add `ChunkDebugPlugin`, drawing the `DebugRender` views over the chunks. The views
+  /// follow their actions when `with_actions` is also set. Only applies with meshing.
+  pub fn with_debug_render(mut self, debug_render: bool) -> ChunksPlugin {
+    self.debug_render = debug_render;
+    self
+  }
+
+  /// How much of an explosion's strength blocks of `kind` absorb. Kinds without a resistance
+  /// absorb 1, and `f32::INFINITY` makes a kind indestructible.
+  pub fn with_block_resistance(mut self, kind: BlockKind, resistance: f32) -> ChunksPlugin {
+    self.resistances.kinds.insert(kind, resistance);
+    self
+  }
+
+  pub const LOADED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(202_118_513_744_015_630_917_208_844_331_061_902_337);
+  /// Chunks waiting to be remeshed.
+  pub const DIRTY_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(318_472_006_195_263_847_120_934_558_671_042_389_165);
+  pub const CHUNK_VERTICES: DiagnosticId =
+    DiagnosticId::from_u128(96_340_718_250_117_462_893_055_174_620_938_411_507);
+  pub const CHUNK_TRIANGLES: DiagnosticId =
+    DiagnosticId::from_u128(257_631_092_448_906_315_778_201_649_053_712_860_294);
+  /// Average time spent generating each chunk's blocks, over the frames that generated any.
+  pub const GENERATION_TIME: DiagnosticId =
+    DiagnosticId::from_u128(183_904_527_612_338_075_946_120_865_297_410_583_726);
+  /// Average time spent meshing each chunk, over the frames that meshed any.
+  pub const MESHING_TIME: DiagnosticId =
+    DiagnosticId::from_u128(60_827_143_995_201_768_430_512_389_064_177_952_118);
+  /// Chunks hidden by occlusion culling.
+  pub const CULLED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(142_322_560_219_750_907_989_936_858_186_655_618_160);
+
+  fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(Self::LOADED_CHUNKS, "loaded_chunks", 1));
+    diagnostics.add(Diagnostic::new(Self::DIRTY_CHUNKS, "dirty_chunks", 1));
+    diagnostics.add(Diagnostic::new(Self::CHUNK_VERTICES, "chunk_vertices", 1));
+    diagnostics.add(Diagnostic::new(Self::CHUNK_TRIANGLES, "chunk_triangles", 1));
+    diagnostics
+      .add(Diagnostic::new(Self::GENERATION_TIME, "chunk_generation_time", 20).with_suffix("ms"));
+    diagnostics
+      .add(Diagnostic::new(Self::MESHING_TIME, "chunk_meshing_time", 20).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(Self::CULLED_CHUNKS, "culled_chunks", 1));
   }
 }
 
-fn toggle_wireframes_for_chunks(
-  mut commands: Commands,
-  mut chunks: Query<(Entity, &mut Chunk)>,
-  key: Res<Input<KeyCode>>,
-) {
-  if key.just_pressed(KeyCode::L) {
-    for (chunk_entity, mut chunk) in &mut chunks {
-      chunk.wireframe = !chunk.wireframe;
-      commands.entity(chunk_entity).insert(ChunkUpdate);
+/// Reports the time spent on `count` chunks to `id`, in milliseconds per chunk.
+fn add_time_per_chunk(diagnostics: &mut Diagnostics, id: DiagnosticId, time: Duration, count: u32) {
+  if count > 0 {
+    diagnostics.add_measurement(id, || time.as_secs_f64() * 1000.0 / count as f64);
+  }
+}
+
+impl Plugin for ChunksPlugin {
+  fn build(&self, app: &mut App) {
+    if self.meshing {
+      // Larger chunks would wrap the positions of their packed vertices around.
+      assert!(
+        self.config.mesh_format != MeshFormat::Packed
+          || self.config.chunk_size.max_element() <= PackedVertex::MAX_CHUNK_SIZE,
+        "`MeshFormat::Packed` fits chunks of at most {} blocks per axis, but they are {}",
+        PackedVertex::MAX_CHUNK_SIZE,
+        self.config.chunk_size
+      );
+      load_internal_asset!(
+        app,
+        PACKED_VERTEX_SHADER_HANDLE,
+        "../assets/shaders/packed_vertex.wgsl",
+        Shader::from_wgsl
+      );
+      load_internal_asset!(
+        app,
+        VOXEL_SHADER_HANDLE,
+        "../assets/shaders/voxel.wgsl",
+        Shader::from_wgsl
+      );
+      if self.actions {
+        app.add_plugin(ActionsPlugin::<Action>::new());
+      }
+      if self.debug_render {
+        app.add_plugin(ChunkDebugPlugin);
+      }
+      app
+        .add_plugin(MaterialPlugin::<VoxelMaterial> {
+          prepass_enabled: false,
+          ..Default::default()
+        })
+        .add_startup_system(add_chunk_materials)
+        .add_system(add_chunk_meshes.in_base_set(CoreSet::PostUpdate))
+        .add_system(add_debris_meshes)
+        .add_system(update_chunks.in_base_set(CoreSet::First));
+      if self.occlusion_culling {
+        app.add_system(
+          cull_hidden_chunks
+            .in_base_set(CoreSet::PostUpdate)
+            // Reads this frame's camera `GlobalTransform`.
+            .after(TransformSystem::TransformPropagate)
+            .before(VisibilitySystems::VisibilityPropagate),
+        );
+      }
+    } else {
+      app.add_system(discard_chunk_updates.in_base_set(CoreSet::First));
     }
+    app
+      .insert_resource(self.config.clone())
+      .insert_resource(Generator(self.generator.clone()))
+      .insert_resource(ChunkMaterials::new(&self.material, &self.class_materials))
+      .insert_resource(self.render_classes.clone())
+      .insert_resource(ChunkWorld::default())
+      .insert_resource(self.resistances.clone())
+      .init_resource::<Diagnostics>()
+      .add_event::<ChunkGenerated>()
+      .add_event::<ChunkMeshed>()
+      .add_event::<BlockChanged>()
+      .add_event::<ChunkUnloaded>()
+      .add_system(apply_block_edits.in_base_set(CoreSet::PostUpdate))
+      .add_system(record_edited_chunks.in_base_set(CoreSet::PostUpdate).after(apply_block_edits))
+      .add_system(
+        apply_chunk_replacements.in_base_set(CoreSet::PostUpdate).before(apply_block_edits),
+      )
+      .add_system(spawn_chunks_within_radius)
+      // Before spawning, so that new chunks are linked to the neighbors that remain.
+      .add_system(unload_chunks_outside_radius.before(spawn_chunks_within_radius))
+      .add_system(update_chunk_lods)
+      .add_system(move_debris)
+      .add_startup_system(Self::setup_diagnostics)
+      .add_system(measure_chunks.in_base_set(CoreSet::Last));
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_chunks_within_radius(
   mut commands: Commands,
   clps: Query<(&Transform, &ChunkLoadingPoint)>,
-  noise: Res<Noise>,
-  mut mesh_assets: ResMut<Assets<Mesh>>,
-  mut standard_material_assets: ResMut<Assets<StandardMaterial>>,
+  generator: Res<Generator>,
   chunk_world_config: Res<ChunkWorldConfig>,
   mut chunk_world: ResMut<ChunkWorld>,
+  chunks: Query<&Chunk>,
+  mut chunk_generated_events: EventWriter<ChunkGenerated>,
+  mut diagnostics: ResMut<Diagnostics>,
 ) {
+  let (mut generation_time, mut generated) = (Duration::ZERO, 0);
   for (clp_transform, clp) in &clps {
-    let clp_aabb =
-      Aabb { center: clp_transform.translation.into(), half_extents: Vec3A::splat(clp.radius) };
-    let clp_min = (clp_aabb.min().round()
-      / (chunk_world_config.chunk_size.x as f32 * chunk_world_config.block_size.x))
-      .as_ivec3();
-    let clp_max = (clp_aabb.max().round()
-      / (chunk_world_config.chunk_size.x as f32 * chunk_world_config.block_size.x))
-      .as_ivec3();
-
-    let mut update_neighbors = false;
+    let (clp_min, clp_max) =
+      chunk_world_config.chunks_within(clp_transform.translation, clp.radius);
+
+    let mut new_keys = Vec::new();
     for x in clp_min.x..clp_max.x {
-      for y in 0..chunk_world_config.y_chunks {
+      for y in clp_min.y..clp_max.y {
         for z in clp_min.z..clp_max.z {
           let key = ivec3(x, y, z);
           if !chunk_world.chunks.contains_key(&key) {
-            update_neighbors = true;
-            let chunk = Chunk::new(
-              &noise.0,
-              key * chunk_world_config.chunk_size.as_ivec3(),
-              chunk_world_config.y_chunks as u32 * chunk_world_config.chunk_size.y,
-              chunk_world_config.chunk_size,
-              chunk_world_config.block_size,
-            );
+            new_keys.push(key);
+            let start = Instant::now();
+            let restored = chunk_world.unloaded.remove(&key).and_then(|data| {
+              match decode_chunk(&data, chunk_world_config.block_size) {
+                Ok(chunk) => Some(chunk),
+                Err(error) => {
+                  warn!("Regenerating chunk {key}, whose edits could not be decoded: {error}");
+                  None
+                }
+              }
+            });
+            let mut chunk = match restored {
+              Some(chunk) => chunk,
+              None => {
+                chunk_world.edited.remove(&key);
+                chunk_world_config.generate_chunk(generator.0.as_ref(), key)
+              }
+            };
+            generation_time += start.elapsed();
+            generated += 1;
+            let chunk_transform =
+              Transform::from_translation(chunk_world_config.chunk_translation(key));
+            chunk.lod = finest_lod(&clps, chunk.center(&chunk_transform));
+
+            // Existing neighbors only need remeshing where the new chunk hides some of their faces.
+            // Neighbors spawned this frame are not queryable yet and get meshed anyway.
+            for (face, offset, neighbor_face) in NEIGHBOR_FACES {
+              let Some(neighbor_entity) = chunk_world.chunks.get(&(key + offset)) else { continue };
+              let Ok(neighbor) = chunks.get(*neighbor_entity) else { continue };
+              if chunk.border(face).touches(&neighbor.border(neighbor_face)) {
+                commands.entity(*neighbor_entity).insert(ChunkUpdate);
+              }
+            }
+
             let chunk = commands
-              .spawn(PbrBundle {
-                mesh: mesh_assets.add((&chunk).into()),
-                material: standard_material_assets
-                  .add(StandardMaterial { base_color: Color::ORANGE_RED, ..Default::default() }),
-                transform: Transform::from_translation(
-                  key.as_vec3()
-                    * chunk_world_config.chunk_size.as_vec3()
-                    * chunk_world_config.block_size,
-                ),
-                ..Default::default()
-              })
-              .insert(chunk)
-              .insert(ChunkUpdate)
+              .spawn((SpatialBundle::from_transform(chunk_transform), chunk, ChunkUpdate))
               .id();
 
             chunk_world.chunks.insert(key, chunk);
+            chunk_generated_events.send(ChunkGenerated { key, entity: chunk });
           }
         }
       }
     }
 
-    if update_neighbors {
-      for (key, chunk_entity) in &chunk_world.chunks {
-        let chunk_neighbors_info = ChunkNeighborsInfo {
-          front: chunk_world.chunks.get(&(*key + ivec3(0, 0, 1))).copied(),
-          back: chunk_world.chunks.get(&(*key - ivec3(0, 0, 1))).copied(),
-          right: chunk_world.chunks.get(&(*key + ivec3(1, 0, 0))).copied(),
-          left: chunk_world.chunks.get(&(*key - ivec3(1, 0, 0))).copied(),
-          top: chunk_world.chunks.get(&(*key + ivec3(0, 1, 0))).copied(),
-          bottom: chunk_world.chunks.get(&(*key - ivec3(0, 1, 0))).copied(),
-        };
-        commands.entity(*chunk_entity).insert(chunk_neighbors_info);
+    let mut relinked_keys = HashSet::new();
+    for key in new_keys {
+      relinked_keys.insert(key);
+      relinked_keys.extend(
+        NEIGHBOR_FACES
+          .iter()
+          .map(|(_, offset, _)| key + *offset)
+          .filter(|neighbor_key| chunk_world.chunks.contains_key(neighbor_key)),
+      );
+    }
+    for key in relinked_keys {
+      commands.entity(chunk_world.chunks[&key]).insert(chunk_world.neighbors_info(key));
+    }
+  }
+  add_time_per_chunk(&mut diagnostics, ChunksPlugin::GENERATION_TIME, generation_time, generated);
+}
+
+/// The mesh of each `RenderClass` of a chunk, rebuilt by `update_chunks`.
+#[derive(Component)]
+pub(crate) struct ChunkMeshes(pub(crate) HashMap<RenderClass, Handle<Mesh>>);
+
+/// Gives chunks spawned this frame a mesh per `RenderClass` with the class's shared material, when
+/// meshing is enabled. The opaque mesh goes on the chunk itself and the others on its children.
+fn add_chunk_meshes(
+  mut commands: Commands,
+  chunks: Query<(Entity, &Chunk), Added<Chunk>>,
+  chunk_materials: Res<ChunkMaterials>,
+  render_classes: Res<RenderClasses>,
+  mut mesh_assets: ResMut<Assets<Mesh>>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+) {
+  let mesh_format = chunk_world_config.mesh_format;
+  for (chunk_entity, chunk) in &chunks {
+    let mut chunk_meshes = HashMap::new();
+    for class in RenderClass::ALL {
+      let mesh = mesh_assets.add(chunk.class_mesh(mesh_format, &render_classes, class));
+      chunk_meshes.insert(class, mesh.clone());
+      let mut mesh_entity = if class == RenderClass::Opaque {
+        commands.entity(chunk_entity)
+      } else {
+        let mut child = commands.spawn(SpatialBundle::default());
+        child.set_parent(chunk_entity);
+        child
+      };
+      match mesh_format {
+        MeshFormat::Standard => {
+          mesh_entity.insert((mesh, chunk_materials.standard(class).unwrap()));
+        }
+        MeshFormat::Packed => {
+          mesh_entity.insert((
+            mesh,
+            chunk_materials.packed(class).unwrap(),
+            // Packed meshes have no position attribute to derive bounds or shadows from.
+            chunk.aabb(),
+            NotShadowCaster,
+          ));
+        }
       }
     }
+    commands.entity(chunk_entity).insert(ChunkMeshes(chunk_meshes));
+  }
+}
+
+/// Stands in for `update_chunks` when meshing is disabled, so that edited chunks are not left
+/// waiting for a remesh.
+fn discard_chunk_updates(mut commands: Commands, chunks: Query<Entity, With<ChunkUpdate>>) {
+  for chunk_entity in &chunks {
+    commands.entity(chunk_entity).remove::<ChunkUpdate>();
   }
 }
 
-fn load_chunks_within_radius(
+/// Despawns the chunks outside every loading point's radius once any loading point moves or is
+/// removed, keeping the blocks of edited ones. Neighbors left behind are relinked, and remeshed
+/// where the chunk hid some of their faces.
+#[allow(clippy::too_many_arguments)]
+fn unload_chunks_outside_radius(
   mut commands: Commands,
-  clps: Query<(&Transform, &ChunkLoadingPoint), Changed<Transform>>,
-  mut chunks: Query<(Entity, &Transform, &mut Chunk)>,
+  moved_clps: Query<(), (With<ChunkLoadingPoint>, Changed<Transform>)>,
+  mut removed_clps: RemovedComponents<ChunkLoadingPoint>,
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunks: Query<&Chunk>,
+  mut chunk_unloaded_events: EventWriter<ChunkUnloaded>,
 ) {
-  for (clp_transform, clp) in &clps {
-    let clp_aabb =
-      Aabb { center: clp_transform.translation.into(), half_extents: Vec3A::splat(clp.radius) };
-
-    for (chunk_entity, chunk_transform, mut chunk) in &mut chunks {
-      let chunk_pos: Vec3A = chunk_transform.translation.into();
-      if (chunk_pos.x >= clp_aabb.min().x && chunk_pos.x <= clp_aabb.max().x)
-        && (chunk_pos.y >= clp_aabb.min().y && chunk_pos.y <= clp_aabb.max().y)
-        && (chunk_pos.z >= clp_aabb.min().z && chunk_pos.z <= clp_aabb.max().z)
-      {
-        if !chunk.activated {
-          chunk.activated = true;
-          commands.entity(chunk_entity).insert(ChunkUpdate);
+  if removed_clps.iter().count() == 0 && moved_clps.is_empty() {
+    return;
+  }
+
+  let ranges = clps
+    .iter()
+    .map(|(clp_transform, clp)| {
+      chunk_world_config.chunks_within(clp_transform.translation, clp.radius)
+    })
+    .collect::<Vec<_>>();
+  let unloaded_keys = chunk_world
+    .chunks
+    .keys()
+    .copied()
+    .filter(|key| !ranges.iter().any(|(min, max)| key.cmpge(*min).all() && key.cmplt(*max).all()))
+    .collect::<Vec<_>>();
+  let unloaded = unloaded_keys
+    .into_iter()
+    .map(|key| (key, chunk_world.chunks.remove(&key).unwrap()))
+    .collect::<Vec<_>>();
+
+  let mut relinked_keys = HashSet::new();
+  for (key, chunk_entity) in unloaded {
+    for (face, offset, neighbor_face) in NEIGHBOR_FACES {
+      let Some(neighbor_entity) = chunk_world.chunks.get(&(key + offset)) else { continue };
+      relinked_keys.insert(key + offset);
+      let (Ok(chunk), Ok(neighbor)) = (chunks.get(chunk_entity), chunks.get(*neighbor_entity))
+      else {
+        continue;
+      };
+      if chunk.border(face).touches(&neighbor.border(neighbor_face)) {
+        commands.entity(*neighbor_entity).insert(ChunkUpdate);
+      }
+    }
+    if chunk_world.edited.contains(&key) {
+      if let Ok(chunk) = chunks.get(chunk_entity) {
+        let data = encode_chunk(chunk, ChunkCompression::STREAMING);
+        chunk_world.unloaded.insert(key, data);
+      }
+    }
+    commands.entity(chunk_entity).despawn_recursive();
+    chunk_unloaded_events.send(ChunkUnloaded { key, entity: chunk_entity });
+  }
+  for key in relinked_keys {
+    commands.entity(chunk_world.chunks[&key]).insert(chunk_world.neighbors_info(key));
+  }
+}
+
+fn apply_block_edits(
+  mut commands: Commands,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunks: Query<&mut Chunk>,
+  block_resistances: Res<BlockResistances>,
+  mut block_changed_events: EventWriter<BlockChanged>,
+) {
+  let mut updated_chunks = HashSet::new();
+  for edit in std::mem::take(&mut chunk_world.edits) {
+    match edit {
+      WorldEdit::Blocks(edits) => apply_batch(
+        &mut chunk_world,
+        &chunk_world_config,
+        &mut chunks,
+        &mut updated_chunks,
+        &mut block_changed_events,
+        edits,
+      ),
+      WorldEdit::Explode(explosion) => {
+        let removed = explosion.blast(&chunk_world_config, &block_resistances, |pos| {
+          let (key, local) = chunk_world_config.block_to_chunk(pos);
+          let chunk = chunks.get(*chunk_world.chunks.get(&key)?).ok()?;
+          chunk
+            .get_block(local.x as usize, local.y as usize, local.z as usize)
+            .map(|block| block.voxel())
+        });
+        if explosion.debris {
+          commands.spawn_batch(explosion.debris(&chunk_world_config, &removed));
+        }
+        // Removed like any other batch, so that it can be undone.
+        apply_batch(
+          &mut chunk_world,
+          &chunk_world_config,
+          &mut chunks,
+          &mut updated_chunks,
+          &mut block_changed_events,
+          removed.into_iter().map(|(pos, _)| (pos, None)).collect(),
+        );
+      }
+      WorldEdit::Undo => {
+        let Some(transaction) = chunk_world.undo_stack.pop_back() else { continue };
+        for (pos, before, _) in transaction.iter().rev() {
+          write_block(
+            &chunk_world,
+            &chunk_world_config,
+            &mut chunks,
+            &mut updated_chunks,
+            &mut block_changed_events,
+            *pos,
+            *before,
+            false,
+          );
+        }
+        chunk_world.redo_stack.push(transaction);
+      }
+      WorldEdit::Redo => {
+        let Some(transaction) = chunk_world.redo_stack.pop() else { continue };
+        for (pos, _, after) in &transaction {
+          write_block(
+            &chunk_world,
+            &chunk_world_config,
+            &mut chunks,
+            &mut updated_chunks,
+            &mut block_changed_events,
+            *pos,
+            *after,
+            false,
+          );
+        }
+        chunk_world.undo_stack.push_back(transaction);
+      }
+      WorldEdit::Synced(edits) => {
+        for (pos, voxel) in edits {
+          let (key, local) = chunk_world_config.block_to_chunk(pos);
+          if let Some(chunk) = chunk_world.replacements.get_mut(&key) {
+            // Its entity is not spawned yet, so the change lands in the blocks it will receive.
+            if let Some(block) =
+              chunk.get_block_mut(local.x as usize, local.y as usize, local.z as usize)
+            {
+              block.set_voxel(voxel);
+            }
+            continue;
+          }
+          write_block(
+            &chunk_world,
+            &chunk_world_config,
+            &mut chunks,
+            &mut updated_chunks,
+            &mut block_changed_events,
+            pos,
+            voxel,
+            true,
+          );
         }
-      } else if chunk.activated {
-        chunk.activated = false;
-        commands.entity(chunk_entity).insert(ChunkUpdate);
+      }
+    }
+  }
+
+  for chunk_entity in updated_chunks {
+    commands.entity(chunk_entity).insert(ChunkUpdate);
+  }
+}
+
+/// Writes a batch of `set_blocks` edits, and records the blocks it changed as one transaction.
+fn apply_batch(
+  chunk_world: &mut ChunkWorld,
+  chunk_world_config: &ChunkWorldConfig,
+  chunks: &mut Query<&mut Chunk>,
+  updated_chunks: &mut HashSet<Entity>,
+  block_changed_events: &mut EventWriter<BlockChanged>,
+  edits: Vec<(IVec3, Option<BlockKind>)>,
+) {
+  let transaction = edits
+    .into_iter()
+    .filter_map(|(pos, voxel)| {
+      let before = write_block(
+        chunk_world,
+        chunk_world_config,
+        chunks,
+        updated_chunks,
+        block_changed_events,
+        pos,
+        voxel,
+        false,
+      )?;
+      Some((pos, before, voxel))
+    })
+    .collect::<Transaction>();
+  if !transaction.is_empty() {
+    chunk_world.redo_stack.clear();
+    chunk_world.undo_stack.push_back(transaction);
+    while chunk_world.undo_stack.len() > chunk_world_config.history_len {
+      chunk_world.undo_stack.pop_front();
+    }
+  }
+}
+
+/// Marks the chunks with changed blocks, which are kept when unloaded since generating them again
+/// would lose the changes.
+fn record_edited_chunks(
+  mut chunk_world: ResMut<ChunkWorld>,
+  mut block_changed_events: EventReader<BlockChanged>,
+) {
+  chunk_world.edited.extend(block_changed_events.iter().map(|block_changed| block_changed.key));
+}
+
+fn apply_chunk_replacements(
+  mut commands: Commands,
+  mut chunk_world: ResMut<ChunkWorld>,
+  mut chunks: Query<&mut Chunk>,
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+) {
+  // Chunks left behind by every loading point would keep their replacements forever.
+  let ranges = clps
+    .iter()
+    .map(|(clp_transform, clp)| {
+      chunk_world_config.chunks_within(clp_transform.translation, clp.radius)
+    })
+    .collect::<Vec<(IVec3, IVec3)>>();
+  chunk_world.replacements.retain(|key, _| {
+    ranges.iter().any(|(min, max)| key.cmpge(*min).all() && key.cmplt(*max).all())
+  });
+
+  let spawned_keys = chunk_world
+    .replacements
+    .keys()
+    .copied()
+    .filter(|key| chunk_world.chunks.contains_key(key))
+    .collect::<Vec<IVec3>>();
+  for key in spawned_keys {
+    let replacement = chunk_world.replacements.remove(&key).unwrap();
+    let chunk_entity = chunk_world.chunks[&key];
+    let Ok(mut chunk) = chunks.get_mut(chunk_entity) else { continue };
+    if chunk.size != replacement.size {
+      warn!("Ignored replacement of chunk {key} with size {}", replacement.size);
+      continue;
+    }
+    chunk.blocks = replacement.blocks;
+    // Replacements do not send `BlockChanged`, so `record_edited_chunks` does not see them.
+    chunk_world.edited.insert(key);
+    commands.entity(chunk_entity).insert(ChunkUpdate);
+    // Neighbors show or hide their border faces depending on the new blocks.
+    for (_, offset, _) in NEIGHBOR_FACES {
+      if let Some(neighbor_entity) = chunk_world.chunks.get(&(key + offset)) {
+        commands.entity(*neighbor_entity).insert(ChunkUpdate);
       }
     }
   }
 }
 
+/// Writes `voxel` at `pos` and collects the chunks to remesh, returning the previous voxel if the
+/// block changed.
+#[allow(clippy::too_many_arguments)]
+fn write_block(
+  chunk_world: &ChunkWorld,
+  chunk_world_config: &ChunkWorldConfig,
+  chunks: &mut Query<&mut Chunk>,
+  updated_chunks: &mut HashSet<Entity>,
+  block_changed_events: &mut EventWriter<BlockChanged>,
+  pos: IVec3,
+  voxel: Option<BlockKind>,
+  remote: bool,
+) -> Option<Option<BlockKind>> {
+  let (key, local) = chunk_world_config.block_to_chunk(pos);
+  let chunk_entity = *chunk_world.chunks.get(&key)?;
+  let mut chunk = chunks.get_mut(chunk_entity).ok()?;
+  let block = chunk.get_block_mut(local.x as usize, local.y as usize, local.z as usize)?;
+  let before = block.voxel();
+  if before == voxel {
+    return None;
+  }
+
+  block.set_voxel(voxel);
+  updated_chunks.insert(chunk_entity);
+  block_changed_events.send(BlockChanged {
+    remote,
+    pos,
+    key,
+    entity: chunk_entity,
+    before,
+    after: voxel,
+  });
+
+  // Blocks on a chunk border also decide which faces the neighboring chunk shows.
+  let max = chunk_world_config.chunk_size - 1;
+  for (on_border, offset) in [
+    (local.x == 0, ivec3(-1, 0, 0)),
+    (local.x == max.x, ivec3(1, 0, 0)),
+    (local.y == 0, ivec3(0, -1, 0)),
+    (local.y == max.y, ivec3(0, 1, 0)),
+    (local.z == 0, ivec3(0, 0, -1)),
+    (local.z == max.z, ivec3(0, 0, 1)),
+  ] {
+    if let Some(neighbor_entity) = chunk_world.chunks.get(&(key + offset)).filter(|_| on_border) {
+      updated_chunks.insert(*neighbor_entity);
+    }
+  }
+
+  Some(before)
+}
+
+fn update_chunk_lods(
+  mut commands: Commands,
+  moved_clps: Query<(), (With<ChunkLoadingPoint>, Changed<Transform>)>,
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  mut chunks: Query<(Entity, &Transform, &mut Chunk)>,
+) {
+  if moved_clps.is_empty() {
+    return;
+  }
+  for (chunk_entity, chunk_transform, mut chunk) in &mut chunks {
+    let lod = finest_lod(&clps, chunk.center(chunk_transform));
+    if chunk.lod != lod {
+      chunk.lod = lod;
+      commands.entity(chunk_entity).insert(ChunkUpdate);
+    }
+  }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn update_chunks(
   mut commands: Commands,
-  mut chunks: Query<(Entity, &mut Chunk, &ChunkNeighborsInfo, &Handle<Mesh>), With<ChunkUpdate>>,
+  mut chunks: ParamSet<(
+    Query<(Entity, &mut Chunk, &ChunkNeighborsInfo, &ChunkMeshes), With<ChunkUpdate>>,
+    Query<&Chunk>,
+  )>,
+  chunk_transforms: Query<&Transform, With<Chunk>>,
   mut mesh_assets: ResMut<Assets<Mesh>>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  render_classes: Res<RenderClasses>,
+  mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+  mut diagnostics: ResMut<Diagnostics>,
 ) {
-  let mut chunks_neighbors = HashMap::new();
-  for (chunk_entity, _, chunk_neighbors_info, _) in &chunks {
-    let chunk_neighbors = ChunkNeighbors {
-      front: chunk_neighbors_info
-        .front
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-      back: chunk_neighbors_info
-        .back
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-      right: chunk_neighbors_info
-        .right
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-      left: chunk_neighbors_info
-        .left
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-      top: chunk_neighbors_info
-        .top
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-      bottom: chunk_neighbors_info
-        .bottom
-        .and_then(|entity| chunks.get(entity).ok())
-        .map(|(_, chunk, _, _)| chunk.clone()),
-    };
-    chunks_neighbors.insert(chunk_entity, chunk_neighbors);
-  }
-
-  for (chunk_entity, mut chunk, _, chunk_mesh_handle) in &mut chunks {
-    chunk.update(&chunks_neighbors[&chunk_entity]);
-    commands.entity(chunk_entity).remove::<ChunkUpdate>();
-    mesh_assets.set_untracked(chunk_mesh_handle, chunk.as_ref().into());
+  let chunk_neighbors_infos = chunks
+    .p0()
+    .iter()
+    .map(|(chunk_entity, _, chunk_neighbors_info, _)| (chunk_entity, chunk_neighbors_info.clone()))
+    .collect::<Vec<_>>();
+  // Neighbors are read whether or not they are remeshed this frame.
+  let all_chunks = chunks.p1();
+  let border = |entity: Option<Entity>, face| Some(all_chunks.get(entity?).ok()?.border(face));
+  let chunks_neighbors = chunk_neighbors_infos
+    .into_iter()
+    .map(|(chunk_entity, info)| {
+      let chunk_neighbors = ChunkNeighbors {
+        front: border(info.front, Face::Back),
+        back: border(info.back, Face::Front),
+        right: border(info.right, Face::Left),
+        left: border(info.left, Face::Right),
+        top: border(info.top, Face::Bottom),
+        bottom: border(info.bottom, Face::Top),
+      };
+      (chunk_entity, chunk_neighbors)
+    })
+    .collect::<HashMap<_, _>>();
+
+  let (mut meshing_time, mut meshed) = (Duration::ZERO, 0);
+  for (chunk_entity, mut chunk, _, chunk_meshes) in &mut chunks.p0() {
+    let start = Instant::now();
+    chunk.update(&chunks_neighbors[&chunk_entity], &render_classes);
+    for (class, chunk_mesh_handle) in &chunk_meshes.0 {
+      let mesh = chunk.class_mesh(chunk_world_config.mesh_format, &render_classes, *class);
+      mesh_assets.set_untracked(chunk_mesh_handle, mesh);
+    }
+    let chunk_connectivity = ChunkConnectivity::new(&chunk, &render_classes);
+    meshing_time += start.elapsed();
+    meshed += 1;
+    commands.entity(chunk_entity).remove::<ChunkUpdate>().insert(chunk_connectivity);
+    if let Ok(chunk_transform) = chunk_transforms.get(chunk_entity) {
+      chunk_meshed_events.send(ChunkMeshed {
+        key: chunk_world_config.world_to_chunk(chunk_transform.translation),
+        entity: chunk_entity,
+      });
+    }
+  }
+  add_time_per_chunk(&mut diagnostics, ChunksPlugin::MESHING_TIME, meshing_time, meshed);
+}
+
+fn measure_chunks(
+  mut diagnostics: ResMut<Diagnostics>,
+  chunk_world: Res<ChunkWorld>,
+  chunk_meshes: Query<&ChunkMeshes>,
+  dirty_chunks: Query<(), With<ChunkUpdate>>,
+  mesh_assets: Option<Res<Assets<Mesh>>>,
+) {
+  let (mut vertices, mut triangles) = (0, 0);
+  for chunk_meshes in &chunk_meshes {
+    for mesh in chunk_meshes.0.values().filter_map(|handle| mesh_assets.as_ref()?.get(handle)) {
+      vertices += mesh.count_vertices();
+      // Chunk meshes are always indexed triangle lists.
+      triangles += mesh.indices().map_or(0, |indices| indices.len() / 3);
+    }
   }
+  diagnostics.add_measurement(ChunksPlugin::LOADED_CHUNKS, || chunk_world.chunks.len() as f64);
+  diagnostics.add_measurement(ChunksPlugin::DIRTY_CHUNKS, || dirty_chunks.iter().len() as f64);
+  diagnostics.add_measurement(ChunksPlugin::CHUNK_VERTICES, || vertices as f64);
+  diagnostics.add_measurement(ChunksPlugin::CHUNK_TRIANGLES, || triangles as f64);
 }
@@ -3,7 +3,6 @@ use bevy::prelude::{
   Mesh,
   Vec3,
 };
-use bevy::render::render_resource::PrimitiveTopology;
 
 use crate::mesh_data::{
   MeshData,
@@ -22,28 +21,37 @@ bitflags::bitflags! {
   }
 }
 
+/// What an activated block is made of. Kinds line up with MagicaVoxel palette entries, so kind `k`
+/// is color index `k + 1` in a `.vox` file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockKind(pub u8);
+
 #[derive(Component, Debug, Clone, Copy)]
 pub struct Block {
   pub size: Vec3,
-  pub wireframe: bool,
   pub activated: bool,
   pub activated_faces: Face,
+  pub kind: BlockKind,
 }
 
 impl Block {
   pub fn new(size: Vec3, activated_faces: Face) -> Block {
-    Block { size, wireframe: false, activated: true, activated_faces }
+    Block { size, activated: true, activated_faces, kind: BlockKind::default() }
+  }
+
+  /// The block's kind, or `None` if it is not activated.
+  pub fn voxel(&self) -> Option<BlockKind> { self.activated.then_some(self.kind) }
+
+  /// Activates the block as `voxel`'s kind, or deactivates it for `None`.
+  pub fn set_voxel(&mut self, voxel: Option<BlockKind>) {
+    self.activated = voxel.is_some();
+    if let Some(kind) = voxel {
+      self.kind = kind;
+    }
   }
 
   pub fn mesh_data(&self) -> MeshData {
-    let mut mesh_data = MeshData {
-      primitive_topology: if self.wireframe {
-        PrimitiveTopology::LineList
-      } else {
-        PrimitiveTopology::TriangleList
-      },
-      ..Default::default()
-    };
+    let mut mesh_data = MeshData::default();
 
     if self.activated {
       if self.activated_faces.contains(Face::Front) {
@@ -115,10 +123,10 @@ impl Block {
         #[rustfmt::skip]
       mesh_data.insert(
         vec![
-          ([ self.size.x / 2.0, -self.size.y / 2.0,  self.size.z / 2.0], [ 0.0, -0.0,  0.0], [0.0, 0.0]),
-          ([-self.size.x / 2.0, -self.size.y / 2.0,  self.size.z / 2.0], [ 0.0, -0.0,  0.0], [1.0, 0.0]),
-          ([-self.size.x / 2.0, -self.size.y / 2.0, -self.size.z / 2.0], [ 0.0, -0.0,  0.0], [1.0, 1.0]),
-          ([ self.size.x / 2.0, -self.size.y / 2.0, -self.size.z / 2.0], [ 0.0, -0.0,  0.0], [0.0, 1.0]),
+          ([ self.size.x / 2.0, -self.size.y / 2.0,  self.size.z / 2.0], [ 0.0, -1.0,  0.0], [0.0, 0.0]),
+          ([-self.size.x / 2.0, -self.size.y / 2.0,  self.size.z / 2.0], [ 0.0, -1.0,  0.0], [1.0, 0.0]),
+          ([-self.size.x / 2.0, -self.size.y / 2.0, -self.size.z / 2.0], [ 0.0, -1.0,  0.0], [1.0, 1.0]),
+          ([ self.size.x / 2.0, -self.size.y / 2.0, -self.size.z / 2.0], [ 0.0, -1.0,  0.0], [0.0, 1.0]),
         ],
         vec![0, 1, 2, 3],
         MeshDataInsert::all()
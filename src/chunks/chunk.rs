@@ -5,24 +5,29 @@ use bevy::prelude::{
   Entity,
   IVec3,
   Mesh,
+  Transform,
   UVec3,
   Vec3,
 };
-use bevy::render::render_resource::PrimitiveTopology;
-use bracket_noise::prelude::FastNoise;
+use bevy::render::primitives::Aabb;
 use rayon::prelude::*;
 
 use crate::chunks::block::{
   Block,
+  BlockKind,
   Face,
 };
+use crate::chunks::generator::ChunkGenerator;
+use crate::chunks::material::{
+  RenderClass,
+  RenderClasses,
+};
 use crate::mesh_data::{
   MeshData,
   MeshDataInsert,
+  MeshFormat,
 };
 
-const WORLD_TO_NOISE: f32 = 0.027;
-
 #[derive(Component)]
 pub struct ChunkUpdate;
 
@@ -36,30 +41,61 @@ pub struct ChunkNeighborsInfo {
   pub bottom: Option<Entity>,
 }
 
+/// The layer of blocks on one face of a chunk, reduced to each block's voxel. Blocks are indexed by
+/// the two axes along the layer, in x, y, z order.
+#[derive(Debug, Clone)]
+pub struct ChunkBorder {
+  stride: usize,
+  voxels: Vec<Option<BlockKind>>,
+}
+
+impl ChunkBorder {
+  pub fn len(&self) -> usize {
+    self.voxels.len()
+  }
+
+  pub fn voxel(&self, u: usize, v: usize) -> Option<BlockKind> {
+    if v < self.stride {
+      self.voxels.get(u * self.stride + v).copied().flatten()
+    } else {
+      None
+    }
+  }
+
+  pub fn is_activated(&self, u: usize, v: usize) -> bool { self.voxel(u, v).is_some() }
+
+  /// Whether any block is activated in both this border and the facing border of a neighbor, which
+  /// is when the two may hide each other's faces.
+  pub fn touches(&self, other: &ChunkBorder) -> bool {
+    self.voxels.iter().zip(&other.voxels).any(|(a, b)| a.is_some() && b.is_some())
+  }
+}
+
+/// The borders neighboring chunks share with a chunk, each taken from the neighbor's face that
+/// touches it, so meshing reads a layer instead of a copy of the whole neighbor.
 #[derive(Component, Default, Debug, Clone)]
 pub struct ChunkNeighbors {
-  pub front: Option<Chunk>,
-  pub back: Option<Chunk>,
-  pub right: Option<Chunk>,
-  pub left: Option<Chunk>,
-  pub top: Option<Chunk>,
-  pub bottom: Option<Chunk>,
+  pub front: Option<ChunkBorder>,
+  pub back: Option<ChunkBorder>,
+  pub right: Option<ChunkBorder>,
+  pub left: Option<ChunkBorder>,
+  pub top: Option<ChunkBorder>,
+  pub bottom: Option<ChunkBorder>,
 }
 
 #[derive(Component, Debug, Clone)]
 pub struct Chunk {
   pub size: UVec3,
   pub block_size: Vec3,
-  pub wireframe: bool,
   pub blocks: Vec<Block>,
-  pub activated: bool,
+  pub lod: u32,
 }
 
 impl Chunk {
+  /// Generates the chunk whose first block is at `offset`, in block coordinates.
   pub fn new(
-    noise: &FastNoise,
-    noise_offset: IVec3,
-    max_y: u32,
+    generator: &dyn ChunkGenerator,
+    offset: IVec3,
     size: UVec3,
     block_size: Vec3,
   ) -> Chunk {
@@ -70,30 +106,39 @@ impl Chunk {
         let y = (i / size.z) % size.y;
         let z = i % size.z;
 
-        let enabled = y as i32 + noise_offset.y
-          <= (noise.get_noise(
-            (x as f32 + noise_offset.x as f32) * WORLD_TO_NOISE,
-            (z as f32 + noise_offset.z as f32) * WORLD_TO_NOISE,
-          ) * max_y as f32)
-            .round() as u32 as i32;
-        // let enabled = noise.get_noise3d(
-        // (x as f32 + noise_offset.x as f32) * WORLD_TO_NOISE,
-        // (y as f32 + noise_offset.y as f32) * WORLD_TO_NOISE,
-        // (z as f32 + noise_offset.z as f32) * WORLD_TO_NOISE,
-        // ) < 0.1;
-
         let mut block = Block::new(block_size, Face::all());
-        if !enabled {
-          block.activated = false;
-        }
+        block.set_voxel(generator.generate(offset + UVec3::new(x, y, z).as_ivec3()));
         block
       })
       .collect::<Vec<Block>>();
 
-    Chunk { size, block_size, wireframe: false, blocks, activated: true }
+    Chunk { size, block_size, blocks, lod: 0 }
+  }
+
+  /// A chunk holding `voxels`, in the same x, y, z order as `blocks`.
+  pub fn from_voxels(voxels: &[Option<BlockKind>], size: UVec3, block_size: Vec3) -> Chunk {
+    assert_eq!(voxels.len(), size.x as usize * size.y as usize * size.z as usize);
+    let blocks = voxels
+      .iter()
+      .map(|voxel| {
+        let mut block = Block::new(block_size, Face::all());
+        block.set_voxel(*voxel);
+        block
+      })
+      .collect();
+    Chunk { size, block_size, blocks, lod: 0 }
+  }
+
+  pub fn center(&self, transform: &Transform) -> Vec3 {
+    // The chunk origin is the center of its first block, not its corner.
+    transform.translation + (self.size.as_vec3() - 1.0) * self.block_size / 2.0
   }
 
-  fn get_block(&self, x: usize, y: usize, z: usize) -> Option<Block> {
+  pub fn aabb(&self) -> Aabb {
+    Aabb::from_min_max(-self.block_size / 2.0, (self.size.as_vec3() - 0.5) * self.block_size)
+  }
+
+  pub fn get_block(&self, x: usize, y: usize, z: usize) -> Option<Block> {
     if x >= self.size.x as usize || y >= self.size.y as usize || z >= self.size.z as usize {
       return None;
     }
@@ -102,65 +147,67 @@ impl Chunk {
     )
   }
 
-  pub fn update(&mut self, chunk_neighbors: &ChunkNeighbors) {
+  pub fn get_block_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut Block> {
+    if x >= self.size.x as usize || y >= self.size.y as usize || z >= self.size.z as usize {
+      return None;
+    }
+    Some(
+      &mut self.blocks
+        [x * self.size.y as usize * self.size.z as usize + y * self.size.z as usize + z],
+    )
+  }
+
+  /// Extracts the layer of blocks on `face`.
+  pub fn border(&self, face: Face) -> ChunkBorder {
+    let [sx, sy, sz] = self.size.to_array().map(|size| size as usize);
+    let (xs, ys, zs, stride) = match face {
+      Face::Front => (0..sx, 0..sy, sz - 1..sz, sy),
+      Face::Back => (0..sx, 0..sy, 0..1, sy),
+      Face::Right => (sx - 1..sx, 0..sy, 0..sz, sz),
+      Face::Left => (0..1, 0..sy, 0..sz, sz),
+      Face::Top => (0..sx, sy - 1..sy, 0..sz, sz),
+      _ => (0..sx, 0..1, 0..sz, sz),
+    };
+
+    let mut voxels = Vec::with_capacity(xs.len() * ys.len() * zs.len());
+    for x in xs {
+      for y in ys.clone() {
+        for z in zs.clone() {
+          voxels.push(self.blocks[x * sy * sz + y * sz + z].voxel());
+        }
+      }
+    }
+    ChunkBorder { stride, voxels }
+  }
+
+  /// Activates the faces of each block that no neighboring block hides, as `render_classes`
+  /// decides, looking across the chunk's faces into `chunk_neighbors`. Faces against missing
+  /// neighbors are activated.
+  pub fn update(&mut self, chunk_neighbors: &ChunkNeighbors, render_classes: &RenderClasses) {
     let faces = (0..self.size.x as usize * self.size.y as usize * self.size.z as usize)
       .into_par_iter()
       .map(|i| {
         let x = (i / (self.size.y as usize * self.size.z as usize)) % self.size.x as usize;
         let y = (i / self.size.z as usize) % self.size.y as usize;
         let z = i % self.size.z as usize;
+        let Some(kind) = self.blocks[i].voxel() else { return Face::empty() };
 
         let mut activated_faces = Face::empty();
-        if self.get_block(x, y, z + 1).map_or(
-          chunk_neighbors
-            .front
-            .as_ref()
-            .map_or(true, |chunk| chunk.get_block(x, y, 0).map_or(true, |block| !block.activated)),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Front, true);
-        }
-        if self.get_block(x, y, z.wrapping_sub(1)).map_or(
-          chunk_neighbors.back.as_ref().map_or(true, |chunk| {
-            chunk.get_block(x, y, chunk.size.z as usize - 1).map_or(true, |block| !block.activated)
-          }),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Back, true);
-        }
-        if self.get_block(x + 1, y, z).map_or(
-          chunk_neighbors
-            .right
-            .as_ref()
-            .map_or(true, |chunk| chunk.get_block(0, y, z).map_or(true, |block| !block.activated)),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Right, true);
-        }
-        if self.get_block(x.wrapping_sub(1), y, z).map_or(
-          chunk_neighbors.left.as_ref().map_or(true, |chunk| {
-            chunk.get_block(chunk.size.x as usize - 1, y, z).map_or(true, |block| !block.activated)
-          }),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Left, true);
-        }
-        if self.get_block(x, y + 1, z).map_or(
-          chunk_neighbors
-            .top
-            .as_ref()
-            .map_or(true, |chunk| chunk.get_block(x, 0, z).map_or(true, |block| !block.activated)),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Top, true);
-        }
-        if self.get_block(x, y.wrapping_sub(1), z).map_or(
-          chunk_neighbors.bottom.as_ref().map_or(true, |chunk| {
-            chunk.get_block(x, chunk.size.y as usize - 1, z).map_or(true, |block| !block.activated)
-          }),
-          |block| !block.activated,
-        ) {
-          activated_faces.set(Face::Bottom, true);
+        for (face, neighbor, border, u, v) in [
+          (Face::Front, (x, y, z + 1), &chunk_neighbors.front, x, y),
+          (Face::Back, (x, y, z.wrapping_sub(1)), &chunk_neighbors.back, x, y),
+          (Face::Right, (x + 1, y, z), &chunk_neighbors.right, y, z),
+          (Face::Left, (x.wrapping_sub(1), y, z), &chunk_neighbors.left, y, z),
+          (Face::Top, (x, y + 1, z), &chunk_neighbors.top, x, z),
+          (Face::Bottom, (x, y.wrapping_sub(1), z), &chunk_neighbors.bottom, x, z),
+        ] {
+          let neighbor = match self.get_block(neighbor.0, neighbor.1, neighbor.2) {
+            Some(block) => block.voxel(),
+            None => border.as_ref().and_then(|border| border.voxel(u, v)),
+          };
+          if !neighbor.is_some_and(|neighbor| render_classes.hides(neighbor, kind)) {
+            activated_faces.set(face, true);
+          }
         }
 
         activated_faces
@@ -168,60 +215,171 @@ impl Chunk {
       .collect::<Vec<Face>>();
 
     self.blocks.par_iter_mut().zip(faces).for_each(|(block, activated_faces)| {
-      block.wireframe = self.wireframe;
       block.activated_faces = activated_faces;
     });
   }
 
-  pub fn mesh_data(&self) -> MeshData {
-    let mut mesh_data = MeshData {
-      primitive_topology: if self.wireframe {
-        PrimitiveTopology::LineList
-      } else {
-        PrimitiveTopology::TriangleList
-      },
-      ..Default::default()
-    };
+  pub fn mesh_data(&self) -> MeshData { self.mesh_data_where(|_| true) }
+
+  /// The part of `mesh_data` made of blocks of `class`. Downsampled chunks are opaque as a whole.
+  pub fn class_mesh_data(&self, render_classes: &RenderClasses, class: RenderClass) -> MeshData {
+    match (self.lod, class) {
+      (0, _) => self.mesh_data_where(|kind| render_classes.get(kind) == class),
+      (_, RenderClass::Opaque) => self.mesh_data(),
+      _ => MeshData::default(),
+    }
+  }
+
+  fn mesh_data_where(&self, include: impl Fn(BlockKind) -> bool + Sync) -> MeshData {
+    let mut mesh_data = MeshData::default();
 
-    if self.activated {
+    if self.lod > 0 {
+      self.insert_lod_mesh_data(&mut mesh_data);
+    } else {
       let (sender, receiver) = mpsc::channel();
-      self.blocks.par_iter().enumerate().for_each_with(sender, |sender, (i, block)| {
-        let x = ((i / (self.size.y as usize * self.size.z as usize)) % self.size.x as usize) as f32;
-        let y = ((i / self.size.z as usize) % self.size.y as usize) as f32;
-        let z = (i % self.size.z as usize) as f32;
-
-        let block_mesh_data = block.mesh_data();
-        sender
-          .send((
-            block_mesh_data
-              .vertex_positions
-              .into_par_iter()
-              .zip(block_mesh_data.vertex_normals)
-              .zip(block_mesh_data.vertex_uvs)
-              .map(|((p, n), u)| {
-                (
-                  [
-                    p[0] + x * self.block_size.x,
-                    p[1] + y * self.block_size.y,
-                    p[2] + z * self.block_size.z,
-                  ],
-                  n,
-                  u,
-                )
-              })
-              .collect::<Vec<([f32; 3], [f32; 3], [f32; 2])>>(),
-            block_mesh_data.indices,
-          ))
-          .unwrap();
+      let blocks = self.blocks.par_iter().enumerate().filter(|(_, block)| include(block.kind));
+      blocks.for_each_with(sender, |sender, (i, block)| {
+        let x = (i / (self.size.y as usize * self.size.z as usize)) % self.size.x as usize;
+        let y = (i / self.size.z as usize) % self.size.y as usize;
+        let z = i % self.size.z as usize;
+        let pos = UVec3::new(x as u32, y as u32, z as u32).as_ivec3();
+
+        let mut block_mesh_data = block.mesh_data();
+        block_mesh_data.vertex_ao = block_mesh_data
+          .vertex_positions
+          .iter()
+          .zip(&block_mesh_data.vertex_normals)
+          .map(|(position, normal)| self.vertex_ao(pos, Vec3::from(*position), Vec3::from(*normal)))
+          .collect();
+        block_mesh_data.vertex_layers = vec![block.kind.0 as u32; block_mesh_data.vertex_ao.len()];
+        sender.send((block_mesh_data, pos.as_vec3() * self.block_size)).unwrap();
       });
 
-      for (vertices, indices) in receiver.iter() {
-        mesh_data.insert(vertices, indices, MeshDataInsert::INDICES_OFFSET);
+      for (block_mesh_data, translation) in receiver.iter() {
+        mesh_data.append(block_mesh_data, translation);
       }
     }
 
     mesh_data
   }
+
+  /// The ambient occlusion level of the corner of the block at `pos` that lies toward `corner` on
+  /// the face with `normal`, from the blocks on either side of the corner and diagonal to it in
+  /// front of the face. Blocks outside the chunk do not occlude.
+  fn vertex_ao(&self, pos: IVec3, corner: Vec3, normal: Vec3) -> u32 {
+    let normal = normal.as_ivec3();
+    let toward = corner.signum().as_ivec3() * (IVec3::ONE - normal.abs());
+    let (side_a, side_b) = if normal.x != 0 {
+      (toward * IVec3::Y, toward * IVec3::Z)
+    } else if normal.y != 0 {
+      (toward * IVec3::X, toward * IVec3::Z)
+    } else {
+      (toward * IVec3::X, toward * IVec3::Y)
+    };
+    let occludes = |offset: IVec3| {
+      let pos = pos + normal + offset;
+      pos.cmpge(IVec3::ZERO).all()
+        && self
+          .get_block(pos.x as usize, pos.y as usize, pos.z as usize)
+          .is_some_and(|block| block.activated)
+    };
+
+    match (occludes(side_a), occludes(side_b)) {
+      (true, true) => 0,
+      (a, b) => 3 - a as u32 - b as u32 - occludes(side_a + side_b) as u32,
+    }
+  }
+
+  pub fn mesh(&self, mesh_format: MeshFormat) -> Mesh {
+    self.mesh_data().into_mesh(mesh_format, self.block_size)
+  }
+
+  pub fn class_mesh(
+    &self,
+    mesh_format: MeshFormat,
+    render_classes: &RenderClasses,
+    class: RenderClass,
+  ) -> Mesh {
+    self.class_mesh_data(render_classes, class).into_mesh(mesh_format, self.block_size)
+  }
+
+  /// Meshes the chunk from voxel data downsampled by `2^lod` on every axis. A cell is solid if any
+  /// of its blocks is, and faces on the chunk border are always emitted as skirts so that cracks
+  /// against neighbors of a different level of detail stay covered.
+  fn insert_lod_mesh_data(&self, mesh_data: &mut MeshData) {
+    let scale = 1 << self.lod;
+    let cells = (self.size + (scale - 1)) / scale;
+    let cell_size = self.block_size * scale as f32;
+
+    let solid = (0..cells.x as usize * cells.y as usize * cells.z as usize)
+      .into_par_iter()
+      .map(|i| {
+        let x = (i / (cells.y as usize * cells.z as usize)) % cells.x as usize * scale as usize;
+        let y = (i / cells.z as usize) % cells.y as usize * scale as usize;
+        let z = i % cells.z as usize * scale as usize;
+
+        (x..x + scale as usize).any(|bx| {
+          (y..y + scale as usize).any(|by| {
+            (z..z + scale as usize)
+              .any(|bz| self.get_block(bx, by, bz).is_some_and(|block| block.activated))
+          })
+        })
+      })
+      .collect::<Vec<bool>>();
+
+    let is_solid = |x: usize, y: usize, z: usize| {
+      x < cells.x as usize
+        && y < cells.y as usize
+        && z < cells.z as usize
+        && solid[x * cells.y as usize * cells.z as usize + y * cells.z as usize + z]
+    };
+
+    let cells_mesh_data = solid
+      .par_iter()
+      .enumerate()
+      .filter(|(_, solid)| **solid)
+      .map(|(i, _)| {
+        let x = (i / (cells.y as usize * cells.z as usize)) % cells.x as usize;
+        let y = (i / cells.z as usize) % cells.y as usize;
+        let z = i % cells.z as usize;
+
+        let mut activated_faces = Face::empty();
+        for (face, (nx, ny, nz)) in [
+          (Face::Front, (x, y, z + 1)),
+          (Face::Back, (x, y, z.wrapping_sub(1))),
+          (Face::Right, (x + 1, y, z)),
+          (Face::Left, (x.wrapping_sub(1), y, z)),
+          (Face::Top, (x, y + 1, z)),
+          (Face::Bottom, (x, y.wrapping_sub(1), z)),
+        ] {
+          if !is_solid(nx, ny, nz) {
+            activated_faces.set(face, true);
+          }
+        }
+
+        let block_mesh_data = Block::new(cell_size, activated_faces).mesh_data();
+
+        // Cell centers sit halfway between the centers of the first and last block they cover.
+        let center = (Vec3::new(x as f32, y as f32, z as f32) * scale as f32
+          + (scale - 1) as f32 / 2.0)
+          * self.block_size;
+        (
+          block_mesh_data
+            .vertex_positions
+            .into_iter()
+            .zip(block_mesh_data.vertex_normals)
+            .zip(block_mesh_data.vertex_uvs)
+            .map(|((p, n), u)| ([p[0] + center.x, p[1] + center.y, p[2] + center.z], n, u))
+            .collect::<Vec<([f32; 3], [f32; 3], [f32; 2])>>(),
+          block_mesh_data.indices,
+        )
+      })
+      .collect::<Vec<_>>();
+
+    for (vertices, indices) in cells_mesh_data {
+      mesh_data.insert(vertices, indices, MeshDataInsert::INDICES_OFFSET);
+    }
+  }
 }
 
 impl From<Chunk> for Mesh {
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+use bevy::core_pipeline::core_3d::Camera3d;
+use bevy::diagnostic::Diagnostics;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::chunks::block::Face;
+use crate::chunks::{
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+  ChunksPlugin,
+  RenderClass,
+  RenderClasses,
+  NEIGHBOR_FACES,
+};
+
+/// Which faces of a chunk see each other through its empty and see-through blocks, computed when
+/// the chunk is meshed. Chunks not meshed yet count as open on every face.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkConnectivity([Face; 6]);
+
+impl ChunkConnectivity {
+  /// Every face sees every other, as in an empty chunk.
+  pub const OPEN: ChunkConnectivity = ChunkConnectivity([Face::all(); 6]);
+
+  /// Flood fills the blocks of `chunk` that are empty or not `RenderClass::Opaque`, connecting the
+  /// faces each such region touches.
+  pub fn new(chunk: &Chunk, render_classes: &RenderClasses) -> ChunkConnectivity {
+    let [sx, sy, sz] = chunk.size.to_array().map(|size| size as usize);
+    let index = |x: usize, y: usize, z: usize| x * sy * sz + y * sz + z;
+    let mut visited = chunk
+      .blocks
+      .iter()
+      .map(|block| {
+        block.voxel().is_some_and(|kind| render_classes.get(kind) == RenderClass::Opaque)
+      })
+      .collect::<Vec<bool>>();
+    let mut faces = [Face::empty(); 6];
+    let mut stack = Vec::new();
+    for start in 0..visited.len() {
+      if visited[start] {
+        continue;
+      }
+      visited[start] = true;
+      stack.push((start / (sy * sz), (start / sz) % sy, start % sz));
+      let mut touched = Face::empty();
+      while let Some((x, y, z)) = stack.pop() {
+        touched |= Self::faces_at(x, y, z, sx, sy, sz);
+        let neighbors = [
+          (x + 1 < sx).then(|| (x + 1, y, z)),
+          x.checked_sub(1).map(|x| (x, y, z)),
+          (y + 1 < sy).then(|| (x, y + 1, z)),
+          y.checked_sub(1).map(|y| (x, y, z)),
+          (z + 1 < sz).then(|| (x, y, z + 1)),
+          z.checked_sub(1).map(|z| (x, y, z)),
+        ];
+        for (x, y, z) in neighbors.into_iter().flatten() {
+          if !visited[index(x, y, z)] {
+            visited[index(x, y, z)] = true;
+            stack.push((x, y, z));
+          }
+        }
+      }
+      for face in touched.iter() {
+        faces[Self::face_index(face)] |= touched;
+      }
+    }
+    ChunkConnectivity(faces)
+  }
+
+  /// Whether something entering through `from` can leave through `to`.
+  pub fn connects(&self, from: Face, to: Face) -> bool {
+    self.0[Self::face_index(from)].contains(to)
+  }
+
+  fn face_index(face: Face) -> usize { face.bits().trailing_zeros() as usize }
+
+  /// The faces of a chunk of size `sx`, `sy`, `sz` the block at `x`, `y`, `z` lies on.
+  fn faces_at(x: usize, y: usize, z: usize, sx: usize, sy: usize, sz: usize) -> Face {
+    let mut faces = Face::empty();
+    faces.set(Face::Left, x == 0);
+    faces.set(Face::Right, x == sx - 1);
+    faces.set(Face::Bottom, y == 0);
+    faces.set(Face::Top, y == sy - 1);
+    faces.set(Face::Back, z == 0);
+    faces.set(Face::Front, z == sz - 1);
+    faces
+  }
+}
+
+/// The chunks that can be seen from the chunk at `start`, walking outwards through the faces each
+/// chunk's `ChunkConnectivity` connects to the face it was entered through, and never back towards
+/// `start`. `connectivity` returns `None` for chunks the walk cannot enter, such as unloaded ones.
+pub fn visible_chunks(
+  start: IVec3,
+  connectivity: impl Fn(IVec3) -> Option<ChunkConnectivity>,
+) -> HashSet<IVec3> {
+  let mut visible = HashSet::new();
+  // Each chunk, the face it was entered through, and the directions walked to reach it.
+  let mut queue = VecDeque::from([(start, None, Face::empty())]);
+  visible.insert(start);
+  while let Some((key, entered, directions)) = queue.pop_front() {
+    let Some(chunk_connectivity) = connectivity(key) else { continue };
+    for (face, offset, neighbor_face) in NEIGHBOR_FACES {
+      if directions.contains(neighbor_face)
+        || entered.is_some_and(|entered| !chunk_connectivity.connects(entered, face))
+      {
+        continue;
+      }
+      let neighbor_key = key + offset;
+      if !visible.contains(&neighbor_key) && connectivity(neighbor_key).is_some() {
+        visible.insert(neighbor_key);
+        queue.push_back((neighbor_key, Some(neighbor_face), directions | face));
+      }
+    }
+  }
+  visible
+}
+
+/// Hides the chunks `visible_chunks` does not reach from the camera's chunk. Frustum culling is
+/// left to the renderer, since chunks behind the camera still cast shadows. Everything is shown
+/// while the camera is outside the loaded chunks.
+pub(crate) fn cull_hidden_chunks(
+  chunk_world: Res<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+  mut chunks: Query<(Option<&ChunkConnectivity>, &mut Visibility), With<Chunk>>,
+  mut diagnostics: ResMut<Diagnostics>,
+) {
+  let Some((_, camera_transform)) = cameras.iter().find(|(camera, _)| camera.is_active) else {
+    return;
+  };
+  let start = chunk_world_config.world_to_chunk(camera_transform.translation());
+  let visible = chunk_world.chunks.contains_key(&start).then(|| {
+    visible_chunks(start, |key| {
+      let (chunk_connectivity, _) = chunks.get(*chunk_world.chunks.get(&key)?).ok()?;
+      Some(chunk_connectivity.copied().unwrap_or(ChunkConnectivity::OPEN))
+    })
+  });
+
+  let mut culled = 0;
+  for (key, chunk_entity) in &chunk_world.chunks {
+    let Ok((_, mut visibility)) = chunks.get_mut(*chunk_entity) else { continue };
+    let shown = visible.as_ref().is_none_or(|visible| visible.contains(key));
+    culled += !shown as usize;
+    let chunk_visibility = if shown { Visibility::Inherited } else { Visibility::Hidden };
+    // Only writes changes, so that unchanged chunks are not flagged for the renderer.
+    if *visibility != chunk_visibility {
+      *visibility = chunk_visibility;
+    }
+  }
+  diagnostics.add_measurement(ChunksPlugin::CULLED_CHUNKS, || culled as f64);
+}
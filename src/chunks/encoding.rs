@@ -0,0 +1,255 @@
+use std::io::{
+  self,
+  Read,
+  Write,
+};
+
+use bevy::prelude::{
+  UVec3,
+  Vec3,
+};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use crate::chunks::block::BlockKind;
+use crate::chunks::chunk::Chunk;
+use crate::codec::{
+  invalid_data,
+  read_array,
+  read_runs,
+  read_varint,
+  voxel_from_u16,
+  voxel_to_u16,
+  write_runs,
+  write_varint,
+};
+
+const FORMAT: &str = "chunk";
+const CHUNK_MAGIC: &[u8; 4] = b"VXCK";
+/// The format `encode_chunk` writes. Bumped when the format changes, along with
+/// `CHUNK_MIN_READER_VERSION` when older readers can no longer decode it.
+pub const CHUNK_FORMAT_VERSION: u8 = 1;
+/// The oldest reader that can decode what `encode_chunk` writes. Readers accept any version whose
+/// minimum reader version they meet, skipping header fields they do not know.
+const CHUNK_MIN_READER_VERSION: u8 = 1;
+/// The header fields this version writes: the size and the compression.
+const CHUNK_HEADER_LEN: u16 = 13;
+/// Larger chunks are rejected before allocating their blocks.
+const MAX_CHUNK_BLOCKS: usize = 1 << 21;
+
+const INDICES_RUNS: u8 = 0;
+const INDICES_PACKED: u8 = 1;
+
+/// How the body of an encoded chunk is compressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkCompression {
+  None,
+  /// Deflate, which is slower to write but shrinks mostly empty or solid chunks further.
+  #[default]
+  Deflate,
+  /// An LZ4 block after its decompressed length as a little-endian `u32`, which is much faster to
+  /// write and read than Deflate but larger. Needs the `lz4` feature, both to encode and to decode.
+  #[cfg(feature = "lz4")]
+  Lz4,
+}
+
+impl ChunkCompression {
+  /// What chunks are streamed to clients and kept in once unloaded, where speed matters more than
+  /// size: LZ4 with the `lz4` feature, and Deflate without it.
+  #[cfg(feature = "lz4")]
+  pub const STREAMING: ChunkCompression = ChunkCompression::Lz4;
+  #[cfg(not(feature = "lz4"))]
+  pub const STREAMING: ChunkCompression = ChunkCompression::Deflate;
+}
+
+/// Encodes the chunk's voxels as a palette of the kinds it holds, followed by each block's index
+/// into it as either runs or bit-packed, whichever is smaller. Everything after the header is
+/// compressed with `compression`.
+///
+/// The header is the magic `VXCK`, the format version, the minimum reader version, the length of
+/// the fields that follow as a little-endian `u16`, then the size as three `u32` and the
+/// compression as a `u8`.
+pub fn encode_chunk(chunk: &Chunk, compression: ChunkCompression) -> Vec<u8> {
+  let mut palette = Vec::new();
+  let indices = chunk
+    .blocks
+    .iter()
+    .map(|block| {
+      let value = voxel_to_u16(block.voxel());
+      match palette.iter().position(|entry| *entry == value) {
+        Some(index) => index as u32,
+        None => {
+          palette.push(value);
+          palette.len() as u32 - 1
+        }
+      }
+    })
+    .collect::<Vec<u32>>();
+
+  let mut body = Vec::new();
+  write_varint(&mut body, palette.len() as u32);
+  for value in &palette {
+    body.extend(value.to_le_bytes());
+  }
+  let mut runs = Vec::new();
+  write_runs(&mut runs, indices.iter().copied(), write_varint);
+  let packed = write_packed(&indices, index_bits(palette.len()));
+  if runs.len() <= packed.len() {
+    body.push(INDICES_RUNS);
+    body.extend(runs);
+  } else {
+    body.push(INDICES_PACKED);
+    body.extend(packed);
+  }
+
+  let mut data = Vec::with_capacity(body.len() + 8 + CHUNK_HEADER_LEN as usize);
+  data.extend(CHUNK_MAGIC);
+  data.push(CHUNK_FORMAT_VERSION);
+  data.push(CHUNK_MIN_READER_VERSION);
+  data.extend(CHUNK_HEADER_LEN.to_le_bytes());
+  for axis in chunk.size.to_array() {
+    data.extend(axis.to_le_bytes());
+  }
+  data.push(compression as u8);
+  match compression {
+    ChunkCompression::None => data.extend(body),
+    ChunkCompression::Deflate => {
+      let mut encoder = DeflateEncoder::new(data, flate2::Compression::fast());
+      // Writing to a `Vec` cannot fail.
+      encoder.write_all(&body).unwrap();
+      data = encoder.finish().unwrap();
+    }
+    #[cfg(feature = "lz4")]
+    ChunkCompression::Lz4 => data.extend(lz4_flex::compress_prepend_size(&body)),
+  }
+  data
+}
+
+/// Decodes a chunk written by `encode_chunk`, giving its blocks `block_size`. Fails instead of
+/// panicking on any malformed input.
+pub fn decode_chunk(data: &[u8], block_size: Vec3) -> io::Result<Chunk> {
+  let mut reader = data;
+  let mut magic = [0; 4];
+  reader.read_exact(&mut magic)?;
+  if &magic != CHUNK_MAGIC {
+    return Err(invalid_data(FORMAT, "not an encoded chunk"));
+  }
+  let mut versions = [0; 2];
+  reader.read_exact(&mut versions)?;
+  let [version, min_reader_version] = versions;
+  if min_reader_version > CHUNK_FORMAT_VERSION {
+    return Err(invalid_data(FORMAT, &format!(
+      "version {version} needs a reader of at least version {min_reader_version}"
+    )));
+  }
+  let header_len = u16::from_le_bytes(read_array(&mut reader)?) as usize;
+  if header_len < CHUNK_HEADER_LEN as usize || reader.len() < header_len {
+    return Err(invalid_data(FORMAT, "header is too short"));
+  }
+  let (mut header, body) = reader.split_at(header_len);
+  let [x, y, z] = [(); 3].map(|_| read_array(&mut header).map(u32::from_le_bytes));
+  let size = UVec3::new(x?, y?, z?);
+  let len = size.to_array().iter().try_fold(1usize, |len, axis| len.checked_mul(*axis as usize));
+  let len = match len {
+    Some(len) if len <= MAX_CHUNK_BLOCKS => len,
+    _ => return Err(invalid_data(FORMAT, &format!("size {size} is too large"))),
+  };
+  if len == 0 {
+    return Err(invalid_data(FORMAT, &format!("size {size} is empty")));
+  }
+
+  let [compression] = read_array(&mut header)?;
+  // Runs take at most 10 bytes per block, which bounds what a valid body decompresses to.
+  let max_len = len as u64 * 10 + 1024;
+  let body = match compression {
+    0 => body.to_vec(),
+    1 => decompress(DeflateDecoder::new(body), max_len)?,
+    #[cfg(feature = "lz4")]
+    2 => decompress_lz4(body, max_len)?,
+    _ => return Err(invalid_data(FORMAT, &format!("unknown compression {compression}"))),
+  };
+
+  let mut reader = body.as_slice();
+  let palette_len = read_varint(FORMAT, &mut reader)? as usize;
+  if palette_len == 0 || palette_len > 257 {
+    return Err(invalid_data(FORMAT, &format!("invalid palette length {palette_len}")));
+  }
+  let palette = (0..palette_len)
+    .map(|_| voxel_from_u16(FORMAT, u16::from_le_bytes(read_array(&mut reader)?)))
+    .collect::<io::Result<Vec<Option<BlockKind>>>>()?;
+  let [indices_encoding] = read_array(&mut reader)?;
+  let indices = match indices_encoding {
+    INDICES_RUNS => read_runs(FORMAT, &mut reader, len, |reader| read_varint(FORMAT, reader))?,
+    INDICES_PACKED => read_packed(reader, len, index_bits(palette_len))?,
+    _ => return Err(invalid_data(FORMAT, &format!("unknown index encoding {indices_encoding}"))),
+  };
+
+  let voxels = indices
+    .into_iter()
+    .map(|index| {
+      palette
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| invalid_data(FORMAT, "index outside palette"))
+    })
+    .collect::<io::Result<Vec<Option<BlockKind>>>>()?;
+  Ok(Chunk::from_voxels(&voxels, size, block_size))
+}
+
+/// Reads all of `decoder`, failing once it yields more than `max_len` bytes.
+fn decompress(decoder: impl Read, max_len: u64) -> io::Result<Vec<u8>> {
+  let mut decompressed = Vec::new();
+  decoder.take(max_len + 1).read_to_end(&mut decompressed)?;
+  if decompressed.len() as u64 > max_len {
+    return Err(invalid_data(FORMAT, "body is too large"));
+  }
+  Ok(decompressed)
+}
+
+#[cfg(feature = "lz4")]
+fn decompress_lz4(mut body: &[u8], max_len: u64) -> io::Result<Vec<u8>> {
+  let len = u32::from_le_bytes(read_array(&mut body)?);
+  if len as u64 > max_len {
+    return Err(invalid_data(FORMAT, "body is too large"));
+  }
+  let mut decompressed = vec![0; len as usize];
+  let written = lz4_flex::decompress_into(body, &mut decompressed)
+    .map_err(|err| invalid_data(FORMAT, &err.to_string()))?;
+  if written != decompressed.len() {
+    return Err(invalid_data(FORMAT, "body is shorter than its length"));
+  }
+  Ok(decompressed)
+}
+
+/// The bits needed to tell `palette_len` entries apart, which is 0 for a single entry.
+fn index_bits(palette_len: usize) -> u32 { usize::BITS - (palette_len.max(1) - 1).leading_zeros() }
+
+/// Packs `bits` per index, starting from the lowest bit of the first byte.
+fn write_packed(indices: &[u32], bits: u32) -> Vec<u8> {
+  let mut packed = vec![0; (indices.len() * bits as usize).div_ceil(8)];
+  for (i, index) in indices.iter().enumerate() {
+    for bit in 0..bits as usize {
+      if index >> bit & 1 != 0 {
+        let position = i * bits as usize + bit;
+        packed[position / 8] |= 1 << (position % 8);
+      }
+    }
+  }
+  packed
+}
+
+fn read_packed(packed: &[u8], len: usize, bits: u32) -> io::Result<Vec<u32>> {
+  if packed.len() != (len * bits as usize).div_ceil(8) {
+    return Err(invalid_data(FORMAT, "packed indices do not match the chunk size"));
+  }
+  Ok(
+    (0..len)
+      .map(|i| {
+        (0..bits as usize).fold(0, |index, bit| {
+          let position = i * bits as usize + bit;
+          index | ((packed[position / 8] >> (position % 8) & 1) as u32) << bit
+        })
+      })
+      .collect(),
+  )
+}
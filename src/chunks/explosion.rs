@@ -0,0 +1,231 @@
+use bevy::math::ivec3;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::utils::{
+  HashMap,
+  HashSet,
+};
+
+use crate::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkMaterials,
+  ChunkWorld,
+  ChunkWorldConfig,
+  RenderClasses,
+};
+
+/// The distance rays advance per step, in blocks.
+const RAY_STEP: f32 = 0.3;
+/// At most this many removed blocks are thrown as `Debris`.
+const MAX_DEBRIS: usize = 64;
+const DEBRIS_LIFETIME: f32 = 2.0;
+/// Debris acceleration, in blocks per second squared.
+const DEBRIS_GRAVITY: f32 = 40.0;
+
+/// How an explosion's strength falls off with distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Falloff {
+  /// Rays are cast in every direction, losing strength with distance and with the resistance of
+  /// every block they pass, so that tough blocks shelter the blocks behind them.
+  Rays,
+  /// Strength only falls off with distance from the center, carving a round crater.
+  Sphere,
+}
+
+/// Queued with `ChunkWorld::explode` or `ChunkWorld::explode_with`.
+#[derive(Debug, Clone, Copy)]
+pub struct Explosion {
+  /// In world space.
+  pub center: Vec3,
+  /// How far the explosion reaches through empty space, in blocks. A block is removed where the
+  /// remaining strength exceeds its `BlockResistances` entry.
+  pub power: f32,
+  pub falloff: Falloff,
+  /// Whether some of the removed blocks are thrown out as `Debris`.
+  pub debris: bool,
+}
+
+impl Explosion {
+  pub fn new(center: Vec3, power: f32) -> Explosion {
+    Explosion { center, power, falloff: Falloff::Rays, debris: false }
+  }
+
+  pub fn with_falloff(mut self, falloff: Falloff) -> Explosion {
+    self.falloff = falloff;
+    self
+  }
+
+  pub fn with_debris(mut self, debris: bool) -> Explosion {
+    self.debris = debris;
+    self
+  }
+
+  /// The blocks the explosion removes and their kinds, reading voxels with `read`, which returns
+  /// `None` for unloaded chunks.
+  pub(crate) fn blast(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    resistances: &BlockResistances,
+    read: impl Fn(IVec3) -> Option<Option<BlockKind>>,
+  ) -> Vec<(IVec3, BlockKind)> {
+    // Everything is measured in blocks, which need not be cubes in world space.
+    let center = self.center / chunk_world_config.block_size;
+    let mut removed = HashMap::new();
+    match self.falloff {
+      Falloff::Rays => {
+        for direction in ray_directions(self.power) {
+          let mut strength = self.power;
+          let mut distance = 0.0;
+          while strength > 0.0 {
+            let pos = (center + direction * distance).round().as_ivec3();
+            let Some(voxel) = read(pos) else { break };
+            strength -= RAY_STEP;
+            if let Some(kind) = voxel {
+              strength -= resistances.get(kind) * RAY_STEP;
+              if strength > 0.0 {
+                removed.insert(pos, kind);
+              }
+            }
+            distance += RAY_STEP;
+          }
+        }
+      }
+      Falloff::Sphere => {
+        let radius = self.power.ceil() as i32;
+        let origin = center.round().as_ivec3();
+        for x in -radius..=radius {
+          for y in -radius..=radius {
+            for z in -radius..=radius {
+              let pos = origin + ivec3(x, y, z);
+              let Some(Some(kind)) = read(pos) else { continue };
+              if self.power - pos.as_vec3().distance(center) > resistances.get(kind) {
+                removed.insert(pos, kind);
+              }
+            }
+          }
+        }
+      }
+    }
+    removed.into_iter().collect()
+  }
+
+  /// Debris thrown out from an even sample of the `removed` blocks.
+  pub(crate) fn debris(
+    &self,
+    chunk_world_config: &ChunkWorldConfig,
+    removed: &[(IVec3, BlockKind)],
+  ) -> Vec<(SpatialBundle, Debris)> {
+    let speed = self.power * 4.0;
+    removed
+      .iter()
+      .step_by(removed.len().div_ceil(MAX_DEBRIS).max(1))
+      .map(|(pos, kind)| {
+        let translation = chunk_world_config.block_to_world(*pos);
+        let away = (translation - self.center).normalize_or_zero();
+        let velocity = (away * speed + Vec3::Y * speed / 2.0) * chunk_world_config.block_size;
+        (
+          SpatialBundle::from_transform(Transform::from_translation(translation)),
+          Debris { kind: *kind, velocity, lifetime: DEBRIS_LIFETIME },
+        )
+      })
+      .collect()
+  }
+}
+
+/// The directions from the center of a cube to the blocks on its surface, with enough blocks that
+/// neighboring rays stay less than a block apart where the explosion ends.
+fn ray_directions(power: f32) -> Vec<Vec3> {
+  let n = ((power * 2.0).ceil() as i32).clamp(16, 64);
+  let mut directions = HashSet::new();
+  for x in 0..n {
+    for y in 0..n {
+      for z in 0..n {
+        let on_surface = [x, y, z].iter().any(|axis| *axis == 0 || *axis == n - 1);
+        if on_surface {
+          let direction = (ivec3(x, y, z).as_vec3() / (n - 1) as f32 * 2.0 - 1.0).normalize();
+          directions.insert(direction.to_array().map(f32::to_bits));
+        }
+      }
+    }
+  }
+  directions.into_iter().map(|bits| Vec3::from_array(bits.map(f32::from_bits))).collect()
+}
+
+/// How much of an explosion's strength each kind of block absorbs, set with
+/// `ChunksPlugin::with_block_resistance`.
+#[derive(Resource, Debug, Clone)]
+pub struct BlockResistances {
+  /// For kinds without their own entry.
+  pub default: f32,
+  pub kinds: HashMap<BlockKind, f32>,
+}
+
+impl BlockResistances {
+  pub fn get(&self, kind: BlockKind) -> f32 { self.kinds.get(&kind).copied().unwrap_or(self.default) }
+}
+
+impl Default for BlockResistances {
+  fn default() -> BlockResistances {
+    BlockResistances { default: 1.0, kinds: HashMap::new() }
+  }
+}
+
+/// A piece of a removed block thrown by an explosion, despawned once it hits a block or its
+/// lifetime runs out. Purely visual: it never turns back into a block.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Debris {
+  pub kind: BlockKind,
+  /// In world units per second.
+  pub velocity: Vec3,
+  /// Seconds left before it is despawned.
+  pub lifetime: f32,
+}
+
+pub(crate) fn move_debris(
+  mut commands: Commands,
+  time: Res<Time>,
+  chunk_world: Res<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunks: Query<&Chunk>,
+  mut debris: Query<(Entity, &mut Transform, &mut Debris)>,
+) {
+  let delta = time.delta_seconds();
+  for (entity, mut transform, mut debris) in &mut debris {
+    debris.velocity.y -= DEBRIS_GRAVITY * chunk_world_config.block_size.y * delta;
+    transform.translation += debris.velocity * delta;
+    debris.lifetime -= delta;
+    let pos = chunk_world_config.world_to_block(transform.translation);
+    let hit = chunk_world
+      .get_block(&chunk_world_config, &chunks, pos)
+      .is_some_and(|block| block.voxel().is_some());
+    if hit || debris.lifetime <= 0.0 {
+      commands.entity(entity).despawn();
+    }
+  }
+}
+
+/// Draws new debris as small cubes of the material of their block's class, when meshing is enabled.
+pub(crate) fn add_debris_meshes(
+  mut commands: Commands,
+  debris: Query<(Entity, &Debris), Added<Debris>>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunk_materials: Res<ChunkMaterials>,
+  render_classes: Res<RenderClasses>,
+  mut mesh_assets: ResMut<Assets<Mesh>>,
+  mut debris_mesh: Local<Option<Handle<Mesh>>>,
+) {
+  for (entity, debris) in &debris {
+    // Debris without a material is simulated without being drawn.
+    let Some(material) = chunk_materials.standard(render_classes.get(debris.kind)) else {
+      continue;
+    };
+    let mesh = debris_mesh
+      .get_or_insert_with(|| {
+        let size = chunk_world_config.block_size / 2.0;
+        mesh_assets.add(shape::Box::new(size.x, size.y, size.z).into())
+      })
+      .clone();
+    commands.entity(entity).insert((mesh, material, NotShadowCaster));
+  }
+}
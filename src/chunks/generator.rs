@@ -0,0 +1,60 @@
+use bevy::prelude::IVec3;
+use bracket_noise::prelude::{
+  FastNoise,
+  Interp,
+  NoiseType,
+};
+
+use crate::chunks::block::BlockKind;
+
+const WORLD_TO_NOISE: f32 = 0.027;
+
+/// Decides which blocks a chunk starts with when it is generated. Called from several threads at
+/// once.
+pub trait ChunkGenerator: Send + Sync + 'static {
+  /// The kind of the block at `pos`, in block coordinates, or `None` if it starts empty.
+  fn generate(&self, pos: IVec3) -> Option<BlockKind>;
+}
+
+/// Generates nothing, for chunks whose blocks come from elsewhere, such as a server.
+pub struct EmptyGenerator;
+
+impl ChunkGenerator for EmptyGenerator {
+  fn generate(&self, _pos: IVec3) -> Option<BlockKind> { None }
+}
+
+/// Heightmap terrain from 2D Perlin noise. Peaks rise and valleys sink up to `height` blocks
+/// around y = 0.
+pub struct TerrainGenerator {
+  pub noise: FastNoise,
+  pub height: u32,
+}
+
+impl TerrainGenerator {
+  pub fn new(seed: u64, height: u32) -> TerrainGenerator {
+    let mut noise = FastNoise::seeded(seed);
+    noise.set_noise_type(NoiseType::Perlin);
+    noise.set_fractal_octaves(5);
+    noise.set_fractal_gain(0.8);
+    noise.set_fractal_lacunarity(0.2);
+    noise.set_frequency(1.2);
+    noise.set_gradient_perterb_amp(2.2);
+    noise.set_interp(Interp::Hermite);
+    TerrainGenerator { noise, height }
+  }
+}
+
+impl ChunkGenerator for TerrainGenerator {
+  fn generate(&self, pos: IVec3) -> Option<BlockKind> {
+    let surface =
+      (self.noise.get_noise(pos.x as f32 * WORLD_TO_NOISE, pos.z as f32 * WORLD_TO_NOISE)
+        * self.height as f32)
+        .round() as i32;
+    // let solid = self.noise.get_noise3d(
+    // pos.x as f32 * WORLD_TO_NOISE,
+    // pos.y as f32 * WORLD_TO_NOISE,
+    // pos.z as f32 * WORLD_TO_NOISE,
+    // ) < 0.1;
+    (pos.y <= surface).then_some(BlockKind::default())
+  }
+}
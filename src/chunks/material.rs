@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::chunks::{
+  BlockKind,
+  ChunkWorldConfig,
+};
+use crate::voxel_material::VoxelMaterial;
+
+/// How the blocks of a mesh blend with what is behind them. Each class gets one material, shared by
+/// every mesh of that class so that they batch together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderClass {
+  Opaque,
+  /// Fully opaque or fully transparent per pixel, such as leaves.
+  Cutout,
+  /// Blended with what is behind, such as glass and water.
+  Translucent,
+}
+
+impl RenderClass {
+  pub const ALL: [RenderClass; 3] =
+    [RenderClass::Opaque, RenderClass::Cutout, RenderClass::Translucent];
+
+  pub fn alpha_mode(self) -> AlphaMode {
+    match self {
+      RenderClass::Opaque => AlphaMode::Opaque,
+      RenderClass::Cutout => AlphaMode::Mask(0.5),
+      RenderClass::Translucent => AlphaMode::Blend,
+    }
+  }
+}
+
+/// The `RenderClass` of each kind of block, set with `ChunksPlugin::with_render_class`.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct RenderClasses {
+  /// Kinds without an entry are opaque.
+  pub kinds: HashMap<BlockKind, RenderClass>,
+}
+
+impl RenderClasses {
+  pub fn get(&self, kind: BlockKind) -> RenderClass {
+    self.kinds.get(&kind).copied().unwrap_or(RenderClass::Opaque)
+  }
+
+  /// Whether a block of `kind` hides the face of a `neighbor` block touching it, which is when
+  /// nothing behind it shows through, or when both are of the same class like the panes of a glass
+  /// wall.
+  pub fn hides(&self, kind: BlockKind, neighbor: BlockKind) -> bool {
+    let class = self.get(kind);
+    class == RenderClass::Opaque || class == self.get(neighbor)
+  }
+}
+
+/// The material of each `RenderClass`, set with `ChunksPlugin::with_material` and
+/// `ChunksPlugin::with_class_material`, and the handles added for them once at startup when
+/// meshing is enabled. Every mesh of a class shares its handle, whether it is a chunk, a falling
+/// block or debris.
+#[derive(Resource, Debug, Clone)]
+pub struct ChunkMaterials {
+  materials: HashMap<RenderClass, StandardMaterial>,
+  standard: HashMap<RenderClass, Handle<StandardMaterial>>,
+  packed: HashMap<RenderClass, Handle<VoxelMaterial>>,
+}
+
+impl ChunkMaterials {
+  /// Uses `materials` for the classes it has, and `base` for the rest, all with the alpha mode of
+  /// their class.
+  pub(crate) fn new(
+    base: &StandardMaterial,
+    materials: &HashMap<RenderClass, StandardMaterial>,
+  ) -> ChunkMaterials {
+    let materials = RenderClass::ALL
+      .into_iter()
+      .map(|class| {
+        let material = materials.get(&class).unwrap_or(base);
+        (class, StandardMaterial { alpha_mode: class.alpha_mode(), ..material.clone() })
+      })
+      .collect();
+    ChunkMaterials { materials, standard: HashMap::new(), packed: HashMap::new() }
+  }
+
+  pub fn material(&self, class: RenderClass) -> &StandardMaterial { &self.materials[&class] }
+
+  /// The handle meshes of `class` in `MeshFormat::Standard` share, or `None` without meshing.
+  pub fn standard(&self, class: RenderClass) -> Option<Handle<StandardMaterial>> {
+    self.standard.get(&class).cloned()
+  }
+
+  /// The handle meshes of `class` in `MeshFormat::Packed` share, which only use the material's
+  /// `base_color` and `alpha_mode`. `None` without meshing.
+  pub fn packed(&self, class: RenderClass) -> Option<Handle<VoxelMaterial>> {
+    self.packed.get(&class).cloned()
+  }
+}
+
+pub(crate) fn add_chunk_materials(
+  mut chunk_materials: ResMut<ChunkMaterials>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut standard_material_assets: ResMut<Assets<StandardMaterial>>,
+  mut voxel_material_assets: ResMut<Assets<VoxelMaterial>>,
+) {
+  for class in RenderClass::ALL {
+    let material = chunk_materials.material(class).clone();
+    let packed = VoxelMaterial {
+      color: material.base_color,
+      block_size: chunk_world_config.block_size,
+      alpha_mode: material.alpha_mode,
+    };
+    chunk_materials.standard.insert(class, standard_material_assets.add(material));
+    chunk_materials.packed.insert(class, voxel_material_assets.add(packed));
+  }
+}
@@ -0,0 +1,92 @@
+//! Byte-level helpers shared by the binary formats: encoded chunks, schematics, `.vox` files and
+//! network messages. Errors name the format they come from, such as "invalid chunk: ...".
+
+use std::io::{
+  self,
+  Read,
+};
+
+use crate::chunks::BlockKind;
+
+pub fn invalid_data(format: &str, message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, format!("invalid {format}: {message}"))
+}
+
+/// Voxels are stored as a `u16`, 0 for `None` and the kind plus one otherwise.
+pub fn voxel_to_u16(voxel: Option<BlockKind>) -> u16 { voxel.map_or(0, |kind| kind.0 as u16 + 1) }
+
+pub fn voxel_from_u16(format: &str, value: u16) -> io::Result<Option<BlockKind>> {
+  match value {
+    0 => Ok(None),
+    1..=256 => Ok(Some(BlockKind((value - 1) as u8))),
+    _ => Err(invalid_data(format, &format!("unknown block value {value}"))),
+  }
+}
+
+pub fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+  let mut array = [0; N];
+  reader.read_exact(&mut array)?;
+  Ok(array)
+}
+
+/// Reads an unsigned LEB128 value of at most 32 bits.
+pub fn read_varint(format: &str, reader: &mut impl Read) -> io::Result<u32> {
+  let mut value = 0;
+  for shift in (0..32).step_by(7) {
+    let [byte] = read_array(reader)?;
+    value |= ((byte & 0x7f) as u32) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err(invalid_data(format, "varint overflows"))
+}
+
+pub fn write_varint(buf: &mut Vec<u8>, mut value: u32) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      buf.push(byte);
+      return;
+    }
+    buf.push(byte | 0x80);
+  }
+}
+
+/// Writes each run of equal values as its length, a LEB128 varint, followed by the value.
+pub fn write_runs<T: PartialEq>(
+  buf: &mut Vec<u8>,
+  values: impl IntoIterator<Item = T>,
+  mut write_value: impl FnMut(&mut Vec<u8>, T),
+) {
+  let mut values = values.into_iter().peekable();
+  while let Some(value) = values.next() {
+    let mut run = 1;
+    while values.next_if_eq(&value).is_some() {
+      run += 1;
+    }
+    write_varint(buf, run);
+    write_value(buf, value);
+  }
+}
+
+/// Reads runs written by [`write_runs`] until they add up to `len` values. Fails on empty runs and
+/// on runs going past `len`.
+pub fn read_runs<R: Read, T: Clone>(
+  format: &str,
+  reader: &mut R,
+  len: usize,
+  mut read_value: impl FnMut(&mut R) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
+  let mut values = Vec::new();
+  while values.len() < len {
+    let run = read_varint(format, reader)? as usize;
+    let value = read_value(reader)?;
+    if run == 0 || values.len() + run > len {
+      return Err(invalid_data(format, &format!("run does not fit the {format} size")));
+    }
+    values.extend(std::iter::repeat_n(value, run));
+  }
+  Ok(values)
+}
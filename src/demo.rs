@@ -0,0 +1,74 @@
+//! The demo's gameplay: editing tools, a heads-up display and their bindings. Built into the demo
+//! binary only, as an example of what a game adds on top of the library.
+
+pub mod actions;
+pub mod editing;
+pub mod hud;
+
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use first_voxel_engine::actions::ActionsPlugin;
+use first_voxel_engine::chunks::{
+  BlockChanged,
+  ChunkGenerated,
+  ChunkMeshed,
+  ChunkUnloaded,
+};
+
+use crate::demo::actions::DemoAction;
+use crate::demo::editing::EditingPlugin;
+use crate::demo::hud::HudPlugin;
+
+/// How far away blocks can be broken, placed and looked at, in world units.
+pub const BLOCK_REACH: f32 = 8.0;
+
+/// Everything the demo adds to the engine. Keeps an `ActionMap<DemoAction>` inserted before it.
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_plugin(ActionsPlugin::<DemoAction>::new())
+      .add_plugin(EditingPlugin)
+      .add_plugin(HudPlugin)
+      .add_system(grab_mouse)
+      .add_system(log_chunk_events);
+  }
+}
+
+fn grab_mouse(mut windows: Query<&mut Window>, actions: Res<Input<DemoAction>>) {
+  let mut window = windows.single_mut();
+
+  if actions.just_pressed(DemoAction::GrabMouse) {
+    if window.cursor.visible {
+      window.cursor.visible = false;
+      window.cursor.grab_mode = CursorGrabMode::Confined;
+    } else {
+      window.cursor.visible = true;
+      window.cursor.grab_mode = CursorGrabMode::None;
+    }
+  }
+}
+
+fn log_chunk_events(
+  mut chunk_generated_events: EventReader<ChunkGenerated>,
+  mut chunk_meshed_events: EventReader<ChunkMeshed>,
+  mut block_changed_events: EventReader<BlockChanged>,
+  mut chunk_unloaded_events: EventReader<ChunkUnloaded>,
+) {
+  for event in chunk_generated_events.iter() {
+    trace!("Generated chunk {} as {:?}", event.key, event.entity);
+  }
+  for event in chunk_meshed_events.iter() {
+    trace!("Meshed chunk {} ({:?})", event.key, event.entity);
+  }
+  for event in block_changed_events.iter() {
+    debug!(
+      "Block {} in chunk {} ({:?}) changed from {:?} to {:?}",
+      event.pos, event.key, event.entity, event.before, event.after
+    );
+  }
+  for event in chunk_unloaded_events.iter() {
+    debug!("Unloaded chunk {} ({:?})", event.key, event.entity);
+  }
+}
@@ -0,0 +1,104 @@
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+use first_voxel_engine::actions::{
+  ActionMap,
+  Binding,
+  InputAction,
+};
+use serde::{
+  Deserialize,
+  Serialize,
+};
+
+/// Where the bindings of `DemoAction` are kept.
+pub const ACTIONS_PATH: &str = "actions.ron";
+/// Where the bindings of the engine's `Action` are kept.
+pub const DEBUG_ACTIONS_PATH: &str = "debug_actions.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DemoAction {
+  ToggleDebugOverlay,
+  GrabMouse,
+  BreakBlock,
+  PlaceBlock,
+  StampProp,
+  SaveRegion,
+  ExportRegion,
+  SetFirstCorner,
+  SetSecondCorner,
+  CopySelection,
+  PasteClipboard,
+  RotateClipboard,
+  MirrorClipboardX,
+  MirrorClipboardY,
+  MirrorClipboardZ,
+  SaveClipboard,
+  LoadClipboard,
+  Undo,
+  Redo,
+  Sculpt,
+  CycleBrushShape,
+  CycleBrushMode,
+  ShrinkBrush,
+  GrowBrush,
+  Explode,
+}
+
+impl InputAction for DemoAction {
+  fn default_bindings() -> Vec<(DemoAction, Vec<Binding>)> {
+    use Binding::*;
+    vec![
+      (DemoAction::ToggleDebugOverlay, vec![Key(KeyCode::F3)]),
+      (DemoAction::GrabMouse, vec![Key(KeyCode::M), Gamepad(GamepadButtonType::Select)]),
+      (
+        DemoAction::BreakBlock,
+        vec![Mouse(MouseButton::Left), Gamepad(GamepadButtonType::RightTrigger2)],
+      ),
+      (
+        DemoAction::PlaceBlock,
+        vec![Mouse(MouseButton::Right), Gamepad(GamepadButtonType::LeftTrigger2)],
+      ),
+      (DemoAction::StampProp, vec![Key(KeyCode::P)]),
+      (DemoAction::SaveRegion, vec![Key(KeyCode::O)]),
+      (DemoAction::ExportRegion, vec![Key(KeyCode::I)]),
+      (DemoAction::SetFirstCorner, vec![Key(KeyCode::Key1)]),
+      (DemoAction::SetSecondCorner, vec![Key(KeyCode::Key2)]),
+      (DemoAction::CopySelection, vec![Key(KeyCode::C)]),
+      (DemoAction::PasteClipboard, vec![Key(KeyCode::V)]),
+      (DemoAction::RotateClipboard, vec![Key(KeyCode::R)]),
+      (DemoAction::MirrorClipboardX, vec![Key(KeyCode::F)]),
+      (DemoAction::MirrorClipboardY, vec![Key(KeyCode::G)]),
+      (DemoAction::MirrorClipboardZ, vec![Key(KeyCode::H)]),
+      (DemoAction::SaveClipboard, vec![Key(KeyCode::F5)]),
+      (DemoAction::LoadClipboard, vec![Key(KeyCode::F9)]),
+      (DemoAction::Undo, vec![Key(KeyCode::Z), Gamepad(GamepadButtonType::West)]),
+      (DemoAction::Redo, vec![Key(KeyCode::Y), Gamepad(GamepadButtonType::North)]),
+      (
+        DemoAction::Sculpt,
+        vec![Mouse(MouseButton::Middle), Gamepad(GamepadButtonType::RightTrigger)],
+      ),
+      (DemoAction::CycleBrushShape, vec![Key(KeyCode::B)]),
+      (DemoAction::CycleBrushMode, vec![Key(KeyCode::N)]),
+      (DemoAction::ShrinkBrush, vec![Key(KeyCode::LBracket)]),
+      (DemoAction::GrowBrush, vec![Key(KeyCode::RBracket)]),
+      (DemoAction::Explode, vec![Key(KeyCode::X)]),
+    ]
+  }
+}
+
+/// Loads the bindings at `path`, writing out the defaults first if there is no file yet so that
+/// there is one to edit.
+pub fn load_action_map<A: InputAction>(path: &str) -> io::Result<ActionMap<A>> {
+  match ActionMap::load(Path::new(path)) {
+    Err(error) if error.kind() == io::ErrorKind::NotFound => {
+      let action_map = ActionMap::default();
+      if let Err(error) = action_map.save(path.as_ref()) {
+        eprintln!("warning: could not write {path}: {error}");
+      }
+      Ok(action_map)
+    }
+    result => result,
+  }
+}
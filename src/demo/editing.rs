@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+use first_voxel_engine::brush::{
+  Brush,
+  BrushMode,
+  BrushShape,
+};
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkLoadingPoint,
+  ChunkWorld,
+  ChunkWorldConfig,
+  Explosion,
+};
+use first_voxel_engine::export;
+use first_voxel_engine::schematic::{
+  Axis,
+  Schematic,
+};
+use first_voxel_engine::vox;
+use first_voxel_engine::vox::{
+  VoxFile,
+  VoxModel,
+};
+use rand::Rng;
+
+use crate::demo::actions::DemoAction;
+use crate::demo::BLOCK_REACH;
+
+/// The `.vox` file passed with `--vox`, stamped into the world with `P`.
+#[derive(Resource)]
+pub struct Prop(pub Option<VoxFile>);
+
+/// The selection corners set with `1` and `2`, and the last copied or loaded schematic.
+#[derive(Resource, Default)]
+struct Clipboard {
+  corners: [Option<IVec3>; 2],
+  schematic: Option<Schematic>,
+}
+
+/// How far in front of the camera brush strokes land, in world units.
+const BRUSH_DISTANCE: f32 = 4.0;
+
+/// The marker previewing the brush in front of the camera.
+#[derive(Component)]
+struct BrushPreview;
+
+/// Breaks and places blocks, stamps props, saves and exports regions, copies and pastes
+/// schematics, undoes edits, blows up blocks and sculpts with the brush, all with `DemoAction`s.
+/// Expects a `Prop`.
+pub struct EditingPlugin;
+
+impl Plugin for EditingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<Clipboard>()
+      .insert_resource(Brush { shape: BrushShape::Sphere, mode: BrushMode::Erase, radius: 1.5 })
+      .add_startup_system(spawn_brush_preview)
+      .add_system(edit_blocks)
+      .add_system(stamp_prop)
+      .add_system(save_region)
+      .add_system(export_region)
+      .add_system(edit_clipboard)
+      .add_system(undo_edits)
+      .add_system(explode)
+      .add_system(sculpt);
+  }
+}
+
+fn edit_blocks(
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  let (break_block, place_block) =
+    (actions.just_pressed(DemoAction::BreakBlock), actions.just_pressed(DemoAction::PlaceBlock));
+  if !break_block && !place_block {
+    return;
+  }
+  for clp_transform in &clps {
+    let Some((hit, previous)) = chunk_world.raycast(
+      &chunk_world_config,
+      &chunks,
+      clp_transform.translation,
+      clp_transform.forward(),
+      BLOCK_REACH,
+    ) else {
+      continue;
+    };
+    if break_block {
+      chunk_world.set_blocks([(hit, None)]);
+    } else if previous != hit {
+      chunk_world.set_blocks([(previous, Some(BlockKind::default()))]);
+    }
+  }
+}
+
+fn stamp_prop(
+  prop: Res<Prop>,
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  if let (true, Some(vox_file)) = (actions.just_pressed(DemoAction::StampProp), &prop.0) {
+    for clp_transform in &clps {
+      let origin = chunk_world_config.world_to_block(clp_transform.translation);
+      for model in &vox_file.models {
+        model.stamp(&mut chunk_world, origin);
+      }
+    }
+  }
+}
+
+/// Saves the 32x32x32 blocks around the camera to `region.vox` with `O`.
+fn save_region(
+  prop: Res<Prop>,
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunk_world: Res<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  if actions.just_pressed(DemoAction::SaveRegion) {
+    for clp_transform in &clps {
+      let center = chunk_world_config.world_to_block(clp_transform.translation);
+      let vox_file = VoxFile {
+        models: vec![VoxModel::from_world(
+          &chunk_world,
+          &chunk_world_config,
+          &chunks,
+          center - 16,
+          center + 16,
+        )],
+        palette: prop.0.as_ref().map_or_else(vox::default_palette, |vox_file| vox_file.palette),
+      };
+      match vox_file.save("region.vox".as_ref()) {
+        Ok(()) => info!("Saved region around {center} to region.vox"),
+        Err(error) => error!("Failed to save region.vox: {error}"),
+      }
+    }
+  }
+}
+
+fn export_region(
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunk_world: Res<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  if actions.just_pressed(DemoAction::ExportRegion) {
+    for (clp_transform, clp) in &clps {
+      let (min, max) = chunk_world_config.chunks_within(clp_transform.translation, clp.radius);
+      let mesh_data = chunk_world.region_mesh_data(&chunk_world_config, &chunks, min, max);
+      match export::export(&mesh_data, "region.glb".as_ref()) {
+        Ok(()) => info!("Exported chunks {min}..{max} to region.glb"),
+        Err(error) => error!("Failed to export region.glb: {error}"),
+      }
+    }
+  }
+}
+
+fn edit_clipboard(
+  mut clipboard: ResMut<Clipboard>,
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  let Ok(clp_transform) = clps.get_single() else { return };
+  let pos = chunk_world_config.world_to_block(clp_transform.translation);
+
+  if actions.just_pressed(DemoAction::SetFirstCorner) {
+    clipboard.corners[0] = Some(pos);
+  }
+  if actions.just_pressed(DemoAction::SetSecondCorner) {
+    clipboard.corners[1] = Some(pos);
+  }
+  if let (true, [Some(a), Some(b)]) =
+    (actions.just_pressed(DemoAction::CopySelection), clipboard.corners)
+  {
+    clipboard.schematic =
+      Some(Schematic::copy(&chunk_world, &chunk_world_config, &chunks, a.min(b), a.max(b) + 1));
+  }
+
+  if let Some(schematic) = &clipboard.schematic {
+    if actions.just_pressed(DemoAction::PasteClipboard) {
+      schematic.paste(&mut chunk_world, pos);
+    }
+    if actions.just_pressed(DemoAction::RotateClipboard) {
+      clipboard.schematic = Some(schematic.rotate_y(1));
+    } else if let Some(axis) = [
+      (DemoAction::MirrorClipboardX, Axis::X),
+      (DemoAction::MirrorClipboardY, Axis::Y),
+      (DemoAction::MirrorClipboardZ, Axis::Z),
+    ]
+    .into_iter()
+    .find_map(|(action, axis)| actions.just_pressed(action).then_some(axis))
+    {
+      clipboard.schematic = Some(schematic.mirror(axis));
+    } else if actions.just_pressed(DemoAction::SaveClipboard) {
+      if let Err(error) = schematic.save("clipboard.schem".as_ref()) {
+        error!("Failed to save clipboard.schem: {error}");
+      }
+    }
+  }
+  if actions.just_pressed(DemoAction::LoadClipboard) {
+    match Schematic::load("clipboard.schem".as_ref()) {
+      Ok(schematic) => clipboard.schematic = Some(schematic),
+      Err(error) => error!("Failed to load clipboard.schem: {error}"),
+    }
+  }
+}
+
+fn undo_edits(mut chunk_world: ResMut<ChunkWorld>, actions: Res<Input<DemoAction>>) {
+  if actions.just_pressed(DemoAction::Undo) {
+    chunk_world.undo();
+  }
+  if actions.just_pressed(DemoAction::Redo) {
+    chunk_world.redo();
+  }
+}
+
+/// Blows up the block the camera looks at with `X`.
+fn explode(
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  actions: Res<Input<DemoAction>>,
+) {
+  if !actions.just_pressed(DemoAction::Explode) {
+    return;
+  }
+  for clp_transform in &clps {
+    if let Some((hit, _)) = chunk_world.raycast(
+      &chunk_world_config,
+      &chunks,
+      clp_transform.translation,
+      clp_transform.forward(),
+      BLOCK_REACH,
+    ) {
+      let center = chunk_world_config.block_to_world(hit);
+      chunk_world.explode_with(Explosion::new(center, 4.0).with_debris(true));
+    }
+  }
+}
+
+fn spawn_brush_preview(
+  mut commands: Commands,
+  brush: Res<Brush>,
+  mut mesh_assets: ResMut<Assets<Mesh>>,
+  mut material_assets: ResMut<Assets<StandardMaterial>>,
+) {
+  commands
+    .spawn(PbrBundle {
+      mesh: mesh_assets.add(brush.outline()),
+      material: material_assets.add(StandardMaterial { unlit: true, ..Color::WHITE.into() }),
+      ..Default::default()
+    })
+    .insert(BrushPreview);
+}
+
+/// Strokes the brush in front of the camera, cycles its shape and mode, and resizes it.
+#[allow(clippy::too_many_arguments)]
+fn sculpt(
+  mut brush: ResMut<Brush>,
+  clps: Query<&Transform, (With<ChunkLoadingPoint>, Without<BrushPreview>)>,
+  mut previews: Query<(&mut Transform, &Handle<Mesh>), With<BrushPreview>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  mut mesh_assets: ResMut<Assets<Mesh>>,
+  actions: Res<Input<DemoAction>>,
+) {
+  let Ok(clp_transform) = clps.get_single() else { return };
+  let center = clp_transform.translation + clp_transform.forward() * BRUSH_DISTANCE;
+
+  if actions.just_pressed(DemoAction::CycleBrushShape) {
+    brush.shape = match brush.shape {
+      BrushShape::Sphere => BrushShape::Box,
+      BrushShape::Box => BrushShape::Cylinder,
+      BrushShape::Cylinder => BrushShape::Sphere,
+    };
+  }
+  if actions.just_pressed(DemoAction::CycleBrushMode) {
+    brush.mode = match brush.mode {
+      BrushMode::Fill(_) => BrushMode::Erase,
+      BrushMode::Erase => BrushMode::Smooth,
+      BrushMode::Smooth => {
+        BrushMode::Displace { amplitude: 2.0, frequency: 0.2, seed: rand::thread_rng().gen() }
+      }
+      BrushMode::Displace { .. } => BrushMode::Fill(BlockKind::default()),
+    };
+    info!("Brush mode: {:?}", brush.mode);
+  }
+  if actions.just_pressed(DemoAction::ShrinkBrush) {
+    brush.radius = (brush.radius - chunk_world_config.block_size.min_element()).max(0.5);
+  }
+  if actions.just_pressed(DemoAction::GrowBrush) {
+    brush.radius += chunk_world_config.block_size.min_element();
+  }
+
+  for (mut preview_transform, preview_mesh) in &mut previews {
+    preview_transform.translation = center;
+    if brush.is_changed() {
+      mesh_assets.set_untracked(preview_mesh, brush.outline());
+    }
+  }
+
+  if actions.just_pressed(DemoAction::Sculpt) {
+    brush.stroke(&mut chunk_world, &chunk_world_config, &chunks, center);
+  }
+}
@@ -0,0 +1,122 @@
+use bevy::diagnostic::{
+  Diagnostics,
+  FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  Chunk,
+  ChunkLoadingPoint,
+  ChunkWorld,
+  ChunkWorldConfig,
+  ChunksPlugin,
+};
+
+use crate::demo::actions::DemoAction;
+use crate::demo::BLOCK_REACH;
+
+#[derive(Component)]
+struct FpsText;
+
+/// The chunk and world statistics toggled with `DemoAction::ToggleDebugOverlay`.
+#[derive(Component)]
+struct DebugText;
+
+/// Shows the frame rate, and the chunk and world statistics toggled with
+/// `DemoAction::ToggleDebugOverlay`.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_plugin(FrameTimeDiagnosticsPlugin)
+      .add_startup_system(spawn_texts)
+      .add_system(draw_fps)
+      .add_system(draw_debug_overlay);
+  }
+}
+
+fn spawn_texts(mut commands: Commands, asset_server: Res<AssetServer>) {
+  let font = asset_server.load("fonts/fff-forward.ttf");
+  commands
+    .spawn(
+      TextBundle::from_sections([
+        TextSection::new(
+          "FPS: ",
+          TextStyle { font: font.clone(), font_size: 30.0, color: Color::BLACK },
+        ),
+        TextSection::from_style(TextStyle {
+          font: font.clone(),
+          font_size: 30.0,
+          color: Color::BLACK,
+        }),
+      ])
+      .with_text_alignment(TextAlignment::Left),
+    )
+    .insert(FpsText);
+  commands.spawn((
+    TextBundle {
+      text: Text::from_section("", TextStyle { font, font_size: 16.0, color: Color::BLACK }),
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: UiRect { top: Val::Px(40.0), left: Val::Px(0.0), ..Default::default() },
+        ..Default::default()
+      },
+      visibility: Visibility::Hidden,
+      ..Default::default()
+    },
+    DebugText,
+  ));
+}
+
+fn draw_fps(mut texts: Query<&mut Text, With<FpsText>>, diagnostics: ResMut<Diagnostics>) {
+  for mut text in &mut texts {
+    if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS).and_then(|d| d.smoothed()) {
+      text.sections[1].value = fps.round().to_string();
+    }
+  }
+}
+
+fn draw_debug_overlay(
+  mut texts: Query<(&mut Text, &mut Visibility), With<DebugText>>,
+  clps: Query<&Transform, With<ChunkLoadingPoint>>,
+  chunks: Query<&Chunk>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunk_world: Res<ChunkWorld>,
+  diagnostics: Res<Diagnostics>,
+  actions: Res<Input<DemoAction>>,
+) {
+  let Ok((mut text, mut visibility)) = texts.get_single_mut() else { return };
+  if actions.just_pressed(DemoAction::ToggleDebugOverlay) {
+    *visibility = match *visibility {
+      Visibility::Hidden => Visibility::Inherited,
+      _ => Visibility::Hidden,
+    };
+  }
+  let Ok(clp_transform) = clps.get_single() else { return };
+  if *visibility == Visibility::Hidden {
+    return;
+  }
+
+  let position = clp_transform.translation;
+  let looked_at = chunk_world
+    .raycast(&chunk_world_config, &chunks, position, clp_transform.forward(), BLOCK_REACH)
+    .map_or_else(|| "none".to_string(), |(hit, _)| hit.to_string());
+  let value = |id| diagnostics.get(id).and_then(|d| d.value()).unwrap_or_default();
+  let average = |id| diagnostics.get(id).and_then(|d| d.average()).unwrap_or_default();
+  text.sections[0].value = format!(
+    "Position: {position:.1}\n\
+     Chunk: {}\n\
+     Looking at: {looked_at}\n\
+     Chunks: {} loaded, {} dirty, {} culled\n\
+     Vertices: {}, triangles: {}\n\
+     Generation: {:.3} ms, meshing: {:.3} ms per chunk",
+    chunk_world_config.world_to_chunk(position),
+    value(ChunksPlugin::LOADED_CHUNKS),
+    value(ChunksPlugin::DIRTY_CHUNKS),
+    value(ChunksPlugin::CULLED_CHUNKS),
+    value(ChunksPlugin::CHUNK_VERTICES),
+    value(ChunksPlugin::CHUNK_TRIANGLES),
+    average(ChunksPlugin::GENERATION_TIME),
+    average(ChunksPlugin::MESHING_TIME),
+  );
+}
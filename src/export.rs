@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{
+  self,
+  BufWriter,
+  Write,
+};
+use std::path::Path;
+
+use bevy::render::render_resource::PrimitiveTopology;
+
+use crate::mesh_data::MeshData;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+/// Writes `mesh_data` to `path` as Wavefront OBJ or binary glTF, picked from the extension.
+pub fn export(mesh_data: &MeshData, path: &Path) -> io::Result<()> {
+  let write: fn(&MeshData, &mut BufWriter<File>) -> io::Result<()> =
+    match path.extension().and_then(|extension| extension.to_str()) {
+      Some("obj") => write_obj,
+      Some("glb") => write_glb,
+      _ => {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("unsupported export format: {}", path.display()),
+        ))
+      }
+    };
+  let mut writer = BufWriter::new(File::create(path)?);
+  write(mesh_data, &mut writer)?;
+  writer.flush()
+}
+
+pub fn write_obj(mesh_data: &MeshData, writer: &mut impl Write) -> io::Result<()> {
+  writeln!(writer, "# first-voxel-engine")?;
+  for [x, y, z] in &mesh_data.vertex_positions {
+    writeln!(writer, "v {x} {y} {z}")?;
+  }
+  for [x, y, z] in &mesh_data.vertex_normals {
+    writeln!(writer, "vn {x} {y} {z}")?;
+  }
+  for [u, v] in &mesh_data.vertex_uvs {
+    writeln!(writer, "vt {u} {v}")?;
+  }
+
+  // OBJ indices are 1-based, and every vertex attribute shares the same index. Attributes the mesh
+  // lacks are left out of the references, and lines never reference normals.
+  let has_normals = !mesh_data.vertex_normals.is_empty();
+  let has_uvs = !mesh_data.vertex_uvs.is_empty();
+  let vertex = |index: u32, normals: bool| {
+    let i = index + 1;
+    match (has_uvs, normals && has_normals) {
+      (true, true) => format!("{i}/{i}/{i}"),
+      (true, false) => format!("{i}/{i}"),
+      (false, true) => format!("{i}//{i}"),
+      (false, false) => i.to_string(),
+    }
+  };
+  match mesh_data.primitive_topology {
+    PrimitiveTopology::LineList => {
+      for line in mesh_data.indices.chunks_exact(2) {
+        writeln!(writer, "l {} {}", vertex(line[0], false), vertex(line[1], false))?;
+      }
+    }
+    _ => {
+      for triangle in mesh_data.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertex(triangle[i], true));
+        writeln!(writer, "f {a} {b} {c}")?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+pub fn write_glb(mesh_data: &MeshData, writer: &mut impl Write) -> io::Result<()> {
+  if mesh_data.vertex_positions.is_empty() || mesh_data.indices.is_empty() {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot export an empty mesh to glTF"));
+  }
+
+  let (min, max) = mesh_data.vertex_positions.iter().fold(
+    ([f32::MAX; 3], [f32::MIN; 3]),
+    |(min, max), position| {
+      (
+        [min[0].min(position[0]), min[1].min(position[1]), min[2].min(position[2])],
+        [max[0].max(position[0]), max[1].max(position[1]), max[2].max(position[2])],
+      )
+    },
+  );
+  let [min_x, min_y, min_z] = min;
+  let [max_x, max_y, max_z] = max;
+  let vertex_count = mesh_data.vertex_positions.len();
+  let index_count = mesh_data.indices.len();
+
+  // Each accessor reads its own buffer view, so both share an index.
+  let mut bin = Vec::new();
+  let mut views = Vec::new();
+  let mut accessors = Vec::new();
+  let mut add_accessor = |bytes: Vec<u8>, target: u32, accessor: String| {
+    views.push(format!(
+      r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{target}}}"#,
+      bin.len(),
+      bytes.len()
+    ));
+    bin.extend(bytes);
+    accessors.push(format!(r#"{{"bufferView":{},{accessor}}}"#, accessors.len()));
+    accessors.len() - 1
+  };
+
+  let position = add_accessor(
+    mesh_data.vertex_positions.iter().flatten().flat_map(|f| f.to_le_bytes()).collect(),
+    34962,
+    format!(
+      r#""componentType":5126,"count":{vertex_count},"type":"VEC3",
+      "min":[{min_x},{min_y},{min_z}],"max":[{max_x},{max_y},{max_z}]"#
+    ),
+  );
+  let mut attributes = vec![format!(r#""POSITION":{position}"#)];
+  // Meshes without normals or UVs, such as line lists, leave their attributes out.
+  if !mesh_data.vertex_normals.is_empty() {
+    let normal = add_accessor(
+      mesh_data.vertex_normals.iter().flatten().flat_map(|f| f.to_le_bytes()).collect(),
+      34962,
+      format!(r#""componentType":5126,"count":{vertex_count},"type":"VEC3""#),
+    );
+    attributes.push(format!(r#""NORMAL":{normal}"#));
+  }
+  if !mesh_data.vertex_uvs.is_empty() {
+    let uv = add_accessor(
+      mesh_data.vertex_uvs.iter().flatten().flat_map(|f| f.to_le_bytes()).collect(),
+      34962,
+      format!(r#""componentType":5126,"count":{vertex_count},"type":"VEC2""#),
+    );
+    attributes.push(format!(r#""TEXCOORD_0":{uv}"#));
+  }
+  let indices = add_accessor(
+    mesh_data.indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+    34963,
+    format!(r#""componentType":5125,"count":{index_count},"type":"SCALAR""#),
+  );
+
+  let bin_length = bin.len();
+  let attributes = attributes.join(",");
+  let views = views.join(",");
+  let accessors = accessors.join(",\n    ");
+  let mode = match mesh_data.primitive_topology {
+    PrimitiveTopology::LineList => 1,
+    _ => 4,
+  };
+
+  let mut json = format!(
+    r#"{{
+  "asset":{{"version":"2.0","generator":"first-voxel-engine"}},
+  "scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],
+  "meshes":[{{"primitives":[{{
+    "attributes":{{{attributes}}},"indices":{indices},"mode":{mode}
+  }}]}}],
+  "buffers":[{{"byteLength":{bin_length}}}],"bufferViews":[{views}],"accessors":[
+    {accessors}
+  ]
+}}"#
+  )
+  .into_bytes();
+
+  // Both chunks must be 4-byte aligned: JSON is padded with spaces, binary data with zeros.
+  json.resize(json.len().next_multiple_of(4), b' ');
+  bin.resize(bin.len().next_multiple_of(4), 0);
+
+  writer.write_all(&GLB_MAGIC.to_le_bytes())?;
+  writer.write_all(&2u32.to_le_bytes())?;
+  writer.write_all(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes())?;
+  writer.write_all(&(json.len() as u32).to_le_bytes())?;
+  writer.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+  writer.write_all(&json)?;
+  writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+  writer.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+  writer.write_all(&bin)
+}
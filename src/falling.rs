@@ -0,0 +1,223 @@
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::utils::{
+  HashMap,
+  HashSet,
+};
+
+use crate::chunks::{
+  BlockChanged,
+  BlockKind,
+  Chunk,
+  ChunkMaterials,
+  ChunkWorld,
+  ChunkWorldConfig,
+  PendingVoxels,
+  RenderClasses,
+};
+use crate::net::Client;
+
+/// How unsupported falling blocks come down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallMode {
+  /// Columns drop to the ground within the frame.
+  Instant,
+  /// Blocks turn into `FallingBlock` entities that turn back into blocks when they land.
+  Entities,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct FallingBlockSettings {
+  /// The kinds that fall when the block below them is empty, such as sand and gravel.
+  pub kinds: HashSet<BlockKind>,
+  pub mode: FallMode,
+  /// The acceleration of `FallingBlock` entities, in world units per second squared.
+  pub gravity: f32,
+}
+
+/// A block falling as an entity in `FallMode::Entities`, placed back in the world where it lands.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FallingBlock {
+  pub kind: BlockKind,
+  /// Downwards, in world units per second.
+  pub velocity: f32,
+}
+
+/// Drops blocks of the `FallingBlockSettings::kinds` that an edit leaves without support, along
+/// with the falling blocks stacked on them. Falls are applied like `ChunkWorld::sync_blocks`, so
+/// they are not undoable. Requires `ChunksPlugin`. Blocks do not fall while a `Client` is
+/// connected, since falls are not sent and the server makes its own.
+pub struct FallingBlocksPlugin {
+  settings: FallingBlockSettings,
+}
+
+impl FallingBlocksPlugin {
+  /// Drops `kinds` instantly.
+  pub fn new(kinds: impl IntoIterator<Item = BlockKind>) -> FallingBlocksPlugin {
+    FallingBlocksPlugin {
+      settings: FallingBlockSettings {
+        kinds: kinds.into_iter().collect(),
+        mode: FallMode::Instant,
+        gravity: 20.0,
+      },
+    }
+  }
+
+  pub fn with_mode(mut self, mode: FallMode) -> FallingBlocksPlugin {
+    self.settings.mode = mode;
+    self
+  }
+
+  pub fn with_gravity(mut self, gravity: f32) -> FallingBlocksPlugin {
+    self.settings.gravity = gravity;
+    self
+  }
+}
+
+impl Plugin for FallingBlocksPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(self.settings.clone())
+      .add_system(move_falling_blocks.run_if(not(resource_exists::<Client>())))
+      // Drops the blocks that this frame's edits left unsupported, see `ChunksPlugin`.
+      .add_system(
+        drop_unsupported_blocks.in_base_set(CoreSet::Last).run_if(not(resource_exists::<Client>())),
+      );
+  }
+}
+
+/// Whether something holds up a block at `pos`. Unloaded chunks do, so that blocks do not fall out
+/// of the world.
+fn supports(blocks: &PendingVoxels, pos: IVec3) -> bool { blocks.get(pos) != Some(None) }
+
+#[allow(clippy::too_many_arguments)]
+fn drop_unsupported_blocks(
+  mut commands: Commands,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunks: Query<&Chunk>,
+  settings: Res<FallingBlockSettings>,
+  chunk_materials: Res<ChunkMaterials>,
+  render_classes: Res<RenderClasses>,
+  mut mesh_assets: Option<ResMut<Assets<Mesh>>>,
+  mut block_changed_events: EventReader<BlockChanged>,
+  mut falling_block_mesh: Local<Option<Handle<Mesh>>>,
+) {
+  // A change can leave the block itself or the one above it unsupported.
+  let candidates = block_changed_events
+    .iter()
+    .flat_map(|block_changed| [block_changed.pos, block_changed.pos + IVec3::Y])
+    .collect::<Vec<IVec3>>();
+  if candidates.is_empty() {
+    return;
+  }
+
+  let read = |pos| chunk_world.get_voxel(&chunk_world_config, &chunks, pos);
+  let mut blocks = PendingVoxels::new(&read, HashMap::new());
+  let mut edits = Vec::new();
+  let mut write = |blocks: &mut PendingVoxels, pos, voxel| {
+    blocks.set(pos, voxel);
+    edits.push((pos, voxel));
+  };
+  let falls =
+    |voxel: Option<Option<BlockKind>>| voxel.flatten().filter(|kind| settings.kinds.contains(kind));
+
+  for pos in candidates {
+    if falls(blocks.get(pos)).is_none() || supports(&blocks, pos - IVec3::Y) {
+      continue;
+    }
+    // The falling blocks stacked on `pos` come down with it.
+    let mut column = Vec::new();
+    while let Some(kind) = falls(blocks.get(pos + IVec3::Y * column.len() as i32)) {
+      column.push(kind);
+    }
+    for i in 0..column.len() {
+      write(&mut blocks, pos + IVec3::Y * i as i32, None);
+    }
+
+    match settings.mode {
+      FallMode::Instant => {
+        let mut landing = pos - IVec3::Y;
+        while !supports(&blocks, landing - IVec3::Y) {
+          landing -= IVec3::Y;
+        }
+        for (i, kind) in column.into_iter().enumerate() {
+          write(&mut blocks, landing + IVec3::Y * i as i32, Some(kind));
+        }
+      }
+      FallMode::Entities => {
+        // Drawn when the app renders, as a cube of the material of the block's class.
+        let mesh = mesh_assets.as_mut().map(|mesh_assets| {
+          let mesh = falling_block_mesh.get_or_insert_with(|| {
+            let size = chunk_world_config.block_size;
+            mesh_assets.add(shape::Box::new(size.x, size.y, size.z).into())
+          });
+          mesh.clone()
+        });
+        for (i, kind) in column.into_iter().enumerate() {
+          let transform = Transform::from_translation(
+            chunk_world_config.block_to_world(pos + IVec3::Y * i as i32),
+          );
+          let mut falling_block = commands.spawn((
+            SpatialBundle::from_transform(transform),
+            FallingBlock { kind, velocity: 0.0 },
+          ));
+          let material = chunk_materials.standard(render_classes.get(kind));
+          if let Some((mesh, material)) = mesh.clone().zip(material) {
+            falling_block.insert((mesh, material, NotShadowCaster));
+          }
+        }
+      }
+    }
+  }
+
+  if !edits.is_empty() {
+    chunk_world.sync_blocks(edits);
+  }
+}
+
+/// Accelerates `FallingBlock` entities and places them back in the world on the first support
+/// they pass.
+fn move_falling_blocks(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunks: Query<&Chunk>,
+  settings: Res<FallingBlockSettings>,
+  mut falling_blocks: Query<(Entity, &mut Transform, &mut FallingBlock)>,
+) {
+  let read = |pos| chunk_world.get_voxel(&chunk_world_config, &chunks, pos);
+  let mut blocks = PendingVoxels::new(&read, HashMap::new());
+  for (entity, mut transform, mut falling_block) in &mut falling_blocks {
+    falling_block.velocity += settings.gravity * time.delta_seconds();
+    let from = chunk_world_config.world_to_block(transform.translation);
+    transform.translation.y -= falling_block.velocity * time.delta_seconds();
+    let to = chunk_world_config.world_to_block(transform.translation);
+
+    // Checking every block passed keeps fast blocks from going through thin floors.
+    let mut pos = from;
+    let landing = loop {
+      let landed = transform.translation.y <= chunk_world_config.block_to_world(pos).y;
+      if landed && supports(&blocks, pos - IVec3::Y) {
+        break Some(pos);
+      }
+      if pos.y <= to.y {
+        break None;
+      }
+      pos -= IVec3::Y;
+    };
+    let Some(mut landing) = landing else { continue };
+    // Something may have been placed where it falls, or another block landed there first.
+    while blocks.get(landing).flatten().is_some() {
+      landing += IVec3::Y;
+    }
+    blocks.set(landing, Some(falling_block.kind));
+    commands.entity(entity).despawn();
+  }
+
+  let edits = blocks.into_edits().into_iter().collect::<Vec<_>>();
+  if !edits.is_empty() {
+    chunk_world.sync_blocks(edits);
+  }
+}
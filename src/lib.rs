@@ -0,0 +1,18 @@
+//! A voxel engine for Bevy. Add `chunks::ChunksPlugin` to an `App` and give an entity a
+//! `chunks::ChunkLoadingPoint` to generate, mesh and edit the world around it.
+
+mod codec;
+
+pub mod actions;
+pub mod brush;
+pub mod chunk_debug;
+pub mod chunks;
+pub mod export;
+pub mod falling;
+pub mod mesh_data;
+pub mod net;
+pub mod pathfinding;
+pub mod schematic;
+pub mod ticks;
+pub mod vox;
+pub mod voxel_material;
@@ -1,28 +1,39 @@
-mod chunks;
-mod mesh_data;
+mod demo;
+
+use std::io;
+use std::path::PathBuf;
 
 use bevy::core_pipeline::tonemapping::{
   DebandDither,
   Tonemapping,
 };
-use bevy::diagnostic::{
-  Diagnostics,
-  FrameTimeDiagnosticsPlugin,
-};
+use bevy::ecs::system::SystemState;
 use bevy::math::{
+  ivec3,
   uvec3,
   vec3,
 };
 use bevy::pbr::DirectionalLightShadowMap;
 use bevy::prelude::*;
-use bevy::window::CursorGrabMode;
-use bracket_noise::prelude::*;
-use chunks::{
+use bevy::render::settings::{
+  WgpuFeatures,
+  WgpuSettings,
+};
+use bevy::render::RenderPlugin;
+use first_voxel_engine::actions::Action;
+use first_voxel_engine::chunks::{
+  Chunk,
   ChunkLoadingPoint,
+  ChunkWorld,
   ChunkWorldConfig,
   ChunksPlugin,
-  Noise,
+  EmptyGenerator,
+  TerrainGenerator,
 };
+use first_voxel_engine::export;
+use first_voxel_engine::mesh_data::MeshFormat;
+use first_voxel_engine::net::ClientPlugin;
+use first_voxel_engine::vox::VoxFile;
 use rand::Rng;
 use smooth_bevy_cameras::controllers::fps::{
   FpsCameraBundle,
@@ -31,49 +42,194 @@ use smooth_bevy_cameras::controllers::fps::{
 };
 use smooth_bevy_cameras::LookTransformPlugin;
 
-#[derive(Component)]
-struct FpsText;
+use crate::demo::actions::{
+  load_action_map,
+  DemoAction,
+  ACTIONS_PATH,
+  DEBUG_ACTIONS_PATH,
+};
+use crate::demo::editing::Prop;
+use crate::demo::DemoPlugin;
+
+/// How far peaks rise above and valleys sink below y = 0, in blocks.
+const TERRAIN_HEIGHT: u32 = 16;
 
 fn main() {
-  let mut rng = rand::thread_rng();
-  let mut noise = FastNoise::seeded(rng.gen());
-  noise.set_noise_type(NoiseType::Perlin);
-  noise.set_fractal_octaves(5);
-  noise.set_fractal_gain(0.8);
-  noise.set_fractal_lacunarity(0.2);
-  noise.set_frequency(1.2);
-  noise.set_gradient_perterb_amp(2.2);
-  noise.set_interp(Interp::Hermite);
-
-  App::new()
-    .add_plugins(DefaultPlugins.set(WindowPlugin {
-      primary_window: Some(Window {
-        title: "First Voxel Engine".to_string(),
-        resolution: (800.0, 600.0).into(),
-        ..Default::default()
-      }),
-      ..Default::default()
-    }))
+  let args = std::env::args().skip(1).collect::<Vec<String>>();
+  let command_result = match args.first().map(String::as_str) {
+    Some("export") => Some(export_command(&args[1..])),
+    _ => None,
+  };
+  if let Some(result) = command_result {
+    if let Err(error) = result {
+      eprintln!("error: {error}");
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  let prop = match args.iter().position(|arg| arg == "--vox").map(|i| args.get(i + 1)) {
+    Some(Some(path)) => match VoxFile::load(path.as_ref()) {
+      Ok(vox_file) => Some(vox_file),
+      Err(error) => {
+        eprintln!("error: {path}: {error}");
+        std::process::exit(1);
+      }
+    },
+    Some(None) => {
+      eprintln!("error: --vox expects a path");
+      std::process::exit(1);
+    }
+    None => None,
+  };
+
+  // Chunks start empty and are filled by the server instead of being generated.
+  let client = match args.iter().position(|arg| arg == "--connect").map(|i| args.get(i + 1)) {
+    Some(Some(addr)) => match ClientPlugin::connect(addr.as_str()) {
+      Ok(client) => Some(client),
+      Err(error) => {
+        eprintln!("error: {addr}: {error}");
+        std::process::exit(1);
+      }
+    },
+    Some(None) => {
+      eprintln!("error: --connect expects an address");
+      std::process::exit(1);
+    }
+    None => None,
+  };
+
+  let (action_map, debug_action_map) = match (
+    load_action_map::<DemoAction>(ACTIONS_PATH),
+    load_action_map::<Action>(DEBUG_ACTIONS_PATH),
+  ) {
+    (Ok(action_map), Ok(debug_action_map)) => (action_map, debug_action_map),
+    (Err(error), _) | (_, Err(error)) => {
+      eprintln!("error: {ACTIONS_PATH} or {DEBUG_ACTIONS_PATH}: {error}");
+      std::process::exit(1);
+    }
+  };
+
+  let mut app = App::new();
+  app
+    .add_plugins(
+      DefaultPlugins
+        .set(WindowPlugin {
+          primary_window: Some(Window {
+            title: "First Voxel Engine".to_string(),
+            resolution: (800.0, 600.0).into(),
+            ..Default::default()
+          }),
+          ..Default::default()
+        })
+        .set(RenderPlugin {
+          wgpu_settings: WgpuSettings {
+            // Needed by the `DebugRender::WIREFRAME` overlay.
+            features: WgpuFeatures::POLYGON_MODE_LINE,
+            ..Default::default()
+          },
+        }),
+    )
     .add_plugin(LookTransformPlugin)
     .add_plugin(FpsCameraPlugin::default())
-    .add_plugin(FrameTimeDiagnosticsPlugin::default())
-    .add_plugin(ChunksPlugin)
     .insert_resource(ClearColor(Color::AQUAMARINE))
     .insert_resource(AmbientLight { brightness: 0.1, ..Default::default() })
     .insert_resource(DirectionalLightShadowMap { size: 4096 })
-    .insert_resource(Noise(noise))
-    .insert_resource(ChunkWorldConfig {
-      y_chunks: 2,
-      chunk_size: uvec3(8, 8, 8),
-      block_size: vec3(0.5, 0.5, 0.5),
-    })
-    .add_startup_system(setup)
-    .add_system(grab_mouse)
-    .add_system(draw_fps)
-    .run();
+    .insert_resource(action_map)
+    .insert_resource(debug_action_map)
+    .insert_resource(Prop(prop))
+    .add_plugin(DemoPlugin)
+    .add_startup_system(setup);
+  let chunks_plugin =
+    ChunksPlugin::new(chunk_world_config()).with_actions(true).with_debug_render(true);
+  match client {
+    Some(client) => {
+      app.add_plugin(chunks_plugin.with_generator(EmptyGenerator)).add_plugin(client);
+    }
+    None => {
+      app.add_plugin(
+        chunks_plugin
+          .with_generator(TerrainGenerator::new(rand::thread_rng().gen(), TERRAIN_HEIGHT)),
+      );
+    }
+  }
+  app.run();
+}
+
+fn chunk_world_config() -> ChunkWorldConfig {
+  ChunkWorldConfig {
+    chunk_size: uvec3(16, 16, 16),
+    block_size: vec3(0.5, 0.5, 0.5),
+    mesh_format: MeshFormat::Packed,
+    history_len: 64,
+  }
+}
+
+/// `export <path.obj|path.glb> [--radius <chunks>] [--seed <seed>]`, without opening a window.
+fn export_command(args: &[String]) -> io::Result<()> {
+  let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+
+  let mut path = None;
+  let mut radius = 8;
+  let mut seed = rand::thread_rng().gen();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--radius" => {
+        radius = args
+          .next()
+          .and_then(|radius| radius.parse().ok())
+          .ok_or_else(|| invalid("--radius expects a number of chunks"))?
+      }
+      "--seed" => {
+        seed = args
+          .next()
+          .and_then(|seed| seed.parse().ok())
+          .ok_or_else(|| invalid("--seed expects an unsigned integer"))?
+      }
+      _ => path = Some(PathBuf::from(arg)),
+    }
+  }
+  let path = path.ok_or_else(|| {
+    invalid("usage: export <path.obj|path.glb> [--radius <chunks>] [--seed <seed>]")
+  })?;
+
+  let config = chunk_world_config();
+  // Peaks reach y = TERRAIN_HEIGHT itself, which starts the next chunk up when it is a multiple of
+  // the chunk height.
+  let y_chunks = (TERRAIN_HEIGHT + 1).div_ceil(config.chunk_size.y) as i32;
+  let (min, max) = (ivec3(-radius, -y_chunks, -radius), ivec3(radius, y_chunks, radius));
+  let mut app = App::new();
+  app.add_plugins(MinimalPlugins).add_plugin(
+    ChunksPlugin::new(config.clone())
+      .with_generator(TerrainGenerator::new(seed, TERRAIN_HEIGHT))
+      .with_meshing(false),
+  );
+  // Loading points load cubes, so the region is tiled with cubes as tall as the terrain instead of
+  // loading one as tall as the region is wide. Each reaches the centers of its edge blocks.
+  let tile = 2 * y_chunks;
+  for x in (min.x..max.x).step_by(tile as usize) {
+    for z in (min.z..max.z).step_by(tile as usize) {
+      let first = config.chunk_translation(ivec3(x, min.y, z));
+      let last = config.chunk_translation(ivec3(x, min.y, z) + tile) - config.block_size;
+      app.world.spawn((
+        Transform::from_translation((first + last) / 2.0),
+        ChunkLoadingPoint { radius: (last.x - first.x) / 2.0, lod_radii: [f32::INFINITY; 3] },
+      ));
+    }
+  }
+  app.update();
+
+  let mut state =
+    SystemState::<(Res<ChunkWorld>, Res<ChunkWorldConfig>, Query<&Chunk>)>::new(&mut app.world);
+  let (chunk_world, chunk_world_config, chunks) = state.get(&app.world);
+  let mesh_data = chunk_world.region_mesh_data(&chunk_world_config, &chunks, min, max);
+  export::export(&mesh_data, &path)?;
+  println!("Exported {} vertices to {}", mesh_data.vertex_positions.len(), path.display());
+  Ok(())
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup(mut commands: Commands) {
   commands
     .spawn(Camera3dBundle {
       tonemapping: Tonemapping::AcesFitted,
@@ -86,21 +242,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
       vec3(0.0, 0.0, 0.0),
       Vec3::Y,
     ))
-    .insert(ChunkLoadingPoint { radius: 40.0 });
-
-  let font = asset_server.load("fonts/fff-forward.ttf");
-  commands
-    .spawn(
-      TextBundle::from_sections([
-        TextSection::new(
-          "FPS: ",
-          TextStyle { font: font.clone(), font_size: 30.0, color: Color::BLACK },
-        ),
-        TextSection::from_style(TextStyle { font, font_size: 30.0, color: Color::BLACK }),
-      ])
-      .with_text_alignment(TextAlignment::Left),
-    )
-    .insert(FpsText);
+    .insert(ChunkLoadingPoint { radius: 40.0, lod_radii: [16.0, 24.0, 32.0] });
 
   commands.spawn(DirectionalLightBundle {
     directional_light: DirectionalLight {
@@ -121,25 +263,3 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     ..Default::default()
   });
 }
-
-fn grab_mouse(mut windows: Query<&mut Window>, key: Res<Input<KeyCode>>) {
-  let mut window = windows.single_mut();
-
-  if key.just_pressed(KeyCode::M) {
-    if window.cursor.visible {
-      window.cursor.visible = false;
-      window.cursor.grab_mode = CursorGrabMode::Confined;
-    } else {
-      window.cursor.visible = true;
-      window.cursor.grab_mode = CursorGrabMode::None;
-    }
-  }
-}
-
-fn draw_fps(mut texts: Query<&mut Text, With<FpsText>>, diagnostics: ResMut<Diagnostics>) {
-  for mut text in &mut texts {
-    if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS).and_then(|d| d.smoothed()) {
-      text.sections[1].value = fps.round().to_string();
-    }
-  }
-}
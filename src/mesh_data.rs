@@ -1,8 +1,29 @@
-use bevy::prelude::Mesh;
-use bevy::render::mesh::Indices;
-use bevy::render::render_resource::PrimitiveTopology;
+use bevy::prelude::{
+  HandleUntyped,
+  Mesh,
+  Shader,
+  UVec3,
+  Vec3,
+};
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::{
+  Indices,
+  MeshVertexAttribute,
+};
+use bevy::render::render_resource::{
+  PrimitiveTopology,
+  VertexFormat,
+};
 use bitflags::bitflags;
 
+pub const ATTRIBUTE_PACKED_VERTEX: MeshVertexAttribute =
+  MeshVertexAttribute::new("Vertex_Packed", 219_807_131, VertexFormat::Uint32x2);
+
+/// `assets/shaders/packed_vertex.wgsl`, imported as `first_voxel_engine::packed_vertex` by the
+/// shaders reading packed meshes. Loaded by `ChunksPlugin` and `ChunkDebugPlugin`.
+pub const PACKED_VERTEX_SHADER_HANDLE: HandleUntyped =
+  HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x51c8_0e7f_a3d2_964b);
+
 bitflags! {
   #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
   pub struct MeshDataInsert: u8 {
@@ -11,12 +32,77 @@ bitflags! {
   }
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+  /// `f32` positions, normals and UVs, rendered with `StandardMaterial`.
+  #[default]
+  Standard,
+  /// One [`PackedVertex`] per vertex, rendered with `VoxelMaterial`.
+  Packed,
+}
+
+/// A voxel face vertex packed into two `u32`s, decoded by `assets/shaders/packed_vertex.wgsl` for
+/// the shaders of `VoxelMaterial` and `ChunkDebugMaterial`.
+///
+/// The first word holds the corner position in blocks (8 bits per axis), the face index
+/// (3 bits, in `Face` bit order) and the ambient occlusion level (2 bits, 3 is unoccluded). The
+/// second word holds the texture layer (16 bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedVertex {
+  pub position: UVec3,
+  pub face: u32,
+  pub ao: u32,
+  pub layer: u32,
+}
+
+impl PackedVertex {
+  /// The most blocks a chunk can have along any axis for its corners to fit a packed position.
+  pub const MAX_CHUNK_SIZE: u32 = 0xff;
+
+  pub fn pack(&self) -> [u32; 2] {
+    debug_assert!(self.position.cmple(UVec3::splat(0xff)).all());
+    debug_assert!(self.face <= 0b111 && self.ao <= 0b11 && self.layer <= 0xffff);
+    [
+      self.position.x
+        | self.position.y << 8
+        | self.position.z << 16
+        | self.face << 24
+        | self.ao << 27,
+      self.layer,
+    ]
+  }
+
+  pub fn unpack(packed: [u32; 2]) -> PackedVertex {
+    PackedVertex {
+      position: UVec3::new(packed[0] & 0xff, packed[0] >> 8 & 0xff, packed[0] >> 16 & 0xff),
+      face: packed[0] >> 24 & 0b111,
+      ao: packed[0] >> 27 & 0b11,
+      layer: packed[1] & 0xffff,
+    }
+  }
+
+  fn face_from_normal(normal: [f32; 3]) -> u32 {
+    match normal {
+      [_, _, z] if z > 0.5 => 0,
+      [_, _, z] if z < -0.5 => 1,
+      [x, _, _] if x > 0.5 => 2,
+      [x, _, _] if x < -0.5 => 3,
+      [_, y, _] if y > 0.5 => 4,
+      _ => 5,
+    }
+  }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct MeshData {
   pub primitive_topology: PrimitiveTopology,
   pub vertex_positions: Vec<[f32; 3]>,
   pub vertex_normals: Vec<[f32; 3]>,
   pub vertex_uvs: Vec<[f32; 2]>,
+  /// The ambient occlusion level of each vertex, from 0 for fully occluded to 3 for unoccluded.
+  pub vertex_ao: Vec<u32>,
+  /// The texture layer of each vertex, which is the kind of the block it belongs to.
+  pub vertex_layers: Vec<u32>,
   pub indices: Vec<u32>,
 }
 
@@ -24,8 +110,11 @@ impl MeshData {
   fn is_vertices_in_sync(&self) -> bool {
     self.vertex_positions.len() == self.vertex_normals.len()
       && self.vertex_normals.len() == self.vertex_uvs.len()
+      && self.vertex_uvs.len() == self.vertex_ao.len()
+      && self.vertex_ao.len() == self.vertex_layers.len()
   }
 
+  /// Adds `vertices` unoccluded and on texture layer 0, and `indices` as `insert` asks.
   pub fn insert(
     &mut self,
     vertices: Vec<([f32; 3], [f32; 3], [f32; 2])>,
@@ -60,6 +149,61 @@ impl MeshData {
     self.vertex_positions.extend(vertices.iter().map(|(vertex_positions, _, _)| vertex_positions));
     self.vertex_normals.extend(vertices.iter().map(|(_, vertex_normals, _)| vertex_normals));
     self.vertex_uvs.extend(vertices.iter().map(|(_, _, vertex_uvs)| vertex_uvs));
+    self.vertex_ao.resize(self.vertex_positions.len(), 3);
+    self.vertex_layers.resize(self.vertex_positions.len(), 0);
+  }
+
+  pub fn append(&mut self, other: MeshData, translation: Vec3) {
+    let start = self.vertex_positions.len();
+    let (ao, layers) = (other.vertex_ao, other.vertex_layers);
+    self.insert(
+      other
+        .vertex_positions
+        .into_iter()
+        .zip(other.vertex_normals)
+        .zip(other.vertex_uvs)
+        .map(|((p, n), u)| ((Vec3::from(p) + translation).into(), n, u))
+        .collect(),
+      other.indices,
+      MeshDataInsert::INDICES_OFFSET,
+    );
+    self.vertex_ao[start..].copy_from_slice(&ao);
+    self.vertex_layers[start..].copy_from_slice(&layers);
+  }
+
+  /// Packs every vertex, assuming the positions lie on block corners of a chunk whose first block
+  /// is centered on the origin.
+  pub fn packed_vertices(&self, block_size: Vec3) -> Vec<[u32; 2]> {
+    assert!(self.is_vertices_in_sync());
+    self
+      .vertex_positions
+      .iter()
+      .zip(&self.vertex_normals)
+      .zip(self.vertex_ao.iter().zip(&self.vertex_layers))
+      .map(|((position, normal), (ao, layer))| {
+        let vertex = PackedVertex {
+          position: (Vec3::from(*position) / block_size + 0.5).round().as_uvec3(),
+          face: PackedVertex::face_from_normal(*normal),
+          ao: *ao,
+          layer: *layer,
+        };
+        let packed = vertex.pack();
+        debug_assert_eq!(PackedVertex::unpack(packed), vertex);
+        packed
+      })
+      .collect()
+  }
+
+  pub fn into_mesh(self, mesh_format: MeshFormat, block_size: Vec3) -> Mesh {
+    match mesh_format {
+      MeshFormat::Standard => self.into(),
+      MeshFormat::Packed => {
+        let mut mesh = Mesh::new(self.primitive_topology);
+        mesh.insert_attribute(ATTRIBUTE_PACKED_VERTEX, self.packed_vertices(block_size));
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+      }
+    }
   }
 }
 
@@ -0,0 +1,19 @@
+mod client;
+mod protocol;
+mod server;
+
+pub use client::{
+  Client,
+  ClientPlugin,
+};
+pub use protocol::{
+  ClientMessage,
+  Connection,
+  Message,
+  Reader,
+  ServerMessage,
+};
+pub use server::{
+  Server,
+  ServerPlugin,
+};
@@ -0,0 +1,128 @@
+use std::io;
+use std::net::{
+  TcpStream,
+  ToSocketAddrs,
+};
+
+use bevy::prelude::*;
+
+use crate::chunks::{
+  decode_chunk,
+  BlockChanged,
+  ChunkLoadingPoint,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use crate::net::protocol::{
+  ClientMessage,
+  Connection,
+  ServerMessage,
+};
+
+/// Fills the world from a server instead of a generator, and sends local edits to it. Pair it
+/// with `ChunksPlugin::with_generator(EmptyGenerator)` so that chunks start empty until the server
+/// sends their blocks. The first `ChunkLoadingPoint` decides which chunks the server sends.
+pub struct ClientPlugin {
+  stream: TcpStream,
+}
+
+impl ClientPlugin {
+  /// Connects to the server at `addr`, blocking until it accepts.
+  pub fn connect(addr: impl ToSocketAddrs) -> io::Result<ClientPlugin> {
+    Ok(ClientPlugin { stream: TcpStream::connect(addr)? })
+  }
+}
+
+impl Plugin for ClientPlugin {
+  fn build(&self, app: &mut App) {
+    let stream = self.stream.try_clone().expect("Failed to clone the client stream");
+    let connection = Connection::new(stream).expect("Failed to set up the server connection");
+    app
+      .insert_resource(Client { connection, loading_range: None })
+      .add_system(
+        receive_server_messages.in_base_set(CoreSet::First).run_if(resource_exists::<Client>()),
+      )
+      .add_system(
+        send_client_messages.in_base_set(CoreSet::Last).run_if(resource_exists::<Client>()),
+      );
+  }
+}
+
+/// The connection to the server, removed once it is lost.
+#[derive(Resource)]
+pub struct Client {
+  connection: Connection,
+  /// The chunks the server was last told to send, as `min..max`.
+  loading_range: Option<(IVec3, IVec3)>,
+}
+
+fn receive_server_messages(
+  mut commands: Commands,
+  mut client: ResMut<Client>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+) {
+  let messages = match client.connection.receive::<ServerMessage>() {
+    Ok(messages) => messages,
+    Err(error) => {
+      error!("Lost the connection to the server: {error}");
+      commands.remove_resource::<Client>();
+      return;
+    }
+  };
+  for message in messages {
+    match message {
+      ServerMessage::Hello { chunk_size } => {
+        if chunk_size != chunk_world_config.chunk_size {
+          error!(
+            "The server's chunks are {chunk_size} blocks, but ours are {}",
+            chunk_world_config.chunk_size
+          );
+          commands.remove_resource::<Client>();
+          return;
+        }
+      }
+      ServerMessage::Chunk { key, data } => {
+        match decode_chunk(&data, chunk_world_config.block_size) {
+          Ok(chunk) => chunk_world.replace_chunk(key, chunk),
+          Err(error) => warn!("Ignored chunk {key} from the server: {error}"),
+        }
+      }
+      ServerMessage::Blocks(edits) => chunk_world.sync_blocks(edits),
+    }
+  }
+}
+
+fn send_client_messages(
+  mut commands: Commands,
+  mut client: ResMut<Client>,
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut block_changed_events: EventReader<BlockChanged>,
+) {
+  if let Some((clp_transform, clp)) = clps.iter().next() {
+    let loading_range = chunk_world_config.chunks_within(clp_transform.translation, clp.radius);
+    if client.loading_range != Some(loading_range) {
+      client.loading_range = Some(loading_range);
+      client.connection.send(&ClientMessage::LoadingPoint {
+        translation: clp_transform.translation,
+        radius: clp.radius,
+      });
+    }
+  }
+
+  // Changes from the server are already there.
+  let edits = block_changed_events
+    .iter()
+    .filter(|block_changed| !block_changed.remote)
+    .map(|block_changed| (block_changed.pos, block_changed.after))
+    .collect::<Vec<_>>();
+  if !edits.is_empty() {
+    client.connection.send(&ClientMessage::SetBlocks(edits));
+  }
+
+  if let Err(error) = client.connection.flush() {
+    error!("Lost the connection to the server: {error}");
+    commands.remove_resource::<Client>();
+  }
+}
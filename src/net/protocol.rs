@@ -0,0 +1,242 @@
+use std::io::{
+  self,
+  Read,
+  Write,
+};
+use std::net::TcpStream;
+
+use bevy::prelude::{
+  IVec3,
+  UVec3,
+  Vec3,
+};
+
+use crate::chunks::BlockKind;
+use crate::codec::{
+  invalid_data,
+  voxel_from_u16,
+  voxel_to_u16,
+};
+
+const FORMAT: &str = "message";
+
+/// Messages are framed by their length as a little-endian `u32`, and larger frames are rejected.
+const MAX_FRAME_LEN: usize = 16 << 20;
+
+/// How far a peer may fall behind reading before the connection is dropped.
+const MAX_QUEUED_LEN: usize = 4 * MAX_FRAME_LEN;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+  /// Where the client's `ChunkLoadingPoint` is, sent whenever the chunks it covers change.
+  LoadingPoint { translation: Vec3, radius: f32 },
+  /// Local edits, in block coordinates, for the server to apply and broadcast.
+  SetBlocks(Vec<(IVec3, Option<BlockKind>)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+  /// Sent first, so that clients can check that their chunks are the same size as the server's.
+  Hello { chunk_size: UVec3 },
+  /// The blocks of a chunk around the client's loading point, from `encode_chunk`.
+  Chunk { key: IVec3, data: Vec<u8> },
+  /// Changes to blocks of chunks the client was sent, in block coordinates.
+  Blocks(Vec<(IVec3, Option<BlockKind>)>),
+}
+
+pub trait Message: Sized {
+  fn write(&self, buf: &mut Vec<u8>);
+  fn read(reader: &mut Reader) -> io::Result<Self>;
+}
+
+impl Message for ClientMessage {
+  fn write(&self, buf: &mut Vec<u8>) {
+    match self {
+      ClientMessage::LoadingPoint { translation, radius } => {
+        buf.push(0);
+        for value in translation.to_array() {
+          buf.extend(value.to_le_bytes());
+        }
+        buf.extend(radius.to_le_bytes());
+      }
+      ClientMessage::SetBlocks(edits) => {
+        buf.push(1);
+        write_edits(buf, edits);
+      }
+    }
+  }
+
+  fn read(reader: &mut Reader) -> io::Result<ClientMessage> {
+    match reader.u8()? {
+      0 => Ok(ClientMessage::LoadingPoint {
+        translation: Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?),
+        radius: reader.f32()?,
+      }),
+      1 => Ok(ClientMessage::SetBlocks(read_edits(reader)?)),
+      tag => Err(invalid_data(FORMAT, &format!("unknown client message {tag}"))),
+    }
+  }
+}
+
+impl Message for ServerMessage {
+  fn write(&self, buf: &mut Vec<u8>) {
+    match self {
+      ServerMessage::Hello { chunk_size } => {
+        buf.push(0);
+        for value in chunk_size.to_array() {
+          buf.extend(value.to_le_bytes());
+        }
+      }
+      ServerMessage::Chunk { key, data } => {
+        buf.push(1);
+        write_ivec3(buf, *key);
+        buf.extend(data);
+      }
+      ServerMessage::Blocks(edits) => {
+        buf.push(2);
+        write_edits(buf, edits);
+      }
+    }
+  }
+
+  fn read(reader: &mut Reader) -> io::Result<ServerMessage> {
+    match reader.u8()? {
+      0 => Ok(ServerMessage::Hello {
+        chunk_size: UVec3::new(reader.u32()?, reader.u32()?, reader.u32()?),
+      }),
+      1 => Ok(ServerMessage::Chunk { key: reader.ivec3()?, data: reader.rest().to_vec() }),
+      2 => Ok(ServerMessage::Blocks(read_edits(reader)?)),
+      tag => Err(invalid_data(FORMAT, &format!("unknown server message {tag}"))),
+    }
+  }
+}
+
+/// Reads the fields of a message, failing instead of panicking on truncated input.
+pub struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+  fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+    if self.0.len() < len {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (bytes, rest) = self.0.split_at(len);
+    self.0 = rest;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> io::Result<u8> { Ok(self.bytes(1)?[0]) }
+
+  fn u16(&mut self) -> io::Result<u16> { Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap())) }
+
+  fn u32(&mut self) -> io::Result<u32> { Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap())) }
+
+  fn i32(&mut self) -> io::Result<i32> { Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap())) }
+
+  fn f32(&mut self) -> io::Result<f32> { Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap())) }
+
+  fn ivec3(&mut self) -> io::Result<IVec3> { Ok(IVec3::new(self.i32()?, self.i32()?, self.i32()?)) }
+
+  fn rest(&mut self) -> &'a [u8] { std::mem::take(&mut self.0) }
+}
+
+fn write_ivec3(buf: &mut Vec<u8>, value: IVec3) {
+  for value in value.to_array() {
+    buf.extend(value.to_le_bytes());
+  }
+}
+
+fn write_edits(buf: &mut Vec<u8>, edits: &[(IVec3, Option<BlockKind>)]) {
+  buf.extend((edits.len() as u32).to_le_bytes());
+  for (pos, voxel) in edits {
+    write_ivec3(buf, *pos);
+    buf.extend(voxel_to_u16(*voxel).to_le_bytes());
+  }
+}
+
+fn read_edits(reader: &mut Reader) -> io::Result<Vec<(IVec3, Option<BlockKind>)>> {
+  let len = reader.u32()? as usize;
+  // Each edit takes 14 bytes, so a length the frame cannot hold is rejected before allocating.
+  if len > reader.0.len() / 14 {
+    return Err(io::ErrorKind::UnexpectedEof.into());
+  }
+  (0..len).map(|_| Ok((reader.ivec3()?, voxel_from_u16(FORMAT, reader.u16()?)?))).collect()
+}
+
+/// A non-blocking TCP connection exchanging framed messages.
+pub struct Connection {
+  stream: TcpStream,
+  incoming: Vec<u8>,
+  outgoing: Vec<u8>,
+}
+
+impl Connection {
+  pub fn new(stream: TcpStream) -> io::Result<Connection> {
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    Ok(Connection { stream, incoming: Vec::new(), outgoing: Vec::new() })
+  }
+
+  /// Queues `message` until the next `flush`.
+  pub fn send(&mut self, message: &impl Message) {
+    let start = self.outgoing.len();
+    self.outgoing.extend([0; 4]);
+    message.write(&mut self.outgoing);
+    let len = (self.outgoing.len() - start - 4) as u32;
+    self.outgoing[start..start + 4].copy_from_slice(&len.to_le_bytes());
+  }
+
+  /// Writes as much of the queued messages as the socket accepts without blocking. Fails when the
+  /// peer stops reading and the rest no longer fits the queue.
+  pub fn flush(&mut self) -> io::Result<()> {
+    while !self.outgoing.is_empty() {
+      match self.stream.write(&self.outgoing) {
+        Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+        Ok(written) => {
+          self.outgoing.drain(..written);
+        }
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+        Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+        Err(error) => return Err(error),
+      }
+    }
+    if self.outgoing.len() > MAX_QUEUED_LEN {
+      return Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("peer is {} bytes behind", self.outgoing.len()),
+      ));
+    }
+    Ok(())
+  }
+
+  /// The messages received in full since the last call. Fails once the peer disconnects.
+  pub fn receive<M: Message>(&mut self) -> io::Result<Vec<M>> {
+    let mut buf = [0; 1 << 16];
+    loop {
+      match self.stream.read(&mut buf) {
+        Ok(0) => return Err(io::ErrorKind::ConnectionAborted.into()),
+        Ok(read) => self.incoming.extend(&buf[..read]),
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+        Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+        Err(error) => return Err(error),
+      }
+    }
+
+    let mut messages = Vec::new();
+    let mut frames = Reader(&self.incoming);
+    while frames.0.len() >= 4 {
+      let len = u32::from_le_bytes(frames.0[..4].try_into().unwrap()) as usize;
+      if len > MAX_FRAME_LEN {
+        return Err(invalid_data(FORMAT, &format!("frame of {len} bytes is too large")));
+      }
+      if frames.0.len() < 4 + len {
+        break;
+      }
+      frames.bytes(4)?;
+      let mut reader = Reader(frames.bytes(len)?);
+      messages.push(M::read(&mut reader)?);
+    }
+    let consumed = self.incoming.len() - frames.0.len();
+    self.incoming.drain(..consumed);
+    Ok(messages)
+  }
+}
@@ -0,0 +1,242 @@
+use std::io;
+use std::net::{
+  SocketAddr,
+  TcpListener,
+  ToSocketAddrs,
+};
+
+use bevy::prelude::*;
+use bevy::utils::{
+  HashMap,
+  HashSet,
+};
+
+use crate::chunks::{
+  encode_chunk,
+  BlockChanged,
+  BlockKind,
+  Chunk,
+  ChunkCompression,
+  ChunkLoadingPoint,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use crate::net::protocol::{
+  ClientMessage,
+  Connection,
+  ServerMessage,
+};
+
+/// Chunks sent to each client per frame, so that a client far from the others does not stall the
+/// server while its area is sent.
+const MAX_CHUNKS_PER_FRAME: usize = 64;
+
+/// How far a client may ask the server to load chunks around it, in chunk widths.
+const MAX_RADIUS_CHUNKS: f32 = 16.0;
+
+/// Accepts clients on a TCP listener, loads chunks around each of them, and streams the chunks
+/// and every block change in them. Requires `ChunksPlugin`, which can run without meshing.
+pub struct ServerPlugin {
+  listener: TcpListener,
+}
+
+impl ServerPlugin {
+  pub fn bind(addr: impl ToSocketAddrs) -> io::Result<ServerPlugin> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    Ok(ServerPlugin { listener })
+  }
+
+  /// The address the server listens on, useful after binding to port 0.
+  pub fn local_addr(&self) -> io::Result<SocketAddr> { self.listener.local_addr() }
+}
+
+impl Plugin for ServerPlugin {
+  fn build(&self, app: &mut App) {
+    let listener = self.listener.try_clone().expect("Failed to clone the server listener");
+    app
+      .insert_resource(Server { listener, clients: HashMap::new() })
+      .add_system(accept_clients.in_base_set(CoreSet::First))
+      .add_system(receive_client_messages.in_base_set(CoreSet::First).after(accept_clients))
+      .add_system(stream_chunks.in_base_set(CoreSet::Last));
+  }
+}
+
+struct ConnectedClient {
+  connection: Connection,
+  /// The `ChunkLoadingPoint` entity following the client, once it sent where it is.
+  loading_point: Option<Entity>,
+  /// The chunks the client has been sent, which also get its block changes.
+  sent_chunks: HashSet<IVec3>,
+  /// The edits the client sent this frame, which it need not be sent back.
+  edits: HashMap<IVec3, Option<BlockKind>>,
+}
+
+#[derive(Resource)]
+pub struct Server {
+  listener: TcpListener,
+  clients: HashMap<SocketAddr, ConnectedClient>,
+}
+
+impl Server {
+  pub fn client_count(&self) -> usize { self.clients.len() }
+}
+
+fn accept_clients(mut server: ResMut<Server>, chunk_world_config: Res<ChunkWorldConfig>) {
+  loop {
+    let (stream, addr) = match server.listener.accept() {
+      Ok(accepted) => accepted,
+      Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+      Err(error) => {
+        warn!("Failed to accept a client: {error}");
+        break;
+      }
+    };
+    let mut connection = match Connection::new(stream) {
+      Ok(connection) => connection,
+      Err(error) => {
+        warn!("Failed to set up the connection to {addr}: {error}");
+        continue;
+      }
+    };
+    connection.send(&ServerMessage::Hello { chunk_size: chunk_world_config.chunk_size });
+    info!("{addr} connected");
+    server.clients.insert(
+      addr,
+      ConnectedClient {
+        connection,
+        loading_point: None,
+        sent_chunks: HashSet::new(),
+        edits: HashMap::new(),
+      },
+    );
+  }
+}
+
+fn receive_client_messages(
+  mut commands: Commands,
+  mut server: ResMut<Server>,
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  mut clps: Query<(&mut Transform, &mut ChunkLoadingPoint)>,
+) {
+  let max_radius = chunk_world_config.chunk_size.max_element() as f32
+    * chunk_world_config.block_size.max_element()
+    * MAX_RADIUS_CHUNKS;
+  let mut disconnected = Vec::new();
+  for (addr, client) in &mut server.clients {
+    let messages = match client.connection.receive::<ClientMessage>() {
+      Ok(messages) => messages,
+      Err(error) => {
+        info!("{addr} disconnected: {error}");
+        disconnected.push(*addr);
+        continue;
+      }
+    };
+    for message in messages {
+      match message {
+        ClientMessage::LoadingPoint { translation, radius } => {
+          let radius = radius.clamp(0.0, max_radius);
+          // The server has no meshes, so levels of detail are left to the clients.
+          let lod_radii = [f32::INFINITY; 3];
+          match client.loading_point.and_then(|entity| clps.get_mut(entity).ok()) {
+            Some((mut clp_transform, mut clp)) => {
+              clp_transform.translation = translation;
+              clp.radius = radius;
+            }
+            None => {
+              let entity = commands
+                .spawn((
+                  Transform::from_translation(translation),
+                  ChunkLoadingPoint { radius, lod_radii },
+                ))
+                .id();
+              client.loading_point = Some(entity);
+            }
+          }
+        }
+        ClientMessage::SetBlocks(edits) => {
+          client.edits.extend(edits.iter().copied());
+          chunk_world.set_blocks(edits);
+        }
+      }
+    }
+  }
+
+  for addr in disconnected {
+    disconnect(&mut commands, &mut server, addr);
+  }
+}
+
+/// Sends every client the block changes in the chunks it has, except those it made itself, then
+/// the chunks around its loading point it does not have yet, nearest first.
+fn stream_chunks(
+  mut commands: Commands,
+  mut server: ResMut<Server>,
+  chunk_world: Res<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  clps: Query<(&Transform, &ChunkLoadingPoint)>,
+  chunks: Query<&Chunk>,
+  mut block_changed_events: EventReader<BlockChanged>,
+) {
+  let block_changes = block_changed_events.iter().copied().collect::<Vec<BlockChanged>>();
+  let last_voxels = block_changes
+    .iter()
+    .map(|block_changed| (block_changed.pos, block_changed.after))
+    .collect::<HashMap<IVec3, Option<BlockKind>>>();
+  let mut disconnected = Vec::new();
+  for (addr, client) in &mut server.clients {
+    // Blocks the client edited already hold its voxel, unless another client overwrote it since.
+    let edits = std::mem::take(&mut client.edits);
+    let blocks = block_changes
+      .iter()
+      .filter(|block_changed| client.sent_chunks.contains(&block_changed.key))
+      .filter(|block_changed| edits.get(&block_changed.pos) != last_voxels.get(&block_changed.pos))
+      .map(|block_changed| (block_changed.pos, block_changed.after))
+      .collect::<Vec<_>>();
+    if !blocks.is_empty() {
+      client.connection.send(&ServerMessage::Blocks(blocks));
+    }
+
+    if let Some(entity) = client.loading_point {
+      if let Ok((clp_transform, clp)) = clps.get(entity) {
+        let (min, max) = chunk_world_config.chunks_within(clp_transform.translation, clp.radius);
+        let within = |key: &IVec3| key.cmpge(min).all() && key.cmplt(max).all();
+        client.sent_chunks.retain(within);
+
+        let center = chunk_world_config.world_to_chunk(clp_transform.translation);
+        let mut missing_keys = chunk_world
+          .chunks
+          .keys()
+          .filter(|key| within(key) && !client.sent_chunks.contains(*key))
+          .copied()
+          .collect::<Vec<IVec3>>();
+        missing_keys.sort_by_key(|key| (*key - center).abs().max_element());
+        for key in missing_keys.into_iter().take(MAX_CHUNKS_PER_FRAME) {
+          let Ok(chunk) = chunks.get(chunk_world.chunks[&key]) else { continue };
+          client.connection.send(&ServerMessage::Chunk {
+            key,
+            data: encode_chunk(chunk, ChunkCompression::STREAMING),
+          });
+          client.sent_chunks.insert(key);
+        }
+      }
+    }
+
+    if let Err(error) = client.connection.flush() {
+      info!("{addr} disconnected: {error}");
+      disconnected.push(*addr);
+    }
+  }
+
+  for addr in disconnected {
+    disconnect(&mut commands, &mut server, addr);
+  }
+}
+
+fn disconnect(commands: &mut Commands, server: &mut Server, addr: SocketAddr) {
+  let client = server.clients.remove(&addr).unwrap();
+  if let Some(entity) = client.loading_point {
+    commands.entity(entity).despawn();
+  }
+}
@@ -0,0 +1,359 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use bevy::utils::{
+  HashMap,
+  HashSet,
+};
+
+use crate::chunks::{
+  BlockChanged,
+  BlockKind,
+  Chunk,
+  ChunkGenerated,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+
+const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+/// The cost of walking to the next block. Climbs and drops add to it.
+const WALK_COST: u32 = 2;
+/// The extra cost of a climb higher than `PathAgent::step_up`.
+const JUMP_COST: u32 = 4;
+/// The distance between the points of a smoothed segment checked for ground, in blocks.
+const SMOOTHING_STEP: f32 = 0.25;
+/// How far an agent reaches from the center of its column, in blocks.
+const AGENT_RADIUS: f32 = 0.3;
+
+/// The size and movement rules of a ground agent, one block wide. Positions in paths are the
+/// block the agent's feet are in, which must be empty and have a solid block below.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathAgent {
+  /// The empty blocks the agent needs above its ground.
+  pub height: u32,
+  /// The highest climb the agent walks up.
+  pub step_up: u32,
+  /// The highest climb the agent jumps up, costing more than walking.
+  pub jump_height: u32,
+  /// The deepest drop the agent takes.
+  pub max_drop: u32,
+}
+
+impl PathAgent {
+  /// Two blocks tall, stepping up one block and dropping up to three.
+  pub fn new() -> PathAgent {
+    PathAgent { height: 2, step_up: 1, jump_height: 1, max_drop: 3 }
+  }
+
+  pub fn with_height(mut self, height: u32) -> PathAgent {
+    self.height = height;
+    self
+  }
+
+  pub fn with_step_up(mut self, step_up: u32) -> PathAgent {
+    self.step_up = step_up;
+    self.jump_height = self.jump_height.max(step_up);
+    self
+  }
+
+  pub fn with_jump_height(mut self, jump_height: u32) -> PathAgent {
+    self.jump_height = jump_height.max(self.step_up);
+    self
+  }
+
+  pub fn with_max_drop(mut self, max_drop: u32) -> PathAgent {
+    self.max_drop = max_drop;
+    self
+  }
+}
+
+impl Default for PathAgent {
+  fn default() -> PathAgent { PathAgent::new() }
+}
+
+/// Where a `PathAgent` wants to go, in block coordinates. The agent's `Transform` is in its feet
+/// block.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathGoal(pub IVec3);
+
+/// The path found to an agent's `PathGoal`, removed to be searched again when a block it relies on
+/// changes.
+#[derive(Component, Debug, Clone)]
+pub struct Path {
+  /// The smoothed path from the agent's feet block to the goal, including both. Empty when no path
+  /// was found.
+  pub waypoints: Vec<IVec3>,
+  pub goal: IVec3,
+  /// The blocks whose changes invalidate the path.
+  cells: HashSet<IVec3>,
+}
+
+impl Path {
+  pub fn found(&self) -> bool { !self.waypoints.is_empty() }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct PathfindingSettings {
+  /// Searches give up after expanding this many blocks.
+  pub max_nodes: usize,
+  /// Whether paths are shortened to straight segments where the agent can walk them.
+  pub smoothing: bool,
+}
+
+/// Finds paths for entities with a `PathAgent`, a `PathGoal` and a `Transform`, searching again
+/// when the goal or the blocks along the path change. Requires `ChunksPlugin`.
+pub struct PathfindingPlugin {
+  settings: PathfindingSettings,
+}
+
+impl PathfindingPlugin {
+  /// Expands up to 10000 blocks per search, with smoothing.
+  pub fn new() -> PathfindingPlugin {
+    PathfindingPlugin { settings: PathfindingSettings { max_nodes: 10_000, smoothing: true } }
+  }
+
+  pub fn with_max_nodes(mut self, max_nodes: usize) -> PathfindingPlugin {
+    self.settings.max_nodes = max_nodes;
+    self
+  }
+
+  pub fn with_smoothing(mut self, smoothing: bool) -> PathfindingPlugin {
+    self.settings.smoothing = smoothing;
+    self
+  }
+}
+
+impl Default for PathfindingPlugin {
+  fn default() -> PathfindingPlugin { PathfindingPlugin::new() }
+}
+
+impl Plugin for PathfindingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(self.settings.clone())
+      .add_system(find_paths)
+      // Drops the paths this frame's edits and loaded chunks affect, see `ChunksPlugin`.
+      .add_system(invalidate_paths.in_base_set(CoreSet::Last));
+  }
+}
+
+/// Reads voxels for the searches, with `None` for unloaded chunks, which agents never enter.
+struct Terrain<F> {
+  read: F,
+  agent: PathAgent,
+}
+
+impl<F: Fn(IVec3) -> Option<Option<BlockKind>>> Terrain<F> {
+  fn solid(&self, pos: IVec3) -> bool { (self.read)(pos).flatten().is_some() }
+
+  /// Whether the `height` blocks from `pos` up are loaded and empty.
+  fn clear(&self, pos: IVec3, height: u32) -> bool {
+    (0..height as i32).all(|y| (self.read)(pos + IVec3::Y * y) == Some(None))
+  }
+
+  fn standable(&self, pos: IVec3) -> bool {
+    self.solid(pos - IVec3::Y) && self.clear(pos, self.agent.height)
+  }
+
+  /// Where a step from `from` in `direction` ends up, with its cost.
+  fn step(&self, from: IVec3, direction: IVec3) -> Option<(IVec3, u32)> {
+    let to = from + direction;
+    if self.clear(to, self.agent.height) {
+      // Walk, or drop to the first ground below.
+      for drop in 0..=self.agent.max_drop {
+        let landing = to - IVec3::Y * drop as i32;
+        if self.solid(landing - IVec3::Y) {
+          return Some((landing, WALK_COST + drop));
+        }
+        // Unloaded chunks end the fall.
+        (self.read)(landing - IVec3::Y)?;
+      }
+      return None;
+    }
+    // Climb onto the first ledge with room, if there is room to climb from `from`.
+    for climb in 1..=self.agent.jump_height {
+      if !self.clear(from + IVec3::Y * self.agent.height as i32, climb) {
+        return None;
+      }
+      let ledge = to + IVec3::Y * climb as i32;
+      if self.standable(ledge) {
+        let jump = if climb > self.agent.step_up { JUMP_COST } else { 0 };
+        return Some((ledge, WALK_COST + climb + jump));
+      }
+    }
+    None
+  }
+
+  /// Whether the agent can walk straight from `from` to `to` on flat ground.
+  fn walkable(&self, from: IVec3, to: IVec3) -> bool {
+    from.y == to.y && footprint(from, to).into_iter().all(|pos| self.standable(pos))
+  }
+}
+
+/// The columns a walk in a straight line from `from` to `to` passes, with the agent's width.
+fn footprint(from: IVec3, to: IVec3) -> HashSet<IVec3> {
+  let (from, to) = (from.as_vec3(), to.as_vec3());
+  let steps = (from.distance(to) / SMOOTHING_STEP).ceil().max(1.0) as usize;
+  let mut columns = HashSet::new();
+  for i in 0..=steps {
+    let point = from.lerp(to, i as f32 / steps as f32);
+    for (x, z) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+      let corner = point + Vec3::new(x, 0.0, z) * AGENT_RADIUS;
+      columns.insert(corner.round().as_ivec3());
+    }
+  }
+  columns
+}
+
+/// Searches for the cheapest path from `start` to `goal` with A*, reading voxels with `read`,
+/// which returns `None` for unloaded chunks. Both ends are feet blocks, and the path includes
+/// them. Agents walk between neighboring columns, climbing up to `PathAgent::jump_height` and
+/// dropping up to `PathAgent::max_drop` blocks. Gives up after expanding `max_nodes` blocks.
+pub fn find_path(
+  read: impl Fn(IVec3) -> Option<Option<BlockKind>>,
+  agent: &PathAgent,
+  start: IVec3,
+  goal: IVec3,
+  max_nodes: usize,
+) -> Option<Vec<IVec3>> {
+  let terrain = Terrain { read, agent: *agent };
+  if !terrain.standable(start) || !terrain.standable(goal) {
+    return None;
+  }
+  // Every step walks one column, so the horizontal distance never overestimates.
+  let heuristic = |pos: IVec3| {
+    let offset = (goal - pos).abs();
+    (offset.x + offset.z) as u32 * WALK_COST
+  };
+
+  let mut open = BinaryHeap::new();
+  let mut costs = HashMap::new();
+  let mut came_from = HashMap::new();
+  open.push(Reverse((heuristic(start), 0, start.to_array())));
+  costs.insert(start, 0);
+  let mut expanded = 0;
+  while let Some(Reverse((_, cost, pos))) = open.pop() {
+    let pos = IVec3::from_array(pos);
+    if pos == goal {
+      let mut path = vec![goal];
+      while let Some(previous) = came_from.get(path.last().unwrap()) {
+        path.push(*previous);
+      }
+      path.reverse();
+      return Some(path);
+    }
+    // Skips entries superseded by a cheaper one.
+    if costs.get(&pos).is_some_and(|best| cost > *best) {
+      continue;
+    }
+    expanded += 1;
+    if expanded > max_nodes {
+      return None;
+    }
+    for direction in DIRECTIONS {
+      let Some((next, step_cost)) = terrain.step(pos, direction) else { continue };
+      let next_cost = cost + step_cost;
+      if costs.get(&next).is_none_or(|best| next_cost < *best) {
+        costs.insert(next, next_cost);
+        came_from.insert(next, pos);
+        open.push(Reverse((next_cost + heuristic(next), next_cost, next.to_array())));
+      }
+    }
+  }
+  None
+}
+
+/// Shortens a path from `find_path` to the waypoints where it turns, climbs or drops, cutting
+/// corners only where the agent keeps flat ground under it all the way.
+pub fn smooth_path(
+  read: impl Fn(IVec3) -> Option<Option<BlockKind>>,
+  agent: &PathAgent,
+  path: &[IVec3],
+) -> Vec<IVec3> {
+  let terrain = Terrain { read, agent: *agent };
+  let Some(first) = path.first() else { return Vec::new() };
+  let mut waypoints = vec![*first];
+  let mut anchor = 0;
+  while anchor < path.len() - 1 {
+    // The furthest point reachable in a straight line, or at least the next one.
+    let next = (anchor + 2..path.len())
+      .rev()
+      .find(|i| terrain.walkable(path[anchor], path[*i]))
+      .unwrap_or(anchor + 1);
+    waypoints.push(path[next]);
+    anchor = next;
+  }
+  waypoints
+}
+
+/// The blocks an agent following `waypoints` along the unsmoothed `path` walks through or on.
+fn path_cells(agent: &PathAgent, path: &[IVec3], waypoints: &[IVec3]) -> HashSet<IVec3> {
+  let top = agent.height as i32 - 1;
+  let mut cells = HashSet::new();
+  let mut add_columns = |columns: HashSet<IVec3>, bottom: i32, top: i32| {
+    for column in columns {
+      cells.extend((bottom..=top).map(|y| ivec3(column.x, y, column.z)));
+    }
+  };
+  for pair in path.windows(2) {
+    let (bottom, high) = (pair[0].y.min(pair[1].y), pair[0].y.max(pair[1].y));
+    // Climbs need room above the lower block too.
+    add_columns(pair.iter().copied().collect(), bottom - 1, high + top);
+  }
+  for pair in waypoints.windows(2) {
+    add_columns(footprint(pair[0], pair[1]), pair[0].y - 1, pair[0].y + top);
+  }
+  if let [pos] = path {
+    add_columns([*pos].into_iter().collect(), pos.y - 1, pos.y + top);
+  }
+  cells
+}
+
+fn find_paths(
+  mut commands: Commands,
+  chunk_world: Res<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunks: Query<&Chunk>,
+  settings: Res<PathfindingSettings>,
+  agents: Query<(Entity, &Transform, &PathAgent, &PathGoal, Option<&Path>)>,
+) {
+  let read = |pos| chunk_world.get_voxel(&chunk_world_config, &chunks, pos);
+  for (entity, transform, agent, goal, path) in &agents {
+    if path.is_some_and(|path| path.goal == goal.0) {
+      continue;
+    }
+    let start = chunk_world_config.world_to_block(transform.translation);
+    let path = match find_path(read, agent, start, goal.0, settings.max_nodes) {
+      Some(path) => {
+        let waypoints =
+          if settings.smoothing { smooth_path(read, agent, &path) } else { path.clone() };
+        let cells = path_cells(agent, &path, &waypoints);
+        Path { waypoints, goal: goal.0, cells }
+      }
+      None => Path { waypoints: Vec::new(), goal: goal.0, cells: HashSet::new() },
+    };
+    commands.entity(entity).insert(path);
+  }
+}
+
+/// Removes the paths that changed blocks lie on, and the failed searches after any change or newly
+/// loaded chunk, since either may have opened a way.
+fn invalidate_paths(
+  mut commands: Commands,
+  mut block_changed_events: EventReader<BlockChanged>,
+  mut chunk_generated_events: EventReader<ChunkGenerated>,
+  paths: Query<(Entity, &Path)>,
+) {
+  let changed = block_changed_events.iter().map(|event| event.pos).collect::<Vec<IVec3>>();
+  let loaded = chunk_generated_events.iter().count() > 0;
+  if changed.is_empty() && !loaded {
+    return;
+  }
+  for (entity, path) in &paths {
+    if !path.found() || changed.iter().any(|pos| path.cells.contains(pos)) {
+      commands.entity(entity).remove::<Path>();
+    }
+  }
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{
+  self,
+  BufReader,
+  BufWriter,
+  Read,
+  Write,
+};
+use std::path::Path;
+
+use bevy::math::{
+  ivec3,
+  uvec3,
+};
+use bevy::prelude::{
+  IVec3,
+  Query,
+  UVec3,
+};
+
+use crate::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use crate::codec::{
+  invalid_data,
+  read_array,
+  read_runs,
+  voxel_from_u16,
+  voxel_to_u16,
+  write_runs,
+};
+
+const FORMAT: &str = "schematic";
+const SCHEMATIC_MAGIC: &[u8; 4] = b"VXSC";
+const SCHEMATIC_VERSION: u8 = 1;
+/// Larger schematics are rejected before reading their blocks.
+const MAX_SCHEMATIC_BLOCKS: usize = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+  X,
+  Y,
+  Z,
+}
+
+/// A box of blocks copied out of a `ChunkWorld`, laid out like `Chunk::blocks`. `None` is air.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+  pub size: UVec3,
+  pub blocks: Vec<Option<BlockKind>>,
+}
+
+impl Schematic {
+  /// Copies the blocks in `min..max`. Blocks in chunks that are not loaded are copied as air.
+  pub fn copy(
+    chunk_world: &ChunkWorld,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    min: IVec3,
+    max: IVec3,
+  ) -> Schematic {
+    let size = (max - min).max(IVec3::ZERO).as_uvec3();
+    let mut blocks = Vec::with_capacity(size.x as usize * size.y as usize * size.z as usize);
+    for x in min.x..max.x {
+      for y in min.y..max.y {
+        for z in min.z..max.z {
+          blocks.push(chunk_world.get_voxel(chunk_world_config, chunks, ivec3(x, y, z)).flatten());
+        }
+      }
+    }
+    Schematic { size, blocks }
+  }
+
+  /// Queues the edits placing this schematic's minimum corner at `origin`, air included, as one
+  /// batch even when it spans several chunks.
+  pub fn paste(&self, chunk_world: &mut ChunkWorld, origin: IVec3) {
+    chunk_world.set_blocks(
+      self.positions().map(|pos| (origin + pos.as_ivec3(), self.blocks[self.index(pos)])),
+    );
+  }
+
+  /// Rotates counterclockwise around the y axis, seen from above, by `quarter_turns` times 90°.
+  pub fn rotate_y(&self, quarter_turns: u32) -> Schematic {
+    (0..quarter_turns % 4).fold(self.clone(), |schematic, _| {
+      let size = uvec3(schematic.size.z, schematic.size.y, schematic.size.x);
+      schematic.remap(size, |pos| uvec3(schematic.size.x - 1 - pos.z, pos.y, pos.x))
+    })
+  }
+
+  pub fn mirror(&self, axis: Axis) -> Schematic {
+    self.remap(self.size, |pos| match axis {
+      Axis::X => uvec3(self.size.x - 1 - pos.x, pos.y, pos.z),
+      Axis::Y => uvec3(pos.x, self.size.y - 1 - pos.y, pos.z),
+      Axis::Z => uvec3(pos.x, pos.y, self.size.z - 1 - pos.z),
+    })
+  }
+
+  pub fn load(path: &Path) -> io::Result<Schematic> {
+    Schematic::read(&mut BufReader::new(File::open(path)?))
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.write(&mut writer)?;
+    writer.flush()
+  }
+
+  /// Reads a schematic written by [`Schematic::write`].
+  pub fn read(reader: &mut impl Read) -> io::Result<Schematic> {
+    let mut header = [0; 17];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != SCHEMATIC_MAGIC {
+      return Err(invalid_data(FORMAT, "not a schematic file"));
+    }
+    if header[4] != SCHEMATIC_VERSION {
+      return Err(invalid_data(FORMAT, &format!("unsupported version {}", header[4])));
+    }
+    let [x, y, z] = [5, 9, 13].map(|i| u32::from_le_bytes(header[i..i + 4].try_into().unwrap()));
+    let size = uvec3(x, y, z);
+    let len = [x, y, z].iter().try_fold(1usize, |len, axis| len.checked_mul(*axis as usize));
+    let len = match len {
+      Some(len) if len <= MAX_SCHEMATIC_BLOCKS => len,
+      _ => return Err(invalid_data(FORMAT, &format!("size {size} is too large"))),
+    };
+
+    let blocks = read_runs(FORMAT, reader, len, |reader| {
+      voxel_from_u16(FORMAT, u16::from_le_bytes(read_array(reader)?))
+    })?;
+
+    Ok(Schematic { size, blocks })
+  }
+
+  /// Writes the size followed by run-length encoded blocks, each run being a LEB128 length and a
+  /// `u16` value where 0 is air and `k + 1` is `BlockKind(k)`.
+  pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(SCHEMATIC_MAGIC)?;
+    writer.write_all(&[SCHEMATIC_VERSION])?;
+    for axis in self.size.to_array() {
+      writer.write_all(&axis.to_le_bytes())?;
+    }
+
+    let mut runs = Vec::new();
+    write_runs(&mut runs, self.blocks.iter().copied(), |buf, voxel| {
+      buf.extend(voxel_to_u16(voxel).to_le_bytes())
+    });
+    writer.write_all(&runs)
+  }
+
+  fn index(&self, pos: UVec3) -> usize {
+    let [x, y, z] = pos.to_array().map(|axis| axis as usize);
+    let [_, sy, sz] = self.size.to_array().map(|axis| axis as usize);
+    x * sy * sz + y * sz + z
+  }
+
+  fn positions(&self) -> impl Iterator<Item = UVec3> {
+    let size = self.size;
+    (0..size.x)
+      .flat_map(move |x| (0..size.y).flat_map(move |y| (0..size.z).map(move |z| uvec3(x, y, z))))
+  }
+
+  /// Builds a schematic of `size` where the block at `pos` comes from `self` at `source(pos)`.
+  fn remap(&self, size: UVec3, source: impl Fn(UVec3) -> UVec3) -> Schematic {
+    let mut schematic = Schematic { size, blocks: Vec::with_capacity(self.blocks.len()) };
+    for pos in schematic.positions().collect::<Vec<UVec3>>() {
+      schematic.blocks.push(self.blocks[self.index(source(pos))]);
+    }
+    schematic
+  }
+}
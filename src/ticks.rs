@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::Rng;
+
+use crate::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+  PendingVoxels,
+};
+use crate::net::Client;
+
+pub type TickCallback = Arc<dyn Fn(&mut BlockTick) + Send + Sync>;
+
+/// What a kind of block does when it is ticked.
+#[derive(Clone, Default)]
+pub struct BlockBehavior {
+  /// Called when a random tick lands on the block, such as for grass spreading.
+  pub random_tick: Option<TickCallback>,
+  /// Called when a tick scheduled with `BlockTick::schedule` or `ScheduledTicks::schedule` is due.
+  pub scheduled_tick: Option<TickCallback>,
+}
+
+impl BlockBehavior {
+  pub fn on_random_tick(
+    mut self,
+    callback: impl Fn(&mut BlockTick) + Send + Sync + 'static,
+  ) -> Self {
+    self.random_tick = Some(Arc::new(callback));
+    self
+  }
+
+  pub fn on_scheduled_tick(
+    mut self,
+    callback: impl Fn(&mut BlockTick) + Send + Sync + 'static,
+  ) -> Self {
+    self.scheduled_tick = Some(Arc::new(callback));
+    self
+  }
+}
+
+/// The behavior of each kind of block. Kinds without one are never ticked.
+#[derive(Resource, Clone, Default)]
+pub struct BlockBehaviors(pub HashMap<BlockKind, BlockBehavior>);
+
+#[derive(Resource, Debug, Clone)]
+pub struct TickSettings {
+  /// Blocks picked at random in every loaded chunk each tick.
+  pub random_ticks_per_chunk: u32,
+}
+
+/// Ticks scheduled for later, by the tick they are due on.
+#[derive(Resource, Default)]
+pub struct ScheduledTicks {
+  tick: u64,
+  due: BTreeMap<u64, Vec<IVec3>>,
+}
+
+impl ScheduledTicks {
+  /// The number of ticks run so far.
+  pub fn tick(&self) -> u64 { self.tick }
+
+  /// Ticks the block at `pos` on the `delay`th tick from now, at least the next one. The tick is
+  /// dropped if the block's kind has no `BlockBehavior::scheduled_tick` by then.
+  pub fn schedule(&mut self, pos: IVec3, delay: u64) {
+    self.due.entry(self.tick + delay.max(1) - 1).or_default().push(pos);
+  }
+
+  /// The number of ticks waiting to run.
+  pub fn len(&self) -> usize { self.due.values().map(Vec::len).sum() }
+
+  pub fn is_empty(&self) -> bool { self.due.is_empty() }
+}
+
+/// Changes made by ticks this frame, which `apply_block_edits` has not written to the chunks yet.
+#[derive(Resource, Default)]
+struct PendingTickEdits(HashMap<IVec3, Option<BlockKind>>);
+
+/// The block being ticked, and access to the world around it. Reads see the changes made by
+/// earlier ticks of the same frame, and changes are applied to the chunks at the end of the frame
+/// like `ChunkWorld::sync_blocks`, so they are not undoable.
+pub struct BlockTick<'a, 'w> {
+  /// The block's position, in block coordinates.
+  pub pos: IVec3,
+  pub kind: BlockKind,
+  /// The number of ticks run before this one.
+  pub tick: u64,
+  blocks: &'a mut PendingVoxels<'w>,
+  edits: &'a mut Vec<(IVec3, Option<BlockKind>)>,
+  scheduled: &'a mut Vec<(IVec3, u64)>,
+}
+
+impl BlockTick<'_, '_> {
+  /// The voxel at `pos`, or `None` if its chunk is not loaded.
+  pub fn get_block(&self, pos: IVec3) -> Option<Option<BlockKind>> { self.blocks.get(pos) }
+
+  pub fn set_block(&mut self, pos: IVec3, voxel: Option<BlockKind>) {
+    self.blocks.set(pos, voxel);
+    self.edits.push((pos, voxel));
+  }
+
+  /// Like `ScheduledTicks::schedule`.
+  pub fn schedule(&mut self, pos: IVec3, delay: u64) { self.scheduled.push((pos, delay)); }
+}
+
+/// Runs random and scheduled block ticks on `CoreSchedule::FixedUpdate`, calling the
+/// `BlockBehaviors` of the ticked blocks. Requires `ChunksPlugin`. The tick rate sets the
+/// `FixedTime` period, which every fixed update system shares. Ticks do not run while a `Client`
+/// is connected, since their changes are not sent and the server runs its own.
+pub struct TicksPlugin {
+  tick_rate: f32,
+  settings: TickSettings,
+  behaviors: BlockBehaviors,
+}
+
+impl TicksPlugin {
+  /// 20 ticks per second, with 3 random ticks per chunk.
+  pub fn new() -> TicksPlugin {
+    TicksPlugin {
+      tick_rate: 20.0,
+      settings: TickSettings { random_ticks_per_chunk: 3 },
+      behaviors: BlockBehaviors::default(),
+    }
+  }
+
+  /// Ticks per second.
+  pub fn with_tick_rate(mut self, tick_rate: f32) -> TicksPlugin {
+    self.tick_rate = tick_rate;
+    self
+  }
+
+  pub fn with_random_ticks_per_chunk(mut self, random_ticks_per_chunk: u32) -> TicksPlugin {
+    self.settings.random_ticks_per_chunk = random_ticks_per_chunk;
+    self
+  }
+
+  pub fn with_behavior(mut self, kind: BlockKind, behavior: BlockBehavior) -> TicksPlugin {
+    self.behaviors.0.insert(kind, behavior);
+    self
+  }
+}
+
+impl Default for TicksPlugin {
+  fn default() -> TicksPlugin { TicksPlugin::new() }
+}
+
+impl Plugin for TicksPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(FixedTime::new_from_secs(1.0 / self.tick_rate))
+      .insert_resource(self.settings.clone())
+      .insert_resource(self.behaviors.clone())
+      .init_resource::<ScheduledTicks>()
+      .init_resource::<PendingTickEdits>()
+      .add_system(
+        run_ticks.in_schedule(CoreSchedule::FixedUpdate).run_if(not(resource_exists::<Client>())),
+      )
+      // Once `apply_block_edits` has written the pending edits to the chunks, see `ChunksPlugin`.
+      .add_system(clear_pending_tick_edits.in_base_set(CoreSet::Last));
+  }
+}
+
+/// Runs the scheduled ticks that are due, then the random ticks.
+fn run_ticks(
+  mut chunk_world: ResMut<ChunkWorld>,
+  chunk_world_config: Res<ChunkWorldConfig>,
+  chunks: Query<&Chunk>,
+  behaviors: Res<BlockBehaviors>,
+  tick_settings: Res<TickSettings>,
+  mut scheduled_ticks: ResMut<ScheduledTicks>,
+  mut pending: ResMut<PendingTickEdits>,
+) {
+  let tick = scheduled_ticks.tick;
+  let mut edits = Vec::new();
+  let mut scheduled = Vec::new();
+  {
+    let read = |pos| chunk_world.get_voxel(&chunk_world_config, &chunks, pos);
+    let mut blocks = PendingVoxels::new(&read, std::mem::take(&mut pending.0));
+    let mut run = |pos, callback: fn(&BlockBehavior) -> Option<&TickCallback>| {
+      let Some(kind) = blocks.get(pos).flatten() else { return };
+      let Some(callback) = behaviors.0.get(&kind).and_then(callback) else { return };
+      callback(&mut BlockTick {
+        pos,
+        kind,
+        tick,
+        blocks: &mut blocks,
+        edits: &mut edits,
+        scheduled: &mut scheduled,
+      });
+    };
+
+    let due_keys = scheduled_ticks.due.range(..=tick).map(|(due, _)| *due).collect::<Vec<u64>>();
+    for due in due_keys {
+      for pos in scheduled_ticks.due.remove(&due).unwrap() {
+        run(pos, |behavior| behavior.scheduled_tick.as_ref());
+      }
+    }
+
+    if behaviors.0.values().any(|behavior| behavior.random_tick.is_some()) {
+      let mut rng = rand::thread_rng();
+      for (key, chunk_entity) in &chunk_world.chunks {
+        let Ok(chunk) = chunks.get(*chunk_entity) else { continue };
+        let first_block = chunk_world_config.chunk_to_block(*key);
+        for _ in 0..tick_settings.random_ticks_per_chunk {
+          let local = IVec3::new(
+            rng.gen_range(0..chunk.size.x as i32),
+            rng.gen_range(0..chunk.size.y as i32),
+            rng.gen_range(0..chunk.size.z as i32),
+          );
+          run(first_block + local, |behavior| behavior.random_tick.as_ref());
+        }
+      }
+    }
+    pending.0 = blocks.into_edits();
+  }
+
+  scheduled_ticks.tick += 1;
+  for (pos, delay) in scheduled {
+    scheduled_ticks.schedule(pos, delay);
+  }
+  if !edits.is_empty() {
+    chunk_world.sync_blocks(edits);
+  }
+}
+
+fn clear_pending_tick_edits(mut pending: ResMut<PendingTickEdits>) { pending.0.clear(); }
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{
+  self,
+  BufReader,
+  BufWriter,
+  Read,
+  Write,
+};
+use std::path::Path;
+
+use bevy::math::{
+  ivec3,
+  uvec3,
+};
+use bevy::prelude::{
+  IVec3,
+  Query,
+  UVec3,
+};
+
+use crate::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use crate::codec::invalid_data;
+
+const FORMAT: &str = ".vox file";
+const VOX_VERSION: u32 = 150;
+const MAX_MODEL_SIZE: u32 = 256;
+
+/// A MagicaVoxel model converted to the engine's y-up axes: MagicaVoxel's z-up `(x, y, z)` becomes
+/// `(x, z, size.z - 1 - y)`, which keeps the model from being mirrored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxModel {
+  pub size: UVec3,
+  pub voxels: Vec<(UVec3, BlockKind)>,
+  /// Where the model's minimum corner sits relative to the file's other models, in blocks.
+  pub position: IVec3,
+}
+
+impl VoxModel {
+  /// Copies the activated blocks in `min..max` out of the loaded chunks.
+  pub fn from_world(
+    chunk_world: &ChunkWorld,
+    chunk_world_config: &ChunkWorldConfig,
+    chunks: &Query<&Chunk>,
+    min: IVec3,
+    max: IVec3,
+  ) -> VoxModel {
+    let mut voxels = Vec::new();
+    for x in min.x..max.x {
+      for y in min.y..max.y {
+        for z in min.z..max.z {
+          let pos = ivec3(x, y, z);
+          if let Some(kind) = chunk_world.get_voxel(chunk_world_config, chunks, pos).flatten() {
+            voxels.push(((pos - min).as_uvec3(), kind));
+          }
+        }
+      }
+    }
+    VoxModel { size: (max - min).max(IVec3::ZERO).as_uvec3(), voxels, position: IVec3::ZERO }
+  }
+
+  /// Queues the edits placing this model at its `position` from `origin`, in block coordinates.
+  pub fn stamp(&self, chunk_world: &mut ChunkWorld, origin: IVec3) {
+    let origin = origin + self.position;
+    chunk_world
+      .set_blocks(self.voxels.iter().map(|(pos, kind)| (origin + pos.as_ivec3(), Some(*kind))));
+  }
+
+  /// The MagicaVoxel translation of the model's center, the inverse of `position_from_vox`.
+  fn vox_translation(&self) -> IVec3 {
+    let half = (self.size / 2).as_ivec3();
+    ivec3(
+      self.position.x + half.x,
+      half.z - self.size.z as i32 - self.position.z,
+      self.position.y + half.y,
+    )
+  }
+
+  /// The position of a model whose center MagicaVoxel translates by `translation`, rounding the
+  /// center of even sizes down as MagicaVoxel does.
+  fn position_from_vox(size: UVec3, translation: IVec3) -> IVec3 {
+    let half = (size / 2).as_ivec3();
+    ivec3(translation.x - half.x, translation.z - half.y, half.z - size.z as i32 - translation.y)
+  }
+}
+
+/// A node of a `.vox` scene graph.
+enum VoxNode {
+  Transform { translation: IVec3, child: i32 },
+  Group(Vec<i32>),
+  Shape(Vec<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxFile {
+  pub models: Vec<VoxModel>,
+  /// RGBA colors indexed by `BlockKind`.
+  pub palette: [[u8; 4]; 256],
+}
+
+impl VoxFile {
+  pub fn load(path: &Path) -> io::Result<VoxFile> {
+    VoxFile::read(&mut BufReader::new(File::open(path)?))
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.write(&mut writer)?;
+    writer.flush()
+  }
+
+  /// Reads the models and palette of a `.vox` file. Models are placed by the translations of
+  /// the scene graph, shifted so that the lowest corner of any model is at the origin; rotations,
+  /// materials and layers are skipped. Files without a palette get MagicaVoxel's default one.
+  pub fn read(reader: &mut impl Read) -> io::Result<VoxFile> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut bytes = bytes.as_slice();
+
+    if take(&mut bytes, 4)? != b"VOX " {
+      return Err(invalid_data(FORMAT, "not a MagicaVoxel file"));
+    }
+    take_u32(&mut bytes)?;
+
+    let (id, _, mut children) = take_chunk(&mut bytes)?;
+    if id != b"MAIN" {
+      return Err(invalid_data(FORMAT, "missing MAIN chunk"));
+    }
+
+    let mut vox_file = VoxFile { models: Vec::new(), palette: default_palette() };
+    let mut nodes = HashMap::new();
+    let mut size = None;
+    while !children.is_empty() {
+      let (id, mut content, _) = take_chunk(&mut children)?;
+      match id {
+        b"SIZE" => {
+          size =
+            Some(uvec3(take_u32(&mut content)?, take_u32(&mut content)?, take_u32(&mut content)?));
+        }
+        b"XYZI" => {
+          let vox_size =
+            size.take().ok_or_else(|| invalid_data(FORMAT, "XYZI chunk without SIZE"))?;
+          let count = take_u32(&mut content)? as usize;
+          let voxels = take(&mut content, count * 4)?
+            .chunks_exact(4)
+            .filter(|voxel| voxel[3] != 0)
+            .map(|voxel| {
+              let [x, y, z] = [voxel[0], voxel[1], voxel[2]].map(u32::from);
+              if uvec3(x, y, z).cmpge(vox_size).any() {
+                let message = format!("voxel at {x}, {y}, {z} is outside its model of {vox_size}");
+                return Err(invalid_data(FORMAT, &message));
+              }
+              Ok((uvec3(x, z, vox_size.y - 1 - y), BlockKind(voxel[3] - 1)))
+            })
+            .collect::<io::Result<Vec<(UVec3, BlockKind)>>>()?;
+          let size = uvec3(vox_size.x, vox_size.z, vox_size.y);
+          vox_file.models.push(VoxModel { size, voxels, position: IVec3::ZERO });
+        }
+        b"nTRN" => {
+          let id = take_i32(&mut content)?;
+          take_dict(&mut content)?;
+          let child = take_i32(&mut content)?;
+          // The reserved id and the layer.
+          take(&mut content, 8)?;
+          let frames = take_i32(&mut content)?;
+          let mut translation = IVec3::ZERO;
+          if frames > 0 {
+            if let Some(t) = take_dict(&mut content)?.get("_t") {
+              let t = t.split(' ').map(str::parse).collect::<Result<Vec<i32>, _>>();
+              match t.as_deref() {
+                Ok(&[x, y, z]) => translation = ivec3(x, y, z),
+                _ => return Err(invalid_data(FORMAT, "invalid nTRN translation")),
+              }
+            }
+          }
+          nodes.insert(id, VoxNode::Transform { translation, child });
+        }
+        b"nGRP" => {
+          let id = take_i32(&mut content)?;
+          take_dict(&mut content)?;
+          let count = take_u32(&mut content)?;
+          let children = (0..count).map(|_| take_i32(&mut content)).collect::<io::Result<_>>()?;
+          nodes.insert(id, VoxNode::Group(children));
+        }
+        b"nSHP" => {
+          let id = take_i32(&mut content)?;
+          take_dict(&mut content)?;
+          let count = take_u32(&mut content)?;
+          let models = (0..count)
+            .map(|_| {
+              let model = take_u32(&mut content)?;
+              take_dict(&mut content)?;
+              Ok(model)
+            })
+            .collect::<io::Result<_>>()?;
+          nodes.insert(id, VoxNode::Shape(models));
+        }
+        b"RGBA" => {
+          for (entry, color) in
+            vox_file.palette.iter_mut().zip(take(&mut content, 256 * 4)?.chunks_exact(4))
+          {
+            *entry = [color[0], color[1], color[2], color[3]];
+          }
+        }
+        _ => {}
+      }
+    }
+
+    if nodes.contains_key(&0) {
+      let mut placed = Vec::new();
+      place_models(&nodes, 0, IVec3::ZERO, 0, &vox_file.models, &mut placed)?;
+      vox_file.models = placed;
+    }
+    let min = vox_file.models.iter().map(|model| model.position).reduce(IVec3::min);
+    for model in &mut vox_file.models {
+      model.position -= min.unwrap_or_default();
+    }
+    Ok(vox_file)
+  }
+
+  pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+    let mut children = Vec::new();
+    if self.models.len() > 1 {
+      put_chunk(&mut children, b"PACK", &(self.models.len() as u32).to_le_bytes());
+    }
+    for model in &self.models {
+      if model.size.cmpgt(UVec3::splat(MAX_MODEL_SIZE)).any() {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("models are limited to {MAX_MODEL_SIZE} voxels per axis, got {}", model.size),
+        ));
+      }
+
+      let vox_size = uvec3(model.size.x, model.size.z, model.size.y);
+      put_chunk(
+        &mut children,
+        b"SIZE",
+        &[vox_size.x, vox_size.y, vox_size.z].map(u32::to_le_bytes).concat(),
+      );
+
+      let mut xyzi = (model.voxels.len() as u32).to_le_bytes().to_vec();
+      for (pos, kind) in &model.voxels {
+        if pos.cmpge(model.size).any() {
+          return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("voxel at {pos} is outside its model of size {}", model.size),
+          ));
+        }
+        // Kind 255 has no color index to map to, so it shares the last one.
+        xyzi.extend([
+          pos.x as u8,
+          (vox_size.y - 1 - pos.z) as u8,
+          pos.y as u8,
+          kind.0.min(254) + 1,
+        ]);
+      }
+      put_chunk(&mut children, b"XYZI", &xyzi);
+    }
+    if self.models.len() > 1 {
+      self.put_scene_graph(&mut children);
+    }
+    put_chunk(&mut children, b"RGBA", &self.palette.concat());
+
+    writer.write_all(b"VOX ")?;
+    writer.write_all(&VOX_VERSION.to_le_bytes())?;
+    writer.write_all(b"MAIN")?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(children.len() as u32).to_le_bytes())?;
+    writer.write_all(&children)
+  }
+
+  /// Writes a root transform and group holding a translated shape for each model.
+  fn put_scene_graph(&self, children: &mut Vec<u8>) {
+    let shapes = 0..self.models.len() as i32;
+    put_transform(children, 0, 1, IVec3::ZERO);
+    let mut group = [1, 0].map(i32::to_le_bytes).concat();
+    group.extend((self.models.len() as u32).to_le_bytes());
+    group.extend(shapes.clone().flat_map(|i| (2 + 2 * i).to_le_bytes()));
+    put_chunk(children, b"nGRP", &group);
+    for (i, model) in shapes.zip(&self.models) {
+      put_transform(children, 2 + 2 * i, 3 + 2 * i, model.vox_translation());
+      let shape = [3 + 2 * i, 0, 1, i, 0].map(i32::to_le_bytes).concat();
+      put_chunk(children, b"nSHP", &shape);
+    }
+  }
+}
+
+/// Collects a copy of each model the shapes under `id` reference, positioned by the translations
+/// of the transforms above them.
+fn place_models(
+  nodes: &HashMap<i32, VoxNode>,
+  id: i32,
+  translation: IVec3,
+  depth: usize,
+  models: &[VoxModel],
+  placed: &mut Vec<VoxModel>,
+) -> io::Result<()> {
+  // Deeper than there are nodes means the graph has a cycle.
+  if depth > nodes.len() {
+    return Err(invalid_data(FORMAT, "scene graph has a cycle"));
+  }
+  let node =
+    nodes.get(&id).ok_or_else(|| invalid_data(FORMAT, &format!("missing scene node {id}")))?;
+  match node {
+    VoxNode::Transform { translation: offset, child } => {
+      place_models(nodes, *child, translation + *offset, depth + 1, models, placed)?;
+    }
+    VoxNode::Group(children) => {
+      for child in children {
+        place_models(nodes, *child, translation, depth + 1, models, placed)?;
+      }
+    }
+    VoxNode::Shape(shape_models) => {
+      for model in shape_models {
+        let model = models
+          .get(*model as usize)
+          .ok_or_else(|| invalid_data(FORMAT, "shape of a missing model"))?;
+        let position = VoxModel::position_from_vox(model.size, translation);
+        placed.push(VoxModel { position, ..model.clone() });
+      }
+    }
+  }
+  Ok(())
+}
+
+/// MagicaVoxel's built-in palette, indexed by `BlockKind`: a 6x6x6 color cube without black,
+/// followed by red, green, blue and gray ramps.
+pub fn default_palette() -> [[u8; 4]; 256] {
+  let cube = (0..215).map(|i: u8| {
+    let [r, g, b] = [i / 36, i / 6 % 6, i % 6].map(|step| 0xff - step * 0x33);
+    [r, g, b, 0xff]
+  });
+  let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+  let ramps = ramp
+    .map(|v| [v, 0, 0, 0xff])
+    .into_iter()
+    .chain(ramp.map(|v| [0, v, 0, 0xff]))
+    .chain(ramp.map(|v| [0, 0, v, 0xff]))
+    .chain(ramp.map(|v| [v, v, v, 0xff]));
+
+  let mut palette = [[0; 4]; 256];
+  for (entry, color) in palette.iter_mut().zip(cube.chain(ramps)) {
+    *entry = color;
+  }
+  palette
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+  if bytes.len() < len {
+    return Err(invalid_data(FORMAT, "unexpected end of file"));
+  }
+  let (taken, rest) = bytes.split_at(len);
+  *bytes = rest;
+  Ok(taken)
+}
+
+fn take_u32(bytes: &mut &[u8]) -> io::Result<u32> {
+  Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+/// Splits the next chunk into its id, content and children.
+fn take_chunk<'a>(bytes: &mut &'a [u8]) -> io::Result<(&'a [u8], &'a [u8], &'a [u8])> {
+  let id = take(bytes, 4)?;
+  let content_len = take_u32(bytes)? as usize;
+  let children_len = take_u32(bytes)? as usize;
+  Ok((id, take(bytes, content_len)?, take(bytes, children_len)?))
+}
+
+fn take_i32(bytes: &mut &[u8]) -> io::Result<i32> { Ok(take_u32(bytes)? as i32) }
+
+fn take_string(bytes: &mut &[u8]) -> io::Result<String> {
+  let len = take_u32(bytes)? as usize;
+  String::from_utf8(take(bytes, len)?.to_vec())
+    .map_err(|_| invalid_data(FORMAT, "invalid string"))
+}
+
+fn take_dict(bytes: &mut &[u8]) -> io::Result<HashMap<String, String>> {
+  let len = take_u32(bytes)?;
+  (0..len).map(|_| Ok((take_string(bytes)?, take_string(bytes)?))).collect()
+}
+
+/// Writes a transform node with a single frame translated by `translation`.
+fn put_transform(bytes: &mut Vec<u8>, id: i32, child: i32, translation: IVec3) {
+  let mut content = [id, 0, child, -1, -1, 1, 1].map(i32::to_le_bytes).concat();
+  let t = format!("{} {} {}", translation.x, translation.y, translation.z);
+  for string in ["_t", &t] {
+    content.extend((string.len() as u32).to_le_bytes());
+    content.extend(string.as_bytes());
+  }
+  put_chunk(bytes, b"nTRN", &content);
+}
+
+fn put_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+  bytes.extend(id);
+  bytes.extend((content.len() as u32).to_le_bytes());
+  bytes.extend(0u32.to_le_bytes());
+  bytes.extend(content);
+}
@@ -0,0 +1,49 @@
+use bevy::pbr::{
+  MaterialPipeline,
+  MaterialPipelineKey,
+};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{
+  AsBindGroup,
+  RenderPipelineDescriptor,
+  ShaderRef,
+  SpecializedMeshPipelineError,
+};
+
+use crate::mesh_data::ATTRIBUTE_PACKED_VERTEX;
+
+/// `assets/shaders/voxel.wgsl`, embedded by `ChunksPlugin` so that games need not ship it.
+pub const VOXEL_SHADER_HANDLE: HandleUntyped =
+  HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x3a91_6c2e_d047_b58f);
+
+/// Renders meshes built with `MeshFormat::Packed`, lit like a `StandardMaterial`.
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "5c0f3c4e-2a8d-4e43-9d1b-7b6f2f1e9a30"]
+pub struct VoxelMaterial {
+  #[uniform(0)]
+  pub color: Color,
+  #[uniform(0)]
+  pub block_size: Vec3,
+  pub alpha_mode: AlphaMode,
+}
+
+impl Material for VoxelMaterial {
+  fn vertex_shader() -> ShaderRef { VOXEL_SHADER_HANDLE.typed().into() }
+
+  fn fragment_shader() -> ShaderRef { VOXEL_SHADER_HANDLE.typed().into() }
+
+  fn alpha_mode(&self) -> AlphaMode { self.alpha_mode }
+
+  fn specialize(
+    _pipeline: &MaterialPipeline<Self>,
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayout,
+    _key: MaterialPipelineKey<Self>,
+  ) -> Result<(), SpecializedMeshPipelineError> {
+    descriptor.vertex.buffers =
+      vec![layout.get_layout(&[ATTRIBUTE_PACKED_VERTEX.at_shader_location(0)])?];
+    Ok(())
+  }
+}
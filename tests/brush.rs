@@ -0,0 +1,173 @@
+mod common;
+
+use bevy::ecs::system::SystemState;
+use bevy::math::{
+  ivec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::brush::{
+  Brush,
+  BrushMode,
+  BrushShape,
+};
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+  EmptyGenerator,
+};
+
+use common::{
+  config,
+  plugin,
+  voxel,
+  Flat,
+};
+
+fn brush(shape: BrushShape, mode: BrushMode, radius: f32) -> Brush {
+  Brush { shape, mode, radius }
+}
+
+/// Applies one stroke of `brush` at `center` and updates the app to apply its edits.
+fn stroke(app: &mut App, brush: Brush, center: Vec3) {
+  let mut state =
+    SystemState::<(ResMut<ChunkWorld>, Res<ChunkWorldConfig>, Query<&Chunk>)>::new(&mut app.world);
+  let (mut chunk_world, chunk_world_config, chunks) = state.get_mut(&mut app.world);
+  brush.stroke(&mut chunk_world, &chunk_world_config, &chunks, center);
+  app.update();
+}
+
+#[test]
+fn covers_the_blocks_of_each_shape() {
+  // Blocks are 1 unit wide, so the counts are those of lattice points in each shape.
+  for (shape, radius, count) in [
+    (BrushShape::Sphere, 0.0, 1),
+    (BrushShape::Sphere, 1.0, 7),
+    (BrushShape::Sphere, 2.0, 33),
+    (BrushShape::Box, 0.0, 1),
+    (BrushShape::Box, 1.0, 27),
+    (BrushShape::Box, 2.0, 125),
+    (BrushShape::Cylinder, 0.0, 1),
+    (BrushShape::Cylinder, 1.0, 5 * 3),
+    (BrushShape::Cylinder, 2.0, 13 * 5),
+  ] {
+    let blocks = brush(shape, BrushMode::Erase, radius).blocks(&config(), Vec3::ZERO);
+    assert_eq!(blocks.len(), count, "{shape:?} of radius {radius}");
+    assert!(blocks.contains(&IVec3::ZERO), "{shape:?} of radius {radius}");
+  }
+
+  let sphere = brush(BrushShape::Sphere, BrushMode::Erase, 1.0);
+  assert!(!sphere.blocks(&config(), Vec3::ZERO).contains(&ivec3(1, 1, 0)));
+  let cylinder = brush(BrushShape::Cylinder, BrushMode::Erase, 1.0);
+  assert!(cylinder.blocks(&config(), Vec3::ZERO).contains(&ivec3(1, 1, 0)));
+  assert!(!cylinder.blocks(&config(), Vec3::ZERO).contains(&ivec3(1, 0, 1)));
+}
+
+#[test]
+fn covers_the_blocks_around_its_center() {
+  let sphere = brush(BrushShape::Sphere, BrushMode::Erase, 1.0);
+  let mut blocks = sphere.blocks(&config(), vec3(10.0, -3.0, 4.0));
+  blocks.sort_by_key(|pos| pos.to_array());
+  assert_eq!(
+    blocks,
+    [
+      ivec3(9, -3, 4),
+      ivec3(10, -4, 4),
+      ivec3(10, -3, 3),
+      ivec3(10, -3, 4),
+      ivec3(10, -3, 5),
+      ivec3(10, -2, 4),
+      ivec3(11, -3, 4),
+    ]
+  );
+
+  // Between blocks, the ones whose centers are in reach are covered.
+  let sphere = brush(BrushShape::Sphere, BrushMode::Erase, 1.2);
+  assert_eq!(sphere.blocks(&config(), vec3(0.5, 0.0, 0.0)).len(), 2 + 4 * 2);
+}
+
+#[test]
+fn fills_and_erases_what_it_covers() {
+  let mut app = common::app(plugin(Flat(BlockKind(1))), |_| {});
+
+  stroke(&mut app, brush(BrushShape::Box, BrushMode::Fill(BlockKind(3)), 1.0), Vec3::ZERO);
+  for pos in brush(BrushShape::Box, BrushMode::Erase, 1.0).blocks(&config(), Vec3::ZERO) {
+    assert_eq!(voxel(&mut app, pos), Some(Some(BlockKind(3))), "{pos}");
+  }
+  assert_eq!(voxel(&mut app, ivec3(2, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(2, -1, 0)), Some(Some(BlockKind(1))));
+
+  let center = vec3(0.0, -4.0, 0.0);
+  stroke(&mut app, brush(BrushShape::Sphere, BrushMode::Erase, 1.0), center);
+  for pos in brush(BrushShape::Sphere, BrushMode::Erase, 1.0).blocks(&config(), center) {
+    assert_eq!(voxel(&mut app, pos), Some(None), "{pos}");
+  }
+  assert_eq!(voxel(&mut app, ivec3(1, -3, 0)), Some(Some(BlockKind(1))));
+}
+
+#[test]
+fn smooths_out_lone_blocks_and_holes() {
+  let mut app = common::app(plugin(Flat(BlockKind(1))), |_| {});
+  app
+    .world
+    .resource_mut::<ChunkWorld>()
+    .set_blocks([(ivec3(0, 3, 0), Some(BlockKind(2))), (ivec3(0, -4, 0), None)]);
+  app.update();
+
+  let smooth = brush(BrushShape::Box, BrushMode::Smooth, 1.0);
+  stroke(&mut app, smooth, vec3(0.0, 3.0, 0.0));
+  assert_eq!(voxel(&mut app, ivec3(0, 3, 0)), Some(None));
+  stroke(&mut app, smooth, vec3(0.0, -4.0, 0.0));
+  assert_eq!(voxel(&mut app, ivec3(0, -4, 0)), Some(Some(BlockKind(1))));
+
+  // The surface is kept where most of each neighborhood agrees.
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+  stroke(&mut app, smooth, Vec3::ZERO);
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(None));
+}
+
+#[test]
+fn breaks_smoothing_ties_the_same_way_every_time() {
+  let plane = |y, kind| {
+    (-1..=1).flat_map(move |x| (-1..=1).map(move |z| (ivec3(x, y, z), Some(BlockKind(kind)))))
+  };
+  let smooth = brush(BrushShape::Box, BrushMode::Smooth, 0.0);
+
+  // Nine blocks of each kind above and below an empty block: the lowest kind wins.
+  let mut app = common::app(plugin(EmptyGenerator), |_| {});
+  app.world.resource_mut::<ChunkWorld>().set_blocks(plane(1, 3).chain(plane(-1, 2)));
+  app.update();
+  stroke(&mut app, smooth, Vec3::ZERO);
+  assert_eq!(voxel(&mut app, IVec3::ZERO), Some(Some(BlockKind(2))));
+
+  // A block tied with the most common kind is kept.
+  let mut app = common::app(plugin(EmptyGenerator), |_| {});
+  let edits = plane(1, 3).skip(1).chain(plane(-1, 2)).chain([(IVec3::ZERO, Some(BlockKind(3)))]);
+  app.world.resource_mut::<ChunkWorld>().set_blocks(edits);
+  app.update();
+  stroke(&mut app, smooth, Vec3::ZERO);
+  assert_eq!(voxel(&mut app, IVec3::ZERO), Some(Some(BlockKind(3))));
+}
+
+#[test]
+fn displaces_columns_within_its_amplitude() {
+  let mut app = common::app(plugin(Flat(BlockKind(1))), |_| {});
+  let displace = BrushMode::Displace { amplitude: 3.0, frequency: 0.3, seed: 7 };
+  stroke(&mut app, brush(BrushShape::Box, displace, 4.0), Vec3::ZERO);
+
+  // Each column stays solid up to its new surface and empty above it.
+  let mut heights = Vec::new();
+  for x in -4..=4 {
+    for z in -4..=4 {
+      let column = (-4..=4).map(|y| voxel(&mut app, ivec3(x, y, z)).unwrap()).collect::<Vec<_>>();
+      let height = column.iter().take_while(|voxel| voxel.is_some()).count() as i32 - 4;
+      assert!(column[(height + 4) as usize..].iter().all(Option::is_none), "{x}, {z}");
+      assert!(height.abs() <= 3, "{x}, {z} moved to {height}");
+      heights.push(height);
+    }
+  }
+  assert!(heights.iter().any(|height| *height != 0));
+}
@@ -0,0 +1,68 @@
+//! Fixtures shared by the integration tests, which each use some of them.
+#![allow(dead_code)]
+
+use bevy::ecs::event::Event;
+use bevy::ecs::system::SystemState;
+use bevy::math::uvec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkGenerator,
+  ChunkLoadingPoint,
+  ChunkWorld,
+  ChunkWorldConfig,
+  ChunksPlugin,
+};
+use first_voxel_engine::mesh_data::MeshFormat;
+
+/// Blocks of the given kind below y = 0, and empty above.
+pub struct Flat(pub BlockKind);
+
+impl ChunkGenerator for Flat {
+  fn generate(&self, pos: IVec3) -> Option<BlockKind> { (pos.y < 0).then_some(self.0) }
+}
+
+/// Chunks 8 blocks of 1 unit wide, keeping 4 batches of history.
+pub fn config() -> ChunkWorldConfig {
+  ChunkWorldConfig {
+    chunk_size: uvec3(8, 8, 8),
+    block_size: Vec3::ONE,
+    mesh_format: MeshFormat::Standard,
+    history_len: 4,
+  }
+}
+
+/// `ChunksPlugin` with `config` and `generator`, without meshes.
+pub fn plugin(generator: impl ChunkGenerator) -> ChunksPlugin {
+  ChunksPlugin::new(config()).with_generator(generator).with_meshing(false)
+}
+
+/// Loads the 27 chunks from -1 to 1 when at the origin, all at full detail.
+pub fn loading_point() -> ChunkLoadingPoint {
+  ChunkLoadingPoint { radius: 8.0, lod_radii: [f32::INFINITY; 3] }
+}
+
+/// A headless app with `plugin` and whatever `build` adds, updated once with a `loading_point` at
+/// the origin.
+pub fn app(plugin: ChunksPlugin, build: impl FnOnce(&mut App)) -> App {
+  let mut app = App::new();
+  app.add_plugins(MinimalPlugins).add_plugin(plugin);
+  build(&mut app);
+  app.world.spawn((Transform::default(), loading_point()));
+  app.update();
+  app
+}
+
+/// The voxel at `pos`, or `None` if its chunk is not loaded.
+pub fn voxel(app: &mut App, pos: IVec3) -> Option<Option<BlockKind>> {
+  let mut state =
+    SystemState::<(Res<ChunkWorld>, Res<ChunkWorldConfig>, Query<&Chunk>)>::new(&mut app.world);
+  let (chunk_world, chunk_world_config, chunks) = state.get(&app.world);
+  chunk_world.get_voxel(&chunk_world_config, &chunks, pos)
+}
+
+/// The events sent during the last update.
+pub fn events<E: Event + Clone>(app: &App) -> Vec<E> {
+  app.world.resource::<Events<E>>().iter_current_update_events().cloned().collect()
+}
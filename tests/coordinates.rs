@@ -0,0 +1,113 @@
+use bevy::math::{
+  ivec3,
+  uvec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::ChunkWorldConfig;
+use first_voxel_engine::mesh_data::MeshFormat;
+use rand::rngs::StdRng;
+use rand::{
+  Rng,
+  SeedableRng,
+};
+
+/// A config with a random size per axis for chunks and blocks.
+fn random_config(rng: &mut StdRng) -> ChunkWorldConfig {
+  ChunkWorldConfig {
+    chunk_size: uvec3(rng.gen_range(1..40), rng.gen_range(1..40), rng.gen_range(1..40)),
+    block_size: vec3(rng.gen_range(0.1..4.0), rng.gen_range(0.1..4.0), rng.gen_range(0.1..4.0)),
+    mesh_format: MeshFormat::Standard,
+    history_len: 0,
+  }
+}
+
+fn random_pos(rng: &mut StdRng, range: i32) -> IVec3 {
+  ivec3(rng.gen_range(-range..range), rng.gen_range(-range..range), rng.gen_range(-range..range))
+}
+
+#[test]
+fn block_to_chunk_inverts_chunk_to_block() {
+  let mut rng = StdRng::seed_from_u64(0);
+  for _ in 0..200 {
+    let config = random_config(&mut rng);
+    for _ in 0..50 {
+      let pos = random_pos(&mut rng, 10_000);
+      let (key, local) = config.block_to_chunk(pos);
+      assert!(local.cmplt(config.chunk_size).all(), "{local} outside {}", config.chunk_size);
+      assert_eq!(config.chunk_to_block(key) + local.as_ivec3(), pos);
+
+      let key = random_pos(&mut rng, 100);
+      assert_eq!(config.block_to_chunk(config.chunk_to_block(key)), (key, UVec3::ZERO));
+    }
+  }
+}
+
+#[test]
+fn world_to_block_inverts_block_to_world() {
+  let mut rng = StdRng::seed_from_u64(1);
+  for _ in 0..200 {
+    let config = random_config(&mut rng);
+    for _ in 0..50 {
+      let pos = random_pos(&mut rng, 10_000);
+      assert_eq!(config.world_to_block(config.block_to_world(pos)), pos);
+
+      // Anywhere inside the block rounds to it.
+      let offset = vec3(rng.gen_range(-0.49..0.49), rng.gen_range(-0.49..0.49), 0.0);
+      let inside = config.block_to_world(pos) + offset * config.block_size;
+      assert_eq!(config.world_to_block(inside), pos);
+    }
+  }
+}
+
+#[test]
+fn negative_coordinates_land_in_the_chunk_below() {
+  let mut rng = StdRng::seed_from_u64(2);
+  for _ in 0..200 {
+    let config = random_config(&mut rng);
+    let chunk_size = config.chunk_size.as_ivec3();
+    assert_eq!(config.block_to_chunk(IVec3::NEG_ONE), (IVec3::NEG_ONE, config.chunk_size - 1));
+    assert_eq!(config.block_to_chunk(-chunk_size), (IVec3::NEG_ONE, UVec3::ZERO));
+
+    for _ in 0..50 {
+      let pos = -random_pos(&mut rng, 10_000).abs() - 1;
+      let (key, _) = config.block_to_chunk(pos);
+      assert!(key.cmplt(IVec3::ZERO).all());
+      let first = config.chunk_to_block(key);
+      assert!(first.cmple(pos).all() && pos.cmplt(first + chunk_size).all());
+    }
+  }
+}
+
+#[test]
+fn world_to_chunk_finds_the_chunk_of_each_block() {
+  let mut rng = StdRng::seed_from_u64(3);
+  for _ in 0..200 {
+    let config = random_config(&mut rng);
+    for _ in 0..50 {
+      let key = random_pos(&mut rng, 100);
+      assert_eq!(config.world_to_chunk(config.chunk_translation(key)), key);
+
+      let pos = random_pos(&mut rng, 10_000);
+      let translation = config.block_to_world(pos);
+      assert_eq!(config.world_to_chunk(translation), config.block_to_chunk(pos).0);
+    }
+  }
+}
+
+#[test]
+fn chunks_within_covers_the_whole_cube() {
+  let mut rng = StdRng::seed_from_u64(4);
+  for _ in 0..200 {
+    let config = random_config(&mut rng);
+    let center = random_pos(&mut rng, 1000).as_vec3() * config.block_size;
+    let radius = rng.gen_range(0.0..100.0);
+    let (min, max) = config.chunks_within(center, radius);
+    assert!(min.cmplt(max).all());
+    for _ in 0..50 {
+      let offset = vec3(rng.gen(), rng.gen(), rng.gen()) * 2.0 - 1.0;
+      let key = config.world_to_chunk(center + offset * radius);
+      assert!(min.cmple(key).all() && key.cmplt(max).all(), "{key} outside {min}..{max}");
+    }
+  }
+}
@@ -0,0 +1,159 @@
+use bevy::math::{
+  ivec3,
+  uvec3,
+};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use first_voxel_engine::chunks::{
+  visible_chunks,
+  BlockKind,
+  Chunk,
+  ChunkConnectivity,
+  Face,
+  RenderClass,
+  RenderClasses,
+};
+
+const SIZE: u32 = 8;
+
+/// An 8x8x8 chunk of stone with the blocks `empty` picks left out.
+fn chunk(empty: impl Fn(IVec3) -> bool) -> Chunk {
+  let mut voxels = Vec::new();
+  for x in 0..SIZE as i32 {
+    for y in 0..SIZE as i32 {
+      for z in 0..SIZE as i32 {
+        voxels.push((!empty(ivec3(x, y, z))).then_some(BlockKind(0)));
+      }
+    }
+  }
+  Chunk::from_voxels(&voxels, uvec3(SIZE, SIZE, SIZE), Vec3::ONE)
+}
+
+/// The connectivity of `chunk` with every kind opaque.
+fn connectivity(chunk: &Chunk) -> ChunkConnectivity {
+  ChunkConnectivity::new(chunk, &RenderClasses::default())
+}
+
+const FACES: [Face; 6] =
+  [Face::Front, Face::Back, Face::Right, Face::Left, Face::Top, Face::Bottom];
+
+/// The pairs of different faces `connectivity` connects.
+fn connected_pairs(connectivity: &ChunkConnectivity) -> Vec<(Face, Face)> {
+  let mut pairs = Vec::new();
+  for (i, from) in FACES.iter().enumerate() {
+    for to in &FACES[i + 1..] {
+      assert_eq!(connectivity.connects(*from, *to), connectivity.connects(*to, *from));
+      if connectivity.connects(*from, *to) {
+        pairs.push((*from, *to));
+      }
+    }
+  }
+  pairs
+}
+
+#[test]
+fn connects_faces_through_empty_blocks() {
+  assert_eq!(connectivity(&chunk(|_| true)), ChunkConnectivity::OPEN);
+  assert!(connected_pairs(&connectivity(&chunk(|_| false))).is_empty());
+
+  // A tunnel along x.
+  let tunnel = chunk(|pos| pos.y == 3 && pos.z == 3);
+  assert_eq!(connected_pairs(&connectivity(&tunnel)), [(Face::Right, Face::Left)]);
+
+  // A cave from the left face bending up to the top face, and a sealed pocket.
+  let cave = chunk(|pos| {
+    (pos.y == 2 && pos.z == 2 && pos.x <= 4)
+      || (pos.x == 4 && pos.z == 2 && pos.y >= 2)
+      || (pos.x == 6 && pos.y == 5 && pos.z == 5)
+  });
+  assert_eq!(connected_pairs(&connectivity(&cave)), [(Face::Left, Face::Top)]);
+
+  // Two separate tunnels do not connect to each other.
+  let tunnels = chunk(|pos| (pos.y == 1 && pos.z == 1) || (pos.x == 6 && pos.y == 6));
+  assert_eq!(
+    connected_pairs(&connectivity(&tunnels)),
+    [(Face::Front, Face::Back), (Face::Right, Face::Left)]
+  );
+}
+
+#[test]
+fn sees_through_blocks_that_are_not_opaque() {
+  // A sheet of glass across the middle of a stone chunk.
+  let mut chunk = chunk(|_| false);
+  for (i, block) in chunk.blocks.iter_mut().enumerate() {
+    if i % SIZE as usize == 4 {
+      block.set_voxel(Some(BlockKind(1)));
+    }
+  }
+  assert!(connected_pairs(&connectivity(&chunk)).is_empty());
+
+  let glass =
+    RenderClasses { kinds: HashMap::from_iter([(BlockKind(1), RenderClass::Translucent)]) };
+  assert_eq!(
+    connected_pairs(&ChunkConnectivity::new(&chunk, &glass)),
+    [
+      (Face::Right, Face::Left),
+      (Face::Right, Face::Top),
+      (Face::Right, Face::Bottom),
+      (Face::Left, Face::Top),
+      (Face::Left, Face::Bottom),
+      (Face::Top, Face::Bottom),
+    ]
+  );
+}
+
+/// A 9x9x9 grid of chunks around the origin, solid unless `connectivities` says otherwise.
+fn visible(connectivities: &HashMap<IVec3, ChunkConnectivity>) -> Vec<IVec3> {
+  let solid = connectivity(&chunk(|_| false));
+  let mut visible = visible_chunks(IVec3::ZERO, |key| {
+    (key.abs().max_element() <= 4).then(|| connectivities.get(&key).copied().unwrap_or(solid))
+  })
+  .into_iter()
+  .collect::<Vec<_>>();
+  visible.sort_by_key(|key| key.to_array());
+  visible
+}
+
+fn sorted(mut keys: Vec<IVec3>) -> Vec<IVec3> {
+  keys.sort_by_key(|key| key.to_array());
+  keys
+}
+
+#[test]
+fn solid_chunks_hide_what_is_behind_them() {
+  // Only the camera's chunk and its neighbors are seen.
+  let seen = visible(&HashMap::new());
+  assert_eq!(seen.len(), 7);
+  assert!(seen.iter().all(|key| key.abs().dot(IVec3::ONE) <= 1));
+}
+
+#[test]
+fn sees_along_open_chunks() {
+  let open = (0..=4).map(|x| (ivec3(x, 0, 0), ChunkConnectivity::OPEN)).collect();
+  let seen = visible(&open);
+  // The open row, the solid chunks around it and the one behind the camera.
+  assert!(seen.contains(&ivec3(4, 0, 0)));
+  assert!(seen.contains(&ivec3(4, 1, 0)));
+  assert!(!seen.contains(&ivec3(4, 2, 0)));
+  assert_eq!(seen.len(), 5 + 4 * 5 + 1);
+
+  // A tunnel that only runs along x hides the chunks beside it.
+  let tunnel = connectivity(&chunk(|pos| pos.y == 3 && pos.z == 3));
+  let tunnels = (1..=4).map(|x| (ivec3(x, 0, 0), tunnel)).collect();
+  let neighbors = [IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+  let expected = (0..=4).map(|x| ivec3(x, 0, 0)).chain(neighbors).collect();
+  assert_eq!(visible(&tunnels), sorted(expected));
+}
+
+#[test]
+fn never_turns_back_towards_the_camera() {
+  // A U-shaped cave going right, up, then back left over the camera.
+  let open = [ivec3(1, 0, 0), ivec3(1, 1, 0), ivec3(1, 2, 0), ivec3(0, 2, 0), ivec3(-1, 2, 0)]
+    .into_iter()
+    .map(|key| (key, ChunkConnectivity::OPEN))
+    .collect();
+  let seen = visible(&open);
+  assert!(seen.contains(&ivec3(1, 2, 0)));
+  assert!(!seen.contains(&ivec3(0, 2, 0)));
+  assert!(!seen.contains(&ivec3(-1, 2, 0)));
+}
@@ -0,0 +1,148 @@
+use bevy::math::uvec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  decode_chunk,
+  encode_chunk,
+  BlockKind,
+  Chunk,
+  ChunkCompression,
+};
+use rand::rngs::StdRng;
+use rand::{
+  Rng,
+  SeedableRng,
+};
+
+#[cfg(not(feature = "lz4"))]
+const COMPRESSIONS: [ChunkCompression; 2] = [ChunkCompression::None, ChunkCompression::Deflate];
+#[cfg(feature = "lz4")]
+const COMPRESSIONS: [ChunkCompression; 3] =
+  [ChunkCompression::None, ChunkCompression::Deflate, ChunkCompression::Lz4];
+
+fn voxels(chunk: &Chunk) -> Vec<Option<BlockKind>> {
+  chunk.blocks.iter().map(|block| block.voxel()).collect()
+}
+
+/// A chunk of random size with blocks drawn from `kinds` kinds, in runs of random length.
+fn random_chunk(rng: &mut StdRng, kinds: u16) -> Chunk {
+  let size = uvec3(rng.gen_range(1..20), rng.gen_range(1..20), rng.gen_range(1..20));
+  let len = (size.x * size.y * size.z) as usize;
+  let max_run = rng.gen_range(1..64);
+  let mut voxels = Vec::with_capacity(len);
+  while voxels.len() < len {
+    let value = rng.gen_range(0..kinds);
+    let voxel = value.checked_sub(1).map(|kind| BlockKind(kind as u8));
+    let run = rng.gen_range(1..=max_run).min(len - voxels.len());
+    voxels.extend(std::iter::repeat_n(voxel, run));
+  }
+  Chunk::from_voxels(&voxels, size, Vec3::ONE)
+}
+
+fn round_trip(chunk: &Chunk, compression: ChunkCompression) -> Chunk {
+  decode_chunk(&encode_chunk(chunk, compression), chunk.block_size).unwrap()
+}
+
+#[test]
+fn round_trips_random_chunks() {
+  let mut rng = StdRng::seed_from_u64(0);
+  for kinds in [1, 2, 3, 16, 17, 200, 257] {
+    for _ in 0..20 {
+      let chunk = random_chunk(&mut rng, kinds);
+      for compression in COMPRESSIONS {
+        let decoded = round_trip(&chunk, compression);
+        assert_eq!(decoded.size, chunk.size);
+        assert_eq!(voxels(&decoded), voxels(&chunk));
+      }
+    }
+  }
+}
+
+#[test]
+fn round_trips_uniform_chunks() {
+  for voxel in [None, Some(BlockKind(0)), Some(BlockKind(255))] {
+    let chunk = Chunk::from_voxels(&[voxel; 16 * 16 * 16], uvec3(16, 16, 16), Vec3::splat(0.5));
+    for compression in COMPRESSIONS {
+      let data = encode_chunk(&chunk, compression);
+      assert!(data.len() < 32, "{} bytes", data.len());
+      let decoded = decode_chunk(&data, chunk.block_size).unwrap();
+      assert_eq!(voxels(&decoded), voxels(&chunk));
+      assert_eq!(decoded.block_size, chunk.block_size);
+    }
+  }
+}
+
+#[test]
+fn packs_noisy_chunks_below_a_byte_per_block() {
+  let mut rng = StdRng::seed_from_u64(1);
+  let voxels = (0..16 * 16 * 16)
+    .map(|_| rng.gen_bool(0.5).then(|| BlockKind(rng.gen_range(0..3))))
+    .collect::<Vec<_>>();
+  let chunk = Chunk::from_voxels(&voxels, uvec3(16, 16, 16), Vec3::ONE);
+  let data = encode_chunk(&chunk, ChunkCompression::None);
+  // Four kinds take 2 bits each.
+  assert!(data.len() < voxels.len() / 4 + 32, "{} bytes", data.len());
+  assert_eq!(round_trip(&chunk, ChunkCompression::None).blocks.len(), voxels.len());
+}
+
+#[test]
+fn skips_unknown_header_fields() {
+  let chunk = random_chunk(&mut StdRng::seed_from_u64(2), 5);
+  let data = encode_chunk(&chunk, ChunkCompression::Deflate);
+
+  // A newer writer that appends a header field but stays readable by version 1.
+  let mut newer = data[..6].to_vec();
+  newer[4] = 2;
+  let header_len = u16::from_le_bytes([data[6], data[7]]);
+  newer.extend((header_len + 3).to_le_bytes());
+  newer.extend(&data[8..8 + header_len as usize]);
+  newer.extend([0xaa, 0xbb, 0xcc]);
+  newer.extend(&data[8 + header_len as usize..]);
+  assert_eq!(voxels(&decode_chunk(&newer, Vec3::ONE).unwrap()), voxels(&chunk));
+
+  // One that older readers cannot decode.
+  let mut incompatible = data.clone();
+  incompatible[5] = 2;
+  assert!(decode_chunk(&incompatible, Vec3::ONE).is_err());
+}
+
+#[test]
+fn rejects_corrupted_data_without_panicking() {
+  let mut rng = StdRng::seed_from_u64(3);
+  for _ in 0..200 {
+    let kinds = rng.gen_range(1..20);
+    let chunk = random_chunk(&mut rng, kinds);
+    let compression = COMPRESSIONS[rng.gen_range(0..COMPRESSIONS.len())];
+    let data = encode_chunk(&chunk, compression);
+
+    let truncated = &data[..rng.gen_range(0..data.len())];
+    assert!(decode_chunk(truncated, Vec3::ONE).is_err());
+
+    // Flipped bytes may still decode to some chunk, but must not panic.
+    let mut corrupted = data.clone();
+    for _ in 0..rng.gen_range(1..4) {
+      let i = rng.gen_range(0..corrupted.len());
+      corrupted[i] ^= rng.gen_range(1..=255);
+    }
+    let _ = decode_chunk(&corrupted, Vec3::ONE);
+
+    let garbage = (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect::<Vec<u8>>();
+    let _ = decode_chunk(&garbage, Vec3::ONE);
+  }
+
+  // A zero axis makes the other axes' product 0, however large they are.
+  let mut data = encode_chunk(&random_chunk(&mut rng, 1), ChunkCompression::None);
+  for (i, axis) in [65536u32, 65536, 0].into_iter().enumerate() {
+    data[8 + i * 4..12 + i * 4].copy_from_slice(&axis.to_le_bytes());
+  }
+  assert!(decode_chunk(&data, Vec3::ONE).is_err());
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn round_trips_lz4_bodies() {
+  let chunk = random_chunk(&mut StdRng::seed_from_u64(4), 17);
+  let data = encode_chunk(&chunk, ChunkCompression::Lz4);
+  // The compression byte follows the three size axes of the header.
+  assert_eq!(data[20], 2);
+  assert_eq!(voxels(&decode_chunk(&data, Vec3::ONE).unwrap()), voxels(&chunk));
+}
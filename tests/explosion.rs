@@ -0,0 +1,118 @@
+mod common;
+
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockChanged,
+  BlockKind,
+  ChunkGenerator,
+  ChunkWorld,
+  Debris,
+  Explosion,
+  Falloff,
+};
+
+use common::{
+  app,
+  events,
+  plugin,
+  voxel,
+};
+
+const STONE: BlockKind = BlockKind(1);
+const OBSIDIAN: BlockKind = BlockKind(2);
+
+/// Solid stone everywhere.
+struct Solid;
+
+impl ChunkGenerator for Solid {
+  fn generate(&self, _pos: IVec3) -> Option<BlockKind> { Some(STONE) }
+}
+
+/// Applies the queued edits, returning the changes.
+fn update(app: &mut App) -> Vec<BlockChanged> {
+  app.update();
+  events(app)
+}
+
+#[test]
+fn sphere_removes_blocks_within_reach() {
+  let mut app = app(plugin(Solid), |_| {});
+  app
+    .world
+    .resource_mut::<ChunkWorld>()
+    .explode_with(Explosion::new(Vec3::ZERO, 4.0).with_falloff(Falloff::Sphere));
+  let changes = update(&mut app);
+
+  assert!(!changes.is_empty());
+  assert!(changes.iter().all(|change| change.before == Some(STONE) && change.after.is_none()));
+  // Stone absorbs 1, so blocks less than 3 blocks away are removed.
+  assert!(changes.iter().all(|change| change.pos.as_vec3().length() < 3.0));
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(2, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(3, 0, 0)), Some(Some(STONE)));
+}
+
+#[test]
+fn tough_blocks_shelter_the_blocks_behind_them() {
+  let mut app = app(plugin(Solid).with_block_resistance(OBSIDIAN, f32::INFINITY), |_| {});
+  let wall = (-8..=8).flat_map(|y| (-8..=8).map(move |z| (ivec3(1, y, z), Some(OBSIDIAN))));
+  app.world.resource_mut::<ChunkWorld>().set_blocks(wall);
+  app.update();
+
+  app.world.resource_mut::<ChunkWorld>().explode(Vec3::ZERO, 6.0);
+  let changes = update(&mut app);
+
+  assert!(!changes.is_empty());
+  assert!(changes.iter().all(|change| change.before == Some(STONE) && change.pos.x <= 0));
+  assert_eq!(voxel(&mut app, ivec3(-2, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(1, 0, 0)), Some(Some(OBSIDIAN)));
+  assert_eq!(voxel(&mut app, ivec3(2, 0, 0)), Some(Some(STONE)));
+}
+
+#[test]
+fn explosions_are_undone_as_one_edit() {
+  let mut app = app(plugin(Solid), |_| {});
+  app.world.resource_mut::<ChunkWorld>().explode(Vec3::ZERO, 4.0);
+  let changes = update(&mut app);
+  assert!(changes.len() > 1);
+  assert!(changes.iter().all(|change| !change.remote));
+
+  app.world.resource_mut::<ChunkWorld>().undo();
+  let undone = update(&mut app);
+  assert_eq!(undone.len(), changes.len());
+  for change in changes {
+    assert_eq!(voxel(&mut app, change.pos), Some(Some(STONE)));
+  }
+}
+
+#[test]
+fn leaves_unloaded_chunks_untouched() {
+  let mut app = app(plugin(Solid), |_| {});
+  // The loaded chunks span blocks -8 to 15 on each axis.
+  app
+    .world
+    .resource_mut::<ChunkWorld>()
+    .explode_with(Explosion::new(Vec3::splat(14.0), 6.0).with_falloff(Falloff::Sphere));
+  let changes = update(&mut app);
+  assert!(!changes.is_empty());
+  assert!(changes.iter().all(|change| change.pos.max_element() <= 15));
+}
+
+#[test]
+fn throws_debris_when_asked() {
+  let mut app = app(plugin(Solid), |_| {});
+  app.world.resource_mut::<ChunkWorld>().explode(Vec3::ZERO, 3.0);
+  update(&mut app);
+  assert_eq!(app.world.query::<&Debris>().iter(&app.world).count(), 0);
+
+  app
+    .world
+    .resource_mut::<ChunkWorld>()
+    .explode_with(Explosion::new(Vec3::ZERO, 5.0).with_debris(true));
+  update(&mut app);
+  let debris =
+    app.world.query::<&Debris>().iter(&app.world).map(|debris| debris.kind).collect::<Vec<_>>();
+  assert!(!debris.is_empty() && debris.len() <= 64);
+  assert!(debris.iter().all(|kind| *kind == STONE));
+}
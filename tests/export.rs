@@ -0,0 +1,180 @@
+mod common;
+
+use bevy::ecs::system::SystemState;
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use bevy::render::render_resource::PrimitiveTopology;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+  ChunkWorldConfig,
+};
+use first_voxel_engine::export::{
+  write_glb,
+  write_obj,
+};
+use first_voxel_engine::mesh_data::MeshData;
+
+use common::{
+  plugin,
+  Flat,
+};
+
+/// A unit quad facing +y, as two triangles.
+fn quad() -> MeshData {
+  MeshData {
+    primitive_topology: PrimitiveTopology::TriangleList,
+    vertex_positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+    vertex_normals: vec![[0.0, 1.0, 0.0]; 4],
+    vertex_uvs: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    vertex_ao: vec![3; 4],
+    vertex_layers: vec![0; 4],
+    indices: vec![0, 2, 1, 0, 3, 2],
+  }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+  u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn region_mesh_data(app: &mut App, min: IVec3, max: IVec3) -> MeshData {
+  let mut state =
+    SystemState::<(Res<ChunkWorld>, Res<ChunkWorldConfig>, Query<&Chunk>)>::new(&mut app.world);
+  let (chunk_world, chunk_world_config, chunks) = state.get(&app.world);
+  chunk_world.region_mesh_data(&chunk_world_config, &chunks, min, max)
+}
+
+#[test]
+fn writes_obj_with_one_based_indices() {
+  let mut obj = Vec::new();
+  write_obj(&quad(), &mut obj).unwrap();
+  assert_eq!(
+    String::from_utf8(obj).unwrap(),
+    "# first-voxel-engine
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+vn 0 1 0
+vn 0 1 0
+vn 0 1 0
+vn 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1/1 3/3/3 2/2/2
+f 1/1/1 4/4/4 3/3/3
+"
+  );
+
+  let lines = MeshData {
+    primitive_topology: PrimitiveTopology::LineList,
+    indices: vec![0, 1, 2, 3],
+    ..quad()
+  };
+  let mut obj = Vec::new();
+  write_obj(&lines, &mut obj).unwrap();
+  let obj = String::from_utf8(obj).unwrap();
+  assert!(obj.ends_with("l 1/1 2/2\nl 3/3 4/4\n"), "{obj}");
+}
+
+#[test]
+fn leaves_missing_attributes_out_of_obj_faces() {
+  let obj = |mesh_data: MeshData| {
+    let mut obj = Vec::new();
+    write_obj(&mesh_data, &mut obj).unwrap();
+    String::from_utf8(obj).unwrap()
+  };
+  let without_normals = obj(MeshData { vertex_normals: Vec::new(), ..quad() });
+  assert!(!without_normals.contains("vn "));
+  assert!(without_normals.ends_with("f 1/1 3/3 2/2\nf 1/1 4/4 3/3\n"), "{without_normals}");
+  let without_uvs = obj(MeshData { vertex_uvs: Vec::new(), ..quad() });
+  assert!(without_uvs.ends_with("f 1//1 3//3 2//2\nf 1//1 4//4 3//3\n"), "{without_uvs}");
+  let bare = obj(MeshData { vertex_normals: Vec::new(), vertex_uvs: Vec::new(), ..quad() });
+  assert!(bare.ends_with("v 0 0 1\nf 1 3 2\nf 1 4 3\n"), "{bare}");
+}
+
+#[test]
+fn writes_aligned_glb_chunks() {
+  let mut glb = Vec::new();
+  write_glb(&quad(), &mut glb).unwrap();
+
+  assert_eq!(&glb[0..4], b"glTF");
+  assert_eq!(u32_at(&glb, 4), 2);
+  assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+  let json_len = u32_at(&glb, 12) as usize;
+  assert_eq!(&glb[16..20], b"JSON");
+  assert_eq!(json_len % 4, 0);
+  let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+  for expected in [
+    r#""count":4,"type":"VEC3""#,
+    r#""count":6,"type":"SCALAR""#,
+    r#""min":[0,0,0],"max":[1,0,1]"#,
+    r#""mode":4"#,
+    // Positions and normals are 12 bytes a vertex, UVs 8 and indices 4 each.
+    r#""byteLength":152"#,
+  ] {
+    assert!(json.contains(expected), "{expected} not in {json}");
+  }
+
+  let bin_len = u32_at(&glb, 20 + json_len) as usize;
+  assert_eq!(&glb[24 + json_len..28 + json_len], b"BIN\0");
+  assert_eq!(bin_len, 152);
+  let bin = &glb[28 + json_len..];
+  assert_eq!(bin.len(), bin_len);
+  assert_eq!(&bin[12..24], [1.0f32, 0.0, 0.0].map(f32::to_le_bytes).concat());
+  assert_eq!(&bin[bin_len - 8..], [3u32, 2].map(u32::to_le_bytes).concat());
+}
+
+#[test]
+fn leaves_missing_attributes_out_of_glb() {
+  let mesh_data = MeshData { vertex_normals: Vec::new(), vertex_uvs: Vec::new(), ..quad() };
+  let mut glb = Vec::new();
+  write_glb(&mesh_data, &mut glb).unwrap();
+
+  let json_len = u32_at(&glb, 12) as usize;
+  let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+  assert!(!json.contains("NORMAL") && !json.contains("TEXCOORD_0"), "{json}");
+  for expected in [
+    r#""attributes":{"POSITION":0},"indices":1"#,
+    r#"{"bufferView":1,"componentType":5125,"count":6,"type":"SCALAR"}"#,
+    // Only positions and indices are left.
+    r#""byteLength":72"#,
+  ] {
+    assert!(json.contains(expected), "{expected} not in {json}");
+  }
+  assert_eq!(u32_at(&glb, 20 + json_len), 72);
+}
+
+#[test]
+fn rejects_empty_glb_meshes() {
+  assert!(write_glb(&MeshData::default(), &mut Vec::new()).is_err());
+  assert!(write_glb(&MeshData { indices: Vec::new(), ..quad() }, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn meshes_the_loaded_region_with_its_edits() {
+  let mut app = common::app(plugin(Flat(BlockKind(1))), |_| {});
+  let (min, max) = (IVec3::NEG_ONE, IVec3::splat(2));
+
+  // The ground of the 9 chunks below y = 0 is 24 blocks wide and 8 deep, with the faces between
+  // chunks hidden and those at the edges of the region kept.
+  let faces = |mesh_data: &MeshData| mesh_data.indices.len() / 6;
+  let mesh_data = region_mesh_data(&mut app, min, max);
+  assert_eq!(faces(&mesh_data), 2 * 24 * 24 + 4 * 24 * 8);
+  assert!(mesh_data.vertex_positions.iter().all(|[_, y, _]| *y < 0.0));
+
+  // A block placed on the ground shows 5 faces and hides 1 of the ground.
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, 0, 0), Some(BlockKind(2)))]);
+  app.update();
+  let mesh_data = region_mesh_data(&mut app, min, max);
+  assert_eq!(faces(&mesh_data), 2 * 24 * 24 + 4 * 24 * 8 + 4);
+  assert!(mesh_data.vertex_positions.iter().any(|[_, y, _]| *y > 0.0));
+
+  // Chunks outside the region or not loaded are left out.
+  assert_eq!(faces(&region_mesh_data(&mut app, ivec3(0, -1, 0), ivec3(1, 0, 1))), 6 * 8 * 8);
+  assert!(region_mesh_data(&mut app, IVec3::splat(5), IVec3::splat(7)).indices.is_empty());
+}
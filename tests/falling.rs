@@ -0,0 +1,100 @@
+mod common;
+
+use std::time::{
+  Duration,
+  Instant,
+};
+
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  ChunkWorld,
+};
+use first_voxel_engine::falling::{
+  FallMode,
+  FallingBlock,
+  FallingBlocksPlugin,
+};
+
+use common::{
+  plugin,
+  voxel,
+  Flat,
+};
+
+const STONE: BlockKind = BlockKind(1);
+const SAND: BlockKind = BlockKind(2);
+const GRAVEL: BlockKind = BlockKind(3);
+
+/// A headless app with the 27 chunks around the origin loaded, where sand and gravel fall.
+fn app(mode: FallMode) -> App {
+  common::app(plugin(Flat(STONE)), |app| {
+    app.add_plugin(FallingBlocksPlugin::new([SAND, GRAVEL]).with_mode(mode).with_gravity(400.0));
+  })
+}
+
+/// Applies `edits`, then the falls they cause.
+fn edit(app: &mut App, edits: impl IntoIterator<Item = (IVec3, Option<BlockKind>)>) {
+  app.world.resource_mut::<ChunkWorld>().set_blocks(edits);
+  app.update();
+  app.update();
+}
+
+fn column(app: &mut App, x: i32, ys: std::ops::Range<i32>) -> Vec<Option<BlockKind>> {
+  ys.map(|y| voxel(app, ivec3(x, y, 0)).unwrap()).collect()
+}
+
+#[test]
+fn drops_placed_blocks_to_the_ground() {
+  let mut app = app(FallMode::Instant);
+  edit(&mut app, [(ivec3(0, 5, 0), Some(SAND)), (ivec3(1, 5, 0), Some(STONE))]);
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(SAND)));
+  assert_eq!(voxel(&mut app, ivec3(0, 5, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(1, 5, 0)), Some(Some(STONE)));
+}
+
+#[test]
+fn collapses_columns_when_their_support_is_removed() {
+  let mut app = app(FallMode::Instant);
+  edit(
+    &mut app,
+    [
+      (ivec3(0, 0, 0), Some(STONE)),
+      (ivec3(0, 1, 0), Some(SAND)),
+      (ivec3(0, 2, 0), Some(GRAVEL)),
+      (ivec3(0, 3, 0), Some(SAND)),
+      (ivec3(0, 4, 0), Some(STONE)),
+      (ivec3(0, 5, 0), Some(SAND)),
+    ],
+  );
+  assert_eq!(
+    column(&mut app, 0, 0..6),
+    [Some(STONE), Some(SAND), Some(GRAVEL), Some(SAND), Some(STONE), Some(SAND)]
+  );
+
+  edit(&mut app, [(ivec3(0, 0, 0), None), (ivec3(0, -1, 0), None), (ivec3(0, -2, 0), None)]);
+  // Stone does not fall, so it stays up along with the sand resting on it.
+  assert_eq!(
+    column(&mut app, 0, -3..6),
+    [Some(STONE), Some(SAND), Some(GRAVEL), Some(SAND), None, None, None, Some(STONE), Some(SAND)]
+  );
+}
+
+#[test]
+fn falls_as_entities_that_land() {
+  let mut app = app(FallMode::Entities);
+  edit(&mut app, [(ivec3(0, 6, 0), Some(SAND)), (ivec3(0, 7, 0), Some(GRAVEL))]);
+  assert_eq!(column(&mut app, 0, 0..8), [None; 8]);
+  let mut falling_blocks = app.world.query::<&FallingBlock>();
+  assert_eq!(falling_blocks.iter(&app.world).count(), 2);
+
+  let start = Instant::now();
+  while falling_blocks.iter(&app.world).count() > 0 {
+    assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+    std::thread::sleep(Duration::from_millis(5));
+    app.update();
+  }
+  app.update();
+  assert_eq!(column(&mut app, 0, -1..3), [Some(STONE), Some(SAND), Some(GRAVEL), None]);
+}
@@ -0,0 +1,425 @@
+mod common;
+
+use bevy::ecs::system::SystemState;
+use bevy::input::InputPlugin;
+use bevy::math::{
+  ivec3,
+  uvec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunk_debug::{
+  ChunkDebugOverlay,
+  DebugRender,
+};
+use first_voxel_engine::chunks::{
+  BlockChanged,
+  BlockKind,
+  Chunk,
+  ChunkGenerated,
+  ChunkGenerator,
+  ChunkLoadingPoint,
+  ChunkMaterials,
+  ChunkUnloaded,
+  ChunkWorld,
+  ChunkWorldConfig,
+  ChunksPlugin,
+  Face,
+  RenderClass,
+};
+use first_voxel_engine::mesh_data::MeshFormat;
+
+use common::{
+  config,
+  events,
+  loading_point,
+  plugin,
+  voxel,
+  Flat,
+};
+
+/// A headless app with one loading point at the origin, updated once. Chunks are 8 blocks of 1
+/// unit wide, so the 27 chunks from -1 to 1 are loaded.
+fn app() -> App { common::app(plugin(Flat(BlockKind(1))), |_| {}) }
+
+fn edit(app: &mut App, edits: impl IntoIterator<Item = (IVec3, Option<BlockKind>)>) {
+  app.world.resource_mut::<ChunkWorld>().set_blocks(edits);
+  app.update();
+}
+
+#[test]
+fn generates_chunks_around_loading_points() {
+  let app = app();
+  let chunk_world = app.world.resource::<ChunkWorld>();
+  assert_eq!(chunk_world.chunks.len(), 27);
+  for x in -1..=1 {
+    for y in -1..=1 {
+      for z in -1..=1 {
+        assert!(chunk_world.chunks.contains_key(&ivec3(x, y, z)));
+      }
+    }
+  }
+  assert_eq!(events::<ChunkGenerated>(&app).len(), 27);
+}
+
+#[test]
+fn fills_chunks_from_the_generator() {
+  let mut app = app();
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut app, ivec3(-8, -8, 15)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(15, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(16, 0, 0)), None);
+}
+
+#[test]
+fn applies_edits_on_update() {
+  let mut app = app();
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(1, 2, 3), Some(BlockKind(2)))]);
+  assert_eq!(voxel(&mut app, ivec3(1, 2, 3)), Some(None));
+
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(1, 2, 3)), Some(Some(BlockKind(2))));
+  let block_changed = events::<BlockChanged>(&app);
+  assert_eq!(block_changed.len(), 1);
+  assert_eq!(block_changed[0].pos, ivec3(1, 2, 3));
+  assert_eq!(block_changed[0].key, IVec3::ZERO);
+  assert_eq!((block_changed[0].before, block_changed[0].after), (None, Some(BlockKind(2))));
+}
+
+#[test]
+fn skips_edits_that_change_nothing() {
+  let mut app = app();
+  edit(&mut app, [(ivec3(0, -1, 0), Some(BlockKind(1))), (ivec3(0, 0, 0), None)]);
+  edit(&mut app, [(ivec3(100, 0, 0), Some(BlockKind(1)))]);
+  assert!(events::<BlockChanged>(&app).is_empty());
+
+  // Neither batch was recorded, so there is nothing to undo.
+  app.world.resource_mut::<ChunkWorld>().undo();
+  app.update();
+  assert!(events::<BlockChanged>(&app).is_empty());
+}
+
+#[test]
+fn undoes_and_redoes_whole_batches() {
+  let mut app = app();
+  edit(&mut app, [(ivec3(0, 0, 0), Some(BlockKind(3))), (ivec3(0, -1, 0), None)]);
+  edit(&mut app, [(ivec3(0, 1, 0), Some(BlockKind(4)))]);
+
+  app.world.resource_mut::<ChunkWorld>().undo();
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(0, 1, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(BlockKind(3))));
+
+  app.world.resource_mut::<ChunkWorld>().undo();
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+
+  app.world.resource_mut::<ChunkWorld>().redo();
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(BlockKind(3))));
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(None));
+  assert_eq!(voxel(&mut app, ivec3(0, 1, 0)), Some(None));
+}
+
+#[test]
+fn keeps_at_most_history_len_batches() {
+  let mut app = app();
+  for x in 0..6 {
+    edit(&mut app, [(ivec3(x, 0, 0), Some(BlockKind(1)))]);
+  }
+  for _ in 0..6 {
+    app.world.resource_mut::<ChunkWorld>().undo();
+    app.update();
+  }
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut app, ivec3(1, 0, 0)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut app, ivec3(2, 0, 0)), Some(None));
+}
+
+#[test]
+fn unloads_chunks_left_behind() {
+  let mut app = app();
+  edit(&mut app, [(ivec3(0, 0, 0), Some(BlockKind(2)))]);
+  let mut clps = app.world.query_filtered::<&mut Transform, With<ChunkLoadingPoint>>();
+  clps.single_mut(&mut app.world).translation = vec3(24.0, 0.0, 0.0);
+  app.update();
+
+  // The loading point now covers chunks 2 to 4 along x, so chunks -1 to 1 are despawned.
+  assert_eq!(events::<ChunkUnloaded>(&app).len(), 27);
+  assert!(events::<ChunkUnloaded>(&app).iter().all(|event| event.key.x < 2));
+  assert_eq!(events::<ChunkGenerated>(&app).len(), 27);
+  assert_eq!(app.world.resource::<ChunkWorld>().chunks.len(), 27);
+  assert_eq!(app.world.query::<&Chunk>().iter(&app.world).len(), 27);
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), None);
+
+  // Coming back generates the chunks again, and restores the edited one as it was left.
+  clps.single_mut(&mut app.world).translation = Vec3::ZERO;
+  app.update();
+  assert_eq!(events::<ChunkUnloaded>(&app).len(), 27);
+  assert_eq!(events::<ChunkGenerated>(&app).len(), 27);
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(BlockKind(2))));
+  assert_eq!(voxel(&mut app, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+}
+
+#[test]
+fn restores_replaced_chunks_when_they_load_again() {
+  let mut app = app();
+  let stone = Chunk::from_voxels(&[Some(BlockKind(3)); 512], uvec3(8, 8, 8), Vec3::ONE);
+  app.world.resource_mut::<ChunkWorld>().replace_chunk(ivec3(1, 0, 0), stone);
+  app.update();
+
+  let mut clps = app.world.query_filtered::<&mut Transform, With<ChunkLoadingPoint>>();
+  clps.single_mut(&mut app.world).translation = vec3(48.0, 0.0, 0.0);
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(8, 0, 0)), None);
+
+  clps.single_mut(&mut app.world).translation = Vec3::ZERO;
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(8, 0, 0)), Some(Some(BlockKind(3))));
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(None));
+}
+
+#[test]
+fn keeps_chunks_within_any_loading_point() {
+  let mut app = app();
+  let far = app.world.spawn((Transform::from_xyz(24.0, 0.0, 0.0), loading_point())).id();
+  app.update();
+  assert_eq!(app.world.resource::<ChunkWorld>().chunks.len(), 54);
+
+  // Moving one point only unloads the chunks neither point covers.
+  app.world.get_mut::<Transform>(far).unwrap().translation = vec3(48.0, 0.0, 0.0);
+  app.update();
+  assert!(events::<ChunkUnloaded>(&app).iter().all(|event| (2..5).contains(&event.key.x)));
+  assert_eq!(events::<ChunkUnloaded>(&app).len(), 27);
+  assert_eq!(app.world.resource::<ChunkWorld>().chunks.len(), 54);
+
+  app.world.despawn(far);
+  app.update();
+  assert_eq!(events::<ChunkUnloaded>(&app).len(), 27);
+  assert_eq!(app.world.resource::<ChunkWorld>().chunks.len(), 27);
+}
+
+/// The level of detail of the chunk at `key`.
+fn lod(app: &App, key: IVec3) -> u32 {
+  let entity = app.world.resource::<ChunkWorld>().chunks[&key];
+  app.world.get::<Chunk>(entity).unwrap().lod
+}
+
+#[test]
+fn takes_the_finest_lod_of_all_loading_points() {
+  let mut app = app();
+  let fine = app.world.query_filtered::<Entity, With<ChunkLoadingPoint>>().single(&app.world);
+  app.world.entity_mut(fine).insert(ChunkLoadingPoint { radius: 8.0, lod_radii: [8.0; 3] });
+  app.world.spawn((Transform::default(), ChunkLoadingPoint { radius: 8.0, lod_radii: [0.0; 3] }));
+  app.update();
+  assert_eq!(lod(&app, IVec3::ZERO), 0);
+  assert_eq!(lod(&app, IVec3::ONE), 3);
+
+  // Chunks follow the fine loading point as it moves away from the coarse one.
+  app.world.get_mut::<Transform>(fine).unwrap().translation = vec3(24.0, 0.0, 0.0);
+  app.update();
+  assert_eq!(lod(&app, IVec3::ZERO), 3);
+  assert_eq!(lod(&app, ivec3(3, 0, 0)), 0);
+}
+
+#[test]
+fn drops_replacements_no_loading_point_covers() {
+  let mut app = app();
+  let stone = Chunk::from_voxels(&[Some(BlockKind(3)); 512], uvec3(8, 8, 8), Vec3::ONE);
+  app.world.resource_mut::<ChunkWorld>().replace_chunk(ivec3(3, 0, 0), stone.clone());
+  app.world.resource_mut::<ChunkWorld>().replace_chunk(ivec3(1, 0, 0), stone);
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(8, 0, 0)), Some(Some(BlockKind(3))));
+
+  // The chunk left out of range is generated when it finally loads.
+  let mut clps = app.world.query_filtered::<&mut Transform, With<ChunkLoadingPoint>>();
+  clps.single_mut(&mut app.world).translation = vec3(24.0, 0.0, 0.0);
+  app.update();
+  assert_eq!(voxel(&mut app, ivec3(24, 0, 0)), Some(None));
+}
+
+#[test]
+fn runs_without_meshes() {
+  let mut app = app();
+  app.update();
+  assert_eq!(app.world.query::<&Handle<Mesh>>().iter(&app.world).count(), 0);
+
+  let diagnostics = app.world.resource::<bevy::diagnostic::Diagnostics>();
+  let value = |id| diagnostics.get(id).and_then(|diagnostic| diagnostic.value());
+  assert_eq!(value(ChunksPlugin::LOADED_CHUNKS), Some(27.0));
+  assert_eq!(value(ChunksPlugin::DIRTY_CHUNKS), Some(0.0));
+  assert_eq!(value(ChunksPlugin::CHUNK_VERTICES), Some(0.0));
+
+  // No material is added without meshing.
+  let chunk_materials = app.world.resource::<ChunkMaterials>();
+  assert!(RenderClass::ALL.iter().all(|class| chunk_materials.standard(*class).is_none()));
+}
+
+#[test]
+fn derives_a_material_per_render_class() {
+  let mut app = App::new();
+  let glass = StandardMaterial { base_color: Color::rgba(0.8, 0.9, 1.0, 0.3), ..default() };
+  app.add_plugins(MinimalPlugins).add_plugin(
+    ChunksPlugin::new(config())
+      .with_material(StandardMaterial { base_color: Color::GREEN, ..default() })
+      .with_class_material(RenderClass::Translucent, glass.clone())
+      .with_meshing(false),
+  );
+
+  let chunk_materials = app.world.resource::<ChunkMaterials>();
+  let opaque = chunk_materials.material(RenderClass::Opaque);
+  assert_eq!((opaque.base_color, opaque.alpha_mode), (Color::GREEN, AlphaMode::Opaque));
+  let cutout = chunk_materials.material(RenderClass::Cutout);
+  assert_eq!((cutout.base_color, cutout.alpha_mode), (Color::GREEN, AlphaMode::Mask(0.5)));
+  // Class materials get the alpha mode of their class too.
+  let translucent = chunk_materials.material(RenderClass::Translucent);
+  assert_eq!(
+    (translucent.base_color, translucent.alpha_mode),
+    (glass.base_color, AlphaMode::Blend)
+  );
+}
+
+/// Like `app`, but with `plugin` meshing chunks, with just enough assets to do so without a
+/// renderer.
+fn meshed_app(plugin: ChunksPlugin) -> App {
+  let mut app = App::new();
+  app
+    .add_plugins(MinimalPlugins)
+    .add_plugin(AssetPlugin::default())
+    .add_plugin(InputPlugin)
+    .add_asset::<Shader>()
+    .add_asset::<Mesh>()
+    .add_asset::<Image>()
+    .add_asset::<StandardMaterial>()
+    .add_plugin(plugin);
+  app.world.spawn((Transform::default(), loading_point()));
+  app.update();
+  app
+}
+
+#[test]
+#[should_panic(expected = "`MeshFormat::Packed` fits chunks of at most 255 blocks per axis")]
+fn rejects_chunks_too_large_to_pack() {
+  let config =
+    ChunkWorldConfig { chunk_size: uvec3(256, 8, 8), mesh_format: MeshFormat::Packed, ..config() };
+  meshed_app(ChunksPlugin::new(config));
+}
+
+/// The faces shown by the block at `pos`.
+fn faces(app: &mut App, pos: IVec3) -> Face {
+  let mut state =
+    SystemState::<(Res<ChunkWorld>, Res<ChunkWorldConfig>, Query<&Chunk>)>::new(&mut app.world);
+  let (chunk_world, chunk_world_config, chunks) = state.get(&app.world);
+  chunk_world.get_block(&chunk_world_config, &chunks, pos).unwrap().activated_faces
+}
+
+#[test]
+fn hides_faces_against_neighbors_that_are_not_remeshed() {
+  let mut app = meshed_app(ChunksPlugin::new(config()).with_generator(Flat(BlockKind(1))));
+  app.update();
+  assert_eq!(faces(&mut app, ivec3(7, -1, 0)), Face::Top);
+
+  // Only the chunk holding the hole is remeshed, while its neighbor along x still hides its border.
+  edit(&mut app, [(ivec3(3, -1, 3), None)]);
+  app.update();
+  assert_eq!(faces(&mut app, ivec3(3, -2, 3)), Face::Top);
+  assert_eq!(faces(&mut app, ivec3(7, -1, 0)), Face::Top);
+  assert_eq!(faces(&mut app, ivec3(7, -2, 0)), Face::empty());
+}
+
+/// Stone below y = 0 under a layer of glass.
+struct Glazed;
+
+impl ChunkGenerator for Glazed {
+  fn generate(&self, pos: IVec3) -> Option<BlockKind> {
+    match pos.y {
+      y if y < 0 => Some(BlockKind(1)),
+      0 => Some(BlockKind(2)),
+      _ => None,
+    }
+  }
+}
+
+#[test]
+fn shares_one_material_per_render_class() {
+  let mut app = meshed_app(
+    ChunksPlugin::new(config())
+      .with_generator(Glazed)
+      .with_render_class(BlockKind(2), RenderClass::Translucent),
+  );
+  app.update();
+
+  let chunk_materials = app.world.resource::<ChunkMaterials>().clone();
+  let handle = |class| chunk_materials.standard(class).unwrap();
+  // One per class, however many chunks are spawned.
+  assert_eq!(app.world.resource::<Assets<StandardMaterial>>().len(), RenderClass::ALL.len());
+
+  // Every chunk has the opaque handle, and its children those of the other classes.
+  let mut chunks =
+    app.world.query_filtered::<(&Handle<StandardMaterial>, &Children), With<Chunk>>();
+  let chunks = chunks.iter(&app.world).map(|(m, c)| (m.clone(), c.to_vec())).collect::<Vec<_>>();
+  assert_eq!(chunks.len(), 27);
+  for (material, children) in chunks {
+    assert_eq!(material, handle(RenderClass::Opaque));
+    let mut child_materials = children
+      .iter()
+      .map(|child| app.world.get::<Handle<StandardMaterial>>(*child).unwrap().clone())
+      .collect::<Vec<_>>();
+    child_materials.sort();
+    let mut expected = vec![handle(RenderClass::Cutout), handle(RenderClass::Translucent)];
+    expected.sort();
+    assert_eq!(child_materials, expected);
+  }
+
+  // The glass is meshed with its class, and does not hide the stone below it.
+  let mut mesh_handles = app.world.query::<(&Handle<Mesh>, &Handle<StandardMaterial>)>();
+  let meshes = app.world.resource::<Assets<Mesh>>();
+  let mut vertices = |class| {
+    mesh_handles
+      .iter(&app.world)
+      .filter(|(_, material)| **material == handle(class))
+      .map(|(mesh, _)| meshes.get(mesh).unwrap().count_vertices())
+      .sum::<usize>()
+  };
+  // The glass shows its top and the sides on the border of the loaded chunks, and the stone its
+  // top through the glass as well as its bottom and sides. Faces have 4 vertices.
+  assert_eq!(vertices(RenderClass::Translucent), (24 * 24 + 4 * 24) * 4);
+  assert_eq!(vertices(RenderClass::Opaque), (2 * 24 * 24 + 4 * 24 * 8) * 4);
+  assert_eq!(vertices(RenderClass::Cutout), 0);
+
+  let mut clps = app.world.query_filtered::<&mut Transform, With<ChunkLoadingPoint>>();
+  clps.single_mut(&mut app.world).translation = vec3(24.0, 0.0, 0.0);
+  app.update();
+  assert_eq!(app.world.resource::<ChunkWorld>().chunks.len(), 27);
+  assert_eq!(app.world.resource::<Assets<StandardMaterial>>().len(), RenderClass::ALL.len());
+  // Unloaded chunks are despawned along with the children holding their other meshes.
+  let mut meshes = app.world.query::<&Handle<StandardMaterial>>();
+  assert_eq!(meshes.iter(&app.world).len(), 27 * RenderClass::ALL.len());
+}
+
+#[test]
+fn overlays_every_render_class() {
+  let mut app = meshed_app(
+    ChunksPlugin::new(config())
+      .with_generator(Glazed)
+      .with_render_class(BlockKind(2), RenderClass::Translucent)
+      .with_debug_render(true),
+  );
+  *app.world.resource_mut::<DebugRender>() = DebugRender::WIREFRAME;
+  app.update();
+
+  // The chunk at the origin only holds the layer of glass, which shows its top. Its sides are
+  // hidden by the glass of the neighboring chunks.
+  let chunk = app.world.resource::<ChunkWorld>().chunks[&IVec3::ZERO];
+  let mut overlays =
+    app.world.query_filtered::<(&Parent, &Handle<Mesh>), With<ChunkDebugOverlay>>();
+  let meshes = app.world.resource::<Assets<Mesh>>();
+  let vertices = overlays
+    .iter(&app.world)
+    .filter(|(parent, _)| parent.get() == chunk)
+    .map(|(_, mesh)| meshes.get(mesh).unwrap().count_vertices())
+    .sum::<usize>();
+  assert_eq!(vertices, 8 * 8 * 4);
+}
@@ -0,0 +1,56 @@
+use bevy::math::{
+  uvec3,
+  vec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkLoadingPoint,
+};
+
+/// An 8-block chunk at `lod`, solid where `solid` says so.
+fn lod_chunk(lod: u32, solid: impl Fn(UVec3) -> bool) -> Chunk {
+  let size = uvec3(8, 8, 8);
+  let voxels = (0..size.x * size.y * size.z)
+    .map(|i| solid(uvec3(i / 64, i / 8 % 8, i % 8)).then_some(BlockKind(1)))
+    .collect::<Vec<_>>();
+  let mut chunk = Chunk::from_voxels(&voxels, size, Vec3::ONE);
+  chunk.lod = lod;
+  chunk
+}
+
+/// The corners of the box around the vertices of `chunk`.
+fn bounds(chunk: &Chunk) -> (Vec3, Vec3) {
+  let positions = chunk.mesh_data().vertex_positions;
+  let min = positions.iter().fold(Vec3::splat(f32::INFINITY), |min, p| min.min(Vec3::from(*p)));
+  let max = positions.iter().fold(Vec3::splat(f32::NEG_INFINITY), |max, p| max.max(Vec3::from(*p)));
+  (min, max)
+}
+
+#[test]
+fn counts_the_lod_radii_passed() {
+  let clp = ChunkLoadingPoint { radius: 64.0, lod_radii: [10.0, 20.0, 30.0] };
+  let lod_at = |x| clp.lod_at(Vec3::ZERO, vec3(x, 0.0, 0.0));
+  assert_eq!([0.0, 10.0, 15.0, 20.0, 25.0, 30.0, 100.0].map(lod_at), [0, 0, 1, 1, 2, 2, 3]);
+  assert_eq!(clp.lod_at(vec3(3.0, 4.0, 0.0), vec3(3.0, 4.0, 12.0)), 1);
+}
+
+#[test]
+fn meshes_the_chunk_surface_in_larger_cells() {
+  assert_eq!(bounds(&lod_chunk(0, |_| true)), (Vec3::splat(-0.5), Vec3::splat(7.5)));
+  for (lod, quads) in [(1, 6 * 16), (2, 6 * 4), (3, 6)] {
+    let chunk = lod_chunk(lod, |_| true);
+    assert_eq!(chunk.mesh_data().vertex_positions.len(), quads * 4, "lod {lod}");
+    assert_eq!(bounds(&chunk), (Vec3::splat(-0.5), Vec3::splat(7.5)), "lod {lod}");
+  }
+}
+
+#[test]
+fn fills_cells_holding_any_solid_block() {
+  let chunk = lod_chunk(1, |pos| pos == uvec3(3, 5, 1));
+  assert_eq!(chunk.mesh_data().vertex_positions.len(), 6 * 4);
+  assert_eq!(bounds(&chunk), (vec3(1.5, 3.5, -0.5), vec3(3.5, 5.5, 1.5)));
+
+  assert!(lod_chunk(2, |_| false).mesh_data().vertex_positions.is_empty());
+}
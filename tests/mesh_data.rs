@@ -0,0 +1,117 @@
+use bevy::math::uvec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  Face,
+};
+use first_voxel_engine::mesh_data::PackedVertex;
+
+#[test]
+fn packs_every_field_at_its_maximum() {
+  let zero = PackedVertex { position: UVec3::ZERO, face: 0, ao: 0, layer: 0 };
+  let max = PackedVertex { position: UVec3::splat(0xff), face: 0b111, ao: 0b11, layer: 0xffff };
+  let vertices = [
+    zero,
+    PackedVertex { position: uvec3(0xff, 0, 0), ..zero },
+    PackedVertex { position: uvec3(0, 0xff, 0), ..zero },
+    PackedVertex { position: uvec3(0, 0, 0xff), ..zero },
+    PackedVertex { face: 0b111, ..zero },
+    PackedVertex { ao: 0b11, ..zero },
+    PackedVertex { layer: 0xffff, ..zero },
+    max,
+  ];
+  for vertex in vertices {
+    assert_eq!(PackedVertex::unpack(vertex.pack()), vertex);
+  }
+  assert_eq!(max.pack(), [0x1fff_ffff, 0xffff]);
+}
+
+/// The normal of each face index, as `assets/shaders/packed_vertex.wgsl` decodes it.
+fn face_normal(face: u32) -> Vec3 {
+  match face {
+    0 => Vec3::Z,
+    1 => Vec3::NEG_Z,
+    2 => Vec3::X,
+    3 => Vec3::NEG_X,
+    4 => Vec3::Y,
+    _ => Vec3::NEG_Y,
+  }
+}
+
+#[test]
+fn packed_vertices_decode_to_the_standard_mesh() {
+  let size = uvec3(5, 4, 3);
+  let voxels = (0..size.x * size.y * size.z)
+    .map(|i| (i % 3 != 0).then_some(BlockKind((i % 7) as u8)))
+    .collect::<Vec<_>>();
+  let block_size = Vec3::new(0.5, 1.0, 2.0);
+  let mesh_data = Chunk::from_voxels(&voxels, size, block_size).mesh_data();
+  let packed = mesh_data.packed_vertices(block_size);
+  assert!(!packed.is_empty());
+  assert_eq!(packed.len(), mesh_data.vertex_positions.len());
+
+  for (i, packed) in packed.iter().enumerate() {
+    let vertex = PackedVertex::unpack(*packed);
+    let decoded = (vertex.position.as_vec3() - 0.5) * block_size;
+    let position = mesh_data.vertex_positions[i];
+    assert!(decoded.abs_diff_eq(Vec3::from(position), 1e-5), "{decoded} != {position:?}");
+    assert_eq!(face_normal(vertex.face), Vec3::from(mesh_data.vertex_normals[i]));
+    assert_eq!(vertex.ao, mesh_data.vertex_ao[i]);
+    assert_eq!(vertex.layer, mesh_data.vertex_layers[i]);
+  }
+}
+
+/// The packed vertices of the faces of `chunk` pointing along `normal`.
+fn faces_along(chunk: &Chunk, normal: Vec3) -> Vec<PackedVertex> {
+  let mesh_data = chunk.mesh_data();
+  mesh_data
+    .packed_vertices(chunk.block_size)
+    .into_iter()
+    .zip(&mesh_data.vertex_normals)
+    .filter(|(_, n)| Vec3::from(**n) == normal)
+    .map(|(packed, _)| PackedVertex::unpack(packed))
+    .collect()
+}
+
+#[test]
+fn carries_the_texture_layer_of_each_block() {
+  let voxels = [Some(BlockKind(3)), None, Some(BlockKind(200))];
+  let chunk = Chunk::from_voxels(&voxels, uvec3(3, 1, 1), Vec3::ONE);
+  let vertices = faces_along(&chunk, Vec3::Y);
+  assert_eq!(vertices.len(), 8);
+  for vertex in vertices {
+    let expected = if vertex.position.x < 2 { 3 } else { 200 };
+    assert_eq!(vertex.layer, expected, "{vertex:?}");
+  }
+}
+
+#[test]
+fn occludes_corners_next_to_blocks_in_front_of_the_face() {
+  let stone = Some(BlockKind(1));
+  // A floor of 3 by 3 blocks, with one block on it at the corner of x = 0 and z = 0.
+  let mut voxels = vec![None; 3 * 2 * 3];
+  for x in 0..3 {
+    for z in 0..3 {
+      voxels[x * 6 + z] = stone;
+    }
+  }
+  voxels[3] = stone;
+  let mut chunk = Chunk::from_voxels(&voxels, uvec3(3, 2, 3), Vec3::ONE);
+  // The block hides the top face of the floor block under it.
+  chunk.blocks[0].activated_faces.remove(Face::Top);
+
+  let ao = |corner: UVec3| {
+    faces_along(&chunk, Vec3::Y)
+      .into_iter()
+      .filter(|vertex| vertex.position == corner)
+      .map(|vertex| vertex.ao)
+      .collect::<Vec<_>>()
+  };
+  // The three floor faces meeting the block's corner have it beside or diagonal to that corner,
+  // and faces away from it are unoccluded.
+  assert_eq!(ao(uvec3(1, 1, 1)), [2, 2, 2]);
+  assert_eq!(ao(uvec3(3, 1, 3)), [3]);
+  // The block's own top face is unoccluded.
+  assert_eq!(ao(uvec3(0, 2, 0)), [3]);
+}
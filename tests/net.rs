@@ -0,0 +1,241 @@
+mod common;
+
+use std::net::{
+  TcpListener,
+  TcpStream,
+};
+use std::time::{
+  Duration,
+  Instant,
+};
+
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  decode_chunk,
+  BlockChanged,
+  BlockKind,
+  ChunkCompression,
+  ChunkWorld,
+  EmptyGenerator,
+};
+use first_voxel_engine::falling::FallingBlocksPlugin;
+use first_voxel_engine::net::{
+  ClientMessage,
+  ClientPlugin,
+  Connection,
+  Server,
+  ServerMessage,
+  ServerPlugin,
+};
+use first_voxel_engine::ticks::{
+  BlockBehavior,
+  TicksPlugin,
+};
+
+use common::{
+  events,
+  loading_point,
+  plugin,
+  voxel,
+  Flat,
+};
+
+/// A headless server on a free local port, and its address.
+fn server() -> (App, String) {
+  let server = ServerPlugin::bind("127.0.0.1:0").unwrap();
+  let addr = server.local_addr().unwrap().to_string();
+  let mut app = App::new();
+  app.add_plugins(MinimalPlugins).add_plugin(plugin(Flat(BlockKind(1)))).add_plugin(server);
+  (app, addr)
+}
+
+/// A headless client loading the 27 chunks around the origin from the server at `addr`.
+fn client(addr: &str) -> App {
+  let mut app = App::new();
+  app
+    .add_plugins(MinimalPlugins)
+    .add_plugin(plugin(EmptyGenerator))
+    .add_plugin(ClientPlugin::connect(addr).unwrap());
+  app.world.spawn((Transform::default(), loading_point()));
+  app
+}
+
+/// Updates every app until `done` holds for the first, failing after a few seconds.
+fn update_until(apps: &mut [&mut App], mut done: impl FnMut(&mut App) -> bool) {
+  let start = Instant::now();
+  loop {
+    for app in apps.iter_mut() {
+      app.update();
+    }
+    if done(apps[0]) {
+      return;
+    }
+    assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+    std::thread::sleep(Duration::from_millis(5));
+  }
+}
+
+#[test]
+fn streams_chunks_to_clients() {
+  let (mut server, addr) = server();
+  let mut client = client(&addr);
+  update_until(&mut [&mut client, &mut server], |client| {
+    voxel(client, ivec3(-8, -8, 15)) == Some(Some(BlockKind(1)))
+  });
+  assert_eq!(server.world.resource::<Server>().client_count(), 1);
+  assert_eq!(voxel(&mut client, ivec3(0, -1, 0)), Some(Some(BlockKind(1))));
+  assert_eq!(voxel(&mut client, ivec3(0, 0, 0)), Some(None));
+}
+
+#[test]
+fn sends_client_edits_to_the_server() {
+  let (mut server, addr) = server();
+  let mut client = client(&addr);
+  update_until(&mut [&mut client, &mut server], |client| {
+    voxel(client, ivec3(0, -1, 0)) == Some(Some(BlockKind(1)))
+  });
+
+  client.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(1, 2, 3), Some(BlockKind(5)))]);
+  update_until(&mut [&mut server, &mut client], |server| {
+    voxel(server, ivec3(1, 2, 3)) == Some(Some(BlockKind(5)))
+  });
+}
+
+#[test]
+fn broadcasts_edits_to_other_clients() {
+  let (mut server, addr) = server();
+  let mut first = client(&addr);
+  let mut second = client(&addr);
+  update_until(&mut [&mut first, &mut second, &mut server], |first| {
+    voxel(first, ivec3(0, -1, 0)) == Some(Some(BlockKind(1)))
+  });
+  update_until(&mut [&mut second, &mut first, &mut server], |second| {
+    voxel(second, ivec3(0, -1, 0)) == Some(Some(BlockKind(1)))
+  });
+
+  first.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, -1, 0), None)]);
+  update_until(&mut [&mut second, &mut first, &mut server], |second| {
+    voxel(second, ivec3(0, -1, 0)) == Some(None)
+  });
+  // The edit is not undoable on the client that only received it.
+  second.world.resource_mut::<ChunkWorld>().undo();
+  second.update();
+  second.update();
+  assert_eq!(voxel(&mut second, ivec3(0, -1, 0)), Some(None));
+}
+
+#[test]
+fn leaves_ticks_to_the_server() {
+  let (mut server, addr) = server();
+  let mut client = client(&addr);
+  let vanish = BlockBehavior::default().on_random_tick(|tick| tick.set_block(tick.pos, None));
+  client.add_plugin(
+    TicksPlugin::new().with_random_ticks_per_chunk(64).with_behavior(BlockKind(1), vanish),
+  );
+  update_until(&mut [&mut client, &mut server], |client| {
+    voxel(client, ivec3(0, -1, 0)) == Some(Some(BlockKind(1)))
+  });
+
+  client.world.run_schedule(CoreSchedule::FixedUpdate);
+  client.update();
+  assert!(events::<BlockChanged>(&client).is_empty());
+}
+
+#[test]
+fn leaves_falls_to_the_server() {
+  let (mut server, addr) = server();
+  let mut client = client(&addr);
+  client.add_plugin(FallingBlocksPlugin::new([BlockKind(2)]));
+  update_until(&mut [&mut client, &mut server], |client| {
+    voxel(client, ivec3(0, -1, 0)) == Some(Some(BlockKind(1)))
+  });
+
+  // The server has no falling blocks, so the block stays up on both.
+  client.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, 5, 0), Some(BlockKind(2)))]);
+  update_until(&mut [&mut server, &mut client], |server| {
+    voxel(server, ivec3(0, 5, 0)) == Some(Some(BlockKind(2)))
+  });
+  client.update();
+  assert_eq!(voxel(&mut client, ivec3(0, 5, 0)), Some(Some(BlockKind(2))));
+  assert_eq!(voxel(&mut client, ivec3(0, 0, 0)), Some(None));
+}
+
+/// A bare connection to the server at `addr` that asked for the chunks around the origin.
+fn raw_client(addr: &str) -> Connection {
+  let mut connection = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+  connection.send(&ClientMessage::LoadingPoint { translation: Vec3::ZERO, radius: 8.0 });
+  connection.flush().unwrap();
+  connection
+}
+
+fn is_origin_chunk(message: &ServerMessage) -> bool {
+  matches!(message, ServerMessage::Chunk { key, .. } if *key == IVec3::ZERO)
+}
+
+fn is_blocks(message: &ServerMessage) -> bool { matches!(message, ServerMessage::Blocks(_)) }
+
+#[test]
+fn does_not_echo_edits_to_their_sender() {
+  let (mut server, addr) = server();
+  let mut clients = [raw_client(&addr), raw_client(&addr)];
+  let mut messages = [Vec::new(), Vec::new()];
+  // Updates the server until the second client received a message `done` holds for.
+  let mut receive = |clients: &mut [Connection; 2], done: fn(&ServerMessage) -> bool| {
+    update_until(&mut [&mut server], |_| {
+      for (connection, messages) in clients.iter_mut().zip(&mut messages) {
+        messages.extend(connection.receive::<ServerMessage>().unwrap());
+      }
+      messages[1].iter().any(done)
+    });
+    messages.clone()
+  };
+  receive(&mut clients, is_origin_chunk);
+
+  let edits = vec![(ivec3(1, 2, 3), Some(BlockKind(5)))];
+  clients[0].send(&ClientMessage::SetBlocks(edits.clone()));
+  clients[0].flush().unwrap();
+  let [first, second] = receive(&mut clients, is_blocks);
+  assert_eq!(
+    second.into_iter().filter(is_blocks).collect::<Vec<_>>(),
+    [ServerMessage::Blocks(edits)]
+  );
+  assert!(!first.iter().any(is_blocks));
+}
+
+#[test]
+fn streams_chunks_with_the_streaming_compression() {
+  let (mut server, addr) = server();
+  let mut client = raw_client(&addr);
+  let is_ground_chunk =
+    |message: &ServerMessage| matches!(message, ServerMessage::Chunk { key, .. } if key.y == -1);
+  let mut messages = Vec::new();
+  update_until(&mut [&mut server], |_| {
+    messages.extend(client.receive::<ServerMessage>().unwrap());
+    messages.iter().any(is_ground_chunk)
+  });
+
+  let Some(ServerMessage::Chunk { data, .. }) = messages.into_iter().find(is_ground_chunk) else {
+    unreachable!()
+  };
+  // The compression byte follows the three size axes of the header.
+  assert_eq!(data[20], ChunkCompression::STREAMING as u8);
+  let chunk = decode_chunk(&data, Vec3::ONE).unwrap();
+  assert!(chunk.blocks.iter().all(|block| block.voxel() == Some(BlockKind(1))));
+}
+
+#[test]
+fn drops_peers_that_stop_reading() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let _peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+  let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+  let message = ServerMessage::Chunk { key: IVec3::ZERO, data: vec![0; 1 << 20] };
+  // The peer never reads, so only the socket buffers and the queue take the messages.
+  let flushed = (0..256)
+    .take_while(|_| {
+      connection.send(&message);
+      connection.flush().is_ok()
+    })
+    .count();
+  assert!((64..256).contains(&flushed), "{flushed}");
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use bevy::math::{
+  ivec3,
+  uvec3,
+};
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockKind,
+  Chunk,
+  ChunkWorld,
+};
+use first_voxel_engine::pathfinding::{
+  find_path,
+  smooth_path,
+  Path,
+  PathAgent,
+  PathGoal,
+  PathfindingPlugin,
+};
+
+use common::{
+  loading_point,
+  plugin,
+  Flat,
+};
+
+const STONE: BlockKind = BlockKind(1);
+const SIZE: u32 = 16;
+
+/// A 16x16x16 chunk at the origin with a stone floor at y = 0 and the blocks `solid` picks.
+fn chunk(solid: impl Fn(IVec3) -> bool) -> Chunk {
+  let mut voxels = Vec::new();
+  for x in 0..SIZE as i32 {
+    for y in 0..SIZE as i32 {
+      for z in 0..SIZE as i32 {
+        voxels.push((y == 0 || solid(ivec3(x, y, z))).then_some(STONE));
+      }
+    }
+  }
+  Chunk::from_voxels(&voxels, uvec3(SIZE, SIZE, SIZE), Vec3::ONE)
+}
+
+/// Reads `chunk`, with everything around it unloaded.
+fn reader(chunk: &Chunk) -> impl Fn(IVec3) -> Option<Option<BlockKind>> + Copy + '_ {
+  move |pos: IVec3| {
+    let pos = pos.to_array().map(usize::try_from);
+    let [Ok(x), Ok(y), Ok(z)] = pos else { return None };
+    chunk.get_block(x, y, z).map(|block| block.voxel())
+  }
+}
+
+#[test]
+fn walks_straight_across_flat_ground() {
+  let chunk = chunk(|_| false);
+  let agent = PathAgent::new();
+  let path = find_path(reader(&chunk), &agent, ivec3(1, 1, 1), ivec3(12, 1, 5), 1000).unwrap();
+  assert_eq!(path.len(), 11 + 4 + 1);
+  assert!(path.windows(2).all(|pair| (pair[1] - pair[0]).abs().dot(IVec3::ONE) == 1));
+  assert_eq!(smooth_path(reader(&chunk), &agent, &path), [ivec3(1, 1, 1), ivec3(12, 1, 5)]);
+}
+
+#[test]
+fn goes_around_walls() {
+  // A wall at x = 6 with a gap at z = 12.
+  let chunk = chunk(|pos| pos.x == 6 && pos.z != 12);
+  let agent = PathAgent::new();
+  let (start, goal) = (ivec3(2, 1, 2), ivec3(10, 1, 2));
+  let path = find_path(reader(&chunk), &agent, start, goal, 10_000).unwrap();
+  assert!(path.contains(&ivec3(6, 1, 12)));
+  assert_eq!(path.len(), 8 + 2 * 10 + 1);
+
+  // Smoothing cuts the corners without going through the wall.
+  let waypoints = smooth_path(reader(&chunk), &agent, &path);
+  assert!(waypoints.len() > 2 && waypoints.len() < path.len());
+  assert_eq!((waypoints[0], *waypoints.last().unwrap()), (start, goal));
+  assert!(waypoints.iter().all(|pos| pos.x != 6 || pos.z == 12));
+}
+
+#[test]
+fn climbs_within_step_up_and_jump_height() {
+  // A two-block ledge at x >= 8.
+  let chunk = chunk(|pos| pos.x >= 8 && pos.y <= 2);
+  let (start, goal) = (ivec3(2, 1, 2), ivec3(12, 3, 2));
+  assert_eq!(find_path(reader(&chunk), &PathAgent::new(), start, goal, 10_000), None);
+
+  let jumper = PathAgent::new().with_jump_height(2);
+  let path = find_path(reader(&chunk), &jumper, start, goal, 10_000).unwrap();
+  assert!(path.windows(2).any(|pair| pair[1] - pair[0] == ivec3(1, 2, 0)));
+  assert!(
+    find_path(reader(&chunk), &PathAgent::new().with_step_up(2), start, goal, 10_000).is_some()
+  );
+
+  // Stairs only need a step up of 1.
+  let stairs = self::chunk(|pos| pos.x >= 8 && pos.y <= 1 || pos.x >= 9 && pos.y <= 2);
+  assert!(find_path(reader(&stairs), &PathAgent::new(), start, goal, 10_000).is_some());
+}
+
+#[test]
+fn drops_no_deeper_than_max_drop() {
+  // A pit at x >= 8, four blocks below a platform.
+  let chunk = chunk(|pos| pos.x < 8 && pos.y <= 4);
+  let (start, goal) = (ivec3(2, 5, 2), ivec3(12, 1, 2));
+  assert_eq!(find_path(reader(&chunk), &PathAgent::new(), start, goal, 10_000), None);
+  let path =
+    find_path(reader(&chunk), &PathAgent::new().with_max_drop(4), start, goal, 10_000).unwrap();
+  assert!(path.contains(&ivec3(8, 1, 2)));
+  // There is no way back up.
+  assert_eq!(find_path(reader(&chunk), &PathAgent::new(), goal, start, 10_000), None);
+}
+
+#[test]
+fn needs_room_for_the_agent_height() {
+  // A ceiling at y = 2 over a tunnel at z = 2, under a wall at x = 6.
+  let chunk = chunk(|pos| pos.x == 6 && (pos.z != 2 || pos.y >= 2));
+  let (start, goal) = (ivec3(2, 1, 2), ivec3(10, 1, 2));
+  assert_eq!(find_path(reader(&chunk), &PathAgent::new(), start, goal, 10_000), None);
+  let path =
+    find_path(reader(&chunk), &PathAgent::new().with_height(1), start, goal, 10_000).unwrap();
+  assert_eq!(path.len(), 9);
+}
+
+#[test]
+fn gives_up_after_max_nodes() {
+  let chunk = chunk(|_| false);
+  let agent = PathAgent::new();
+  assert_eq!(find_path(reader(&chunk), &agent, ivec3(0, 1, 0), ivec3(15, 1, 15), 20), None);
+  assert!(find_path(reader(&chunk), &agent, ivec3(0, 1, 0), ivec3(15, 1, 15), 10_000).is_some());
+}
+
+fn path(app: &mut App) -> Option<Path> {
+  app.world.query::<&Path>().iter(&app.world).next().cloned()
+}
+
+#[test]
+fn searches_again_when_blocks_change() {
+  let mut app = common::app(plugin(Flat(STONE)), |app| {
+    app.add_plugin(PathfindingPlugin::new());
+  });
+  app.world.spawn((
+    SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 0.0)),
+    PathAgent::new(),
+    PathGoal(ivec3(6, 0, 0)),
+  ));
+  app.update();
+  assert_eq!(path(&mut app).unwrap().waypoints, [ivec3(0, 0, 0), ivec3(6, 0, 0)]);
+
+  // A change away from the path keeps it.
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(3, 0, 5), Some(STONE))]);
+  app.update();
+  assert!(path(&mut app).is_some());
+
+  // A wall across the loaded chunks blocks it.
+  let wall = (-8..16).flat_map(|z| [0, 1].map(|y| (ivec3(3, y, z), Some(STONE))));
+  app.world.resource_mut::<ChunkWorld>().set_blocks(wall);
+  app.update();
+  assert!(path(&mut app).is_none());
+  app.update();
+  assert!(!path(&mut app).unwrap().found());
+
+  // An opening lets it through again, around the wall.
+  app
+    .world
+    .resource_mut::<ChunkWorld>()
+    .set_blocks([(ivec3(3, 0, 4), None), (ivec3(3, 1, 4), None)]);
+  app.update();
+  app.update();
+  let waypoints = path(&mut app).unwrap().waypoints;
+  assert!(waypoints.len() > 2);
+  assert_eq!(*waypoints.last().unwrap(), ivec3(6, 0, 0));
+}
+
+#[test]
+fn searches_again_when_chunks_load() {
+  let mut app = common::app(plugin(Flat(STONE)), |app| {
+    app.add_plugin(PathfindingPlugin::new());
+  });
+  // The goal lies past the loaded chunks, which end at x = 15.
+  app.world.spawn((
+    SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 0.0)),
+    PathAgent::new(),
+    PathGoal(ivec3(20, 0, 0)),
+  ));
+  app.update();
+  assert!(!path(&mut app).unwrap().found());
+
+  app.world.spawn((Transform::from_xyz(24.0, 0.0, 0.0), loading_point()));
+  app.update();
+  app.update();
+  let waypoints = path(&mut app).unwrap().waypoints;
+  assert_eq!(waypoints, [ivec3(0, 0, 0), ivec3(20, 0, 0)]);
+}
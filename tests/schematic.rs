@@ -0,0 +1,89 @@
+use bevy::math::uvec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::BlockKind;
+use first_voxel_engine::schematic::{
+  Axis,
+  Schematic,
+};
+use rand::rngs::StdRng;
+use rand::{
+  Rng,
+  SeedableRng,
+};
+
+/// A schematic of `size` holding a distinct kind at each position, so that every block can be
+/// told apart after moving it.
+fn numbered(size: UVec3) -> Schematic {
+  let len = (size.x * size.y * size.z) as usize;
+  Schematic { size, blocks: (0..len).map(|i| Some(BlockKind(i as u8))).collect() }
+}
+
+fn block(schematic: &Schematic, pos: UVec3) -> Option<BlockKind> {
+  let size = schematic.size;
+  schematic.blocks[(pos.x * size.y * size.z + pos.y * size.z + pos.z) as usize]
+}
+
+#[test]
+fn rotates_around_y() {
+  let schematic = numbered(uvec3(3, 2, 4));
+  let rotated = schematic.rotate_y(1);
+  assert_eq!(rotated.size, uvec3(4, 2, 3));
+  // The +x edge moves to the -z edge, and the +z edge to the +x edge.
+  assert_eq!(block(&rotated, uvec3(0, 1, 0)), block(&schematic, uvec3(2, 1, 0)));
+  assert_eq!(block(&rotated, uvec3(3, 0, 0)), block(&schematic, uvec3(2, 0, 3)));
+  assert_eq!(block(&rotated, uvec3(3, 0, 2)), block(&schematic, uvec3(0, 0, 3)));
+
+  assert_eq!(schematic.rotate_y(2), rotated.rotate_y(1));
+  assert_eq!(schematic.rotate_y(3), rotated.rotate_y(2));
+  assert_eq!(schematic.rotate_y(4), schematic);
+  assert_eq!(schematic.rotate_y(0), schematic);
+  assert_eq!(rotated.rotate_y(3), schematic);
+}
+
+#[test]
+fn mirrors_along_each_axis() {
+  let schematic = numbered(uvec3(3, 2, 4));
+  for (axis, pos, mirrored) in [
+    (Axis::X, uvec3(0, 1, 2), uvec3(2, 1, 2)),
+    (Axis::Y, uvec3(0, 1, 2), uvec3(0, 0, 2)),
+    (Axis::Z, uvec3(0, 1, 2), uvec3(0, 1, 1)),
+  ] {
+    let mirror = schematic.mirror(axis);
+    assert_eq!(mirror.size, schematic.size);
+    assert_eq!(block(&mirror, mirrored), block(&schematic, pos), "{axis:?}");
+    assert_eq!(mirror.mirror(axis), schematic, "{axis:?}");
+  }
+
+  // Two mirrors across the horizontal axes are a half turn.
+  assert_eq!(schematic.mirror(Axis::X).mirror(Axis::Z), schematic.rotate_y(2));
+}
+
+#[test]
+fn reads_what_it_writes() {
+  let mut rng = StdRng::seed_from_u64(0);
+  for _ in 0..50 {
+    let size = uvec3(rng.gen_range(0..12), rng.gen_range(0..12), rng.gen_range(0..12));
+    let len = (size.x * size.y * size.z) as usize;
+    // Long runs of air among a few kinds.
+    let blocks =
+      (0..len).map(|_| rng.gen_bool(0.3).then(|| BlockKind(rng.gen_range(250..=255)))).collect();
+    let schematic = Schematic { size, blocks };
+
+    let mut bytes = Vec::new();
+    schematic.write(&mut bytes).unwrap();
+    assert_eq!(Schematic::read(&mut bytes.as_slice()).unwrap(), schematic);
+    assert!(Schematic::read(&mut &bytes[..bytes.len() - 1]).is_err());
+  }
+}
+
+#[test]
+fn rejects_oversized_headers() {
+  let mut bytes = Vec::new();
+  numbered(uvec3(2, 2, 2)).write(&mut bytes).unwrap();
+  for size in [[u32::MAX; 3], [1 << 16, 1 << 16, 1 << 16], [1 << 12, 1 << 12, 2]] {
+    for (i, axis) in size.into_iter().enumerate() {
+      bytes[5 + i * 4..9 + i * 4].copy_from_slice(&axis.to_le_bytes());
+    }
+    assert!(Schematic::read(&mut bytes.as_slice()).is_err(), "{size:?}");
+  }
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use bevy::math::ivec3;
+use bevy::prelude::*;
+use first_voxel_engine::chunks::{
+  BlockChanged,
+  BlockKind,
+  ChunkWorld,
+};
+use first_voxel_engine::ticks::{
+  BlockBehavior,
+  ScheduledTicks,
+  TicksPlugin,
+};
+
+use common::{
+  events,
+  plugin,
+  voxel,
+  Flat,
+};
+
+const GRASS: BlockKind = BlockKind(1);
+const DIRT: BlockKind = BlockKind(2);
+const FUSE: BlockKind = BlockKind(3);
+
+/// A headless app with the 27 chunks around the origin loaded.
+fn app(ticks: TicksPlugin) -> App {
+  common::app(plugin(Flat(DIRT)), |app| {
+    // Ticks only run when a test calls `tick`.
+    app.add_plugin(ticks).insert_resource(FixedTime::new_from_secs(3600.0));
+  })
+}
+
+/// Runs one tick and applies its changes, returning them.
+fn tick(app: &mut App) -> Vec<BlockChanged> {
+  app.world.run_schedule(CoreSchedule::FixedUpdate);
+  app.update();
+  events(app)
+}
+
+/// Grass turns the dirt under it into grass.
+fn grass() -> BlockBehavior {
+  BlockBehavior::default().on_random_tick(|tick| {
+    let below = tick.pos - IVec3::Y;
+    if tick.get_block(below) == Some(Some(DIRT)) {
+      tick.set_block(below, Some(GRASS));
+    }
+  })
+}
+
+#[test]
+fn random_ticks_only_reach_blocks_with_behaviors() {
+  let mut app = app(TicksPlugin::new().with_random_ticks_per_chunk(64).with_behavior(
+    DIRT,
+    BlockBehavior::default().on_random_tick(|tick| tick.set_block(tick.pos, None)),
+  ));
+  let changes = tick(&mut app);
+  // Only the 9 chunks below y = 0 hold dirt, and picks may repeat.
+  assert!(!changes.is_empty() && changes.len() <= 9 * 64, "{} changes", changes.len());
+  assert!(changes.iter().all(|change| change.before == Some(DIRT) && change.after.is_none()));
+  assert!(changes.iter().all(|change| change.remote && change.pos.y < 0));
+
+  // Tick changes are not undoable.
+  app.world.resource_mut::<ChunkWorld>().undo();
+  app.update();
+  assert_eq!(voxel(&mut app, changes[0].pos), Some(None));
+}
+
+#[test]
+fn random_ticks_spread_blocks() {
+  let mut app =
+    app(TicksPlugin::new().with_random_ticks_per_chunk(512).with_behavior(GRASS, grass()));
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, -1, 0), Some(GRASS))]);
+  app.update();
+
+  let mut spread = 0;
+  for _ in 0..200 {
+    spread += tick(&mut app).len();
+    if voxel(&mut app, ivec3(0, -3, 0)) == Some(Some(GRASS)) {
+      break;
+    }
+  }
+  assert_eq!(voxel(&mut app, ivec3(0, -2, 0)), Some(Some(GRASS)));
+  assert_eq!(voxel(&mut app, ivec3(0, -3, 0)), Some(Some(GRASS)));
+  assert!(spread >= 2);
+  assert_eq!(voxel(&mut app, ivec3(1, -2, 0)), Some(Some(DIRT)));
+}
+
+#[test]
+fn scheduled_ticks_run_when_due() {
+  // A fuse burns out after 2 ticks and lights the fuse next to it.
+  let fuse = BlockBehavior::default().on_scheduled_tick(|tick| {
+    tick.set_block(tick.pos, None);
+    if tick.get_block(tick.pos + IVec3::X) == Some(Some(FUSE)) {
+      tick.schedule(tick.pos + IVec3::X, 2);
+    }
+  });
+  let mut app = app(TicksPlugin::new().with_random_ticks_per_chunk(0).with_behavior(FUSE, fuse));
+  app.world.resource_mut::<ChunkWorld>().set_blocks((0..3).map(|x| (ivec3(x, 0, 0), Some(FUSE))));
+  app.update();
+  app.world.resource_mut::<ScheduledTicks>().schedule(ivec3(0, 0, 0), 2);
+
+  assert!(tick(&mut app).is_empty());
+  assert_eq!(tick(&mut app).iter().map(|change| change.pos).collect::<Vec<_>>(), [ivec3(0, 0, 0)]);
+  assert!(tick(&mut app).is_empty());
+  assert_eq!(tick(&mut app).iter().map(|change| change.pos).collect::<Vec<_>>(), [ivec3(1, 0, 0)]);
+  assert!(tick(&mut app).is_empty());
+  assert_eq!(tick(&mut app).iter().map(|change| change.pos).collect::<Vec<_>>(), [ivec3(2, 0, 0)]);
+  assert!(app.world.resource::<ScheduledTicks>().is_empty());
+  assert_eq!(app.world.resource::<ScheduledTicks>().tick(), 6);
+}
+
+#[test]
+fn drops_scheduled_ticks_of_replaced_blocks() {
+  let fuse = BlockBehavior::default().on_scheduled_tick(|tick| tick.set_block(tick.pos, None));
+  let mut app = app(TicksPlugin::new().with_random_ticks_per_chunk(0).with_behavior(FUSE, fuse));
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, 0, 0), Some(FUSE))]);
+  app.update();
+  app.world.resource_mut::<ScheduledTicks>().schedule(ivec3(0, 0, 0), 2);
+  app.world.resource_mut::<ChunkWorld>().set_blocks([(ivec3(0, 0, 0), Some(DIRT))]);
+  app.update();
+
+  assert!(tick(&mut app).is_empty());
+  assert!(tick(&mut app).is_empty());
+  assert_eq!(voxel(&mut app, ivec3(0, 0, 0)), Some(Some(DIRT)));
+}
@@ -0,0 +1,109 @@
+use std::io;
+
+use bevy::math::{
+  ivec3,
+  uvec3,
+};
+use first_voxel_engine::chunks::BlockKind;
+use first_voxel_engine::vox::{
+  default_palette,
+  VoxFile,
+};
+
+fn fixture(name: &str) -> VoxFile {
+  VoxFile::load(format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR")).as_ref()).unwrap()
+}
+
+fn round_trip(vox_file: &VoxFile) -> VoxFile {
+  let mut bytes = Vec::new();
+  vox_file.write(&mut bytes).unwrap();
+  VoxFile::read(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn reads_models_in_y_up_axes() {
+  let vox_file = fixture("single.vox");
+  assert_eq!(vox_file.models.len(), 1);
+  let model = &vox_file.models[0];
+  assert_eq!(model.size, uvec3(2, 4, 3));
+  assert_eq!(model.position, ivec3(0, 0, 0));
+  assert_eq!(
+    model.voxels,
+    [
+      (uvec3(0, 0, 2), BlockKind(0)),
+      (uvec3(1, 3, 0), BlockKind(4)),
+      (uvec3(1, 2, 2), BlockKind(254)),
+    ]
+  );
+}
+
+#[test]
+fn maps_palette_entries_to_block_kinds() {
+  // The fixture's color index `i + 1` is `[i, 255 - i, i / 2, 255]`.
+  let palette = fixture("single.vox").palette;
+  assert_eq!(palette[0], [0, 255, 0, 255]);
+  assert_eq!(palette[4], [4, 251, 2, 255]);
+  assert_eq!(palette[254], [254, 1, 127, 255]);
+
+  // Files without a palette get the default one.
+  let default = default_palette();
+  assert_eq!(fixture("scene.vox").palette, default);
+  assert_eq!(default[0], [0xff, 0xff, 0xff, 0xff]);
+  assert_eq!(default[214], [0, 0, 0x33, 0xff]);
+  assert_eq!(default[254], [0x11, 0x11, 0x11, 0xff]);
+}
+
+#[test]
+fn places_models_by_their_translations() {
+  let vox_file = fixture("scene.vox");
+  assert_eq!(vox_file.models.len(), 2);
+  let [first, second] = [&vox_file.models[0], &vox_file.models[1]];
+  assert_eq!((first.size, first.position), (uvec3(1, 1, 1), ivec3(0, 0, 0)));
+  assert_eq!((second.size, second.position), (uvec3(3, 2, 1), ivec3(9, 6, 4)));
+  assert_eq!(second.voxels, [(uvec3(2, 1, 0), BlockKind(19))]);
+}
+
+#[test]
+fn writes_what_it_reads() {
+  for name in ["single.vox", "scene.vox"] {
+    let vox_file = fixture(name);
+    assert_eq!(round_trip(&vox_file), vox_file, "{name}");
+  }
+
+  // Models at arbitrary positions keep their layout.
+  let mut vox_file = fixture("scene.vox");
+  vox_file.models.push(vox_file.models[1].clone());
+  vox_file.models[1].position = ivec3(3, 0, 17);
+  vox_file.models[2].position = ivec3(0, 5, 2);
+  assert_eq!(round_trip(&vox_file), vox_file);
+}
+
+#[test]
+fn rejects_models_larger_than_magicavoxel_allows() {
+  let mut vox_file = fixture("single.vox");
+  vox_file.models[0].size = uvec3(2, 257, 3);
+  assert!(vox_file.write(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn rejects_voxels_outside_their_model() {
+  let mut vox_file = fixture("single.vox");
+  let size = vox_file.models[0].size;
+  vox_file.models[0].voxels.push((uvec3(0, 0, size.z), BlockKind(0)));
+  let error = vox_file.write(&mut Vec::new()).unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn rejects_reading_voxels_outside_their_model() {
+  let path = format!("{}/tests/fixtures/single.vox", env!("CARGO_MANIFEST_DIR"));
+  let bytes = std::fs::read(path).unwrap();
+  // The first voxel follows the XYZI chunk's id, its two lengths and the voxel count.
+  let first_voxel = bytes.windows(4).position(|id| id == b"XYZI").unwrap() + 16;
+  for axis in 0..3 {
+    let mut bytes = bytes.clone();
+    bytes[first_voxel + axis] = 255;
+    let error = VoxFile::read(&mut bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData, "axis {axis}");
+  }
+}